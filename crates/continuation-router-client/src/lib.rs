@@ -0,0 +1,30 @@
+//! Builds continuation router instructions from route plans.
+#![deny(clippy::unwrap_used)]
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+
+use anchor_lang::prelude::*;
+
+pub mod lookup_table;
+pub mod message;
+pub mod route;
+
+pub use lookup_table::{route_addresses, AddressLookupTableAccount};
+pub use message::compile_v0_message;
+pub use route::{
+    find_router_config_address, route_instructions, route_message, simulate_route_instruction,
+    simulation_results, RouteParams,
+};
+
+/// Errors.
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Route has too many steps.")]
+    TooManySteps,
+    #[msg("Transaction references more than 256 accounts.")]
+    TooManyAccounts,
+    #[msg("Account is not an initialized address lookup table.")]
+    InvalidLookupTable,
+    #[msg("Instruction references an account which is not in the message.")]
+    AccountNotInMessage,
+}
@@ -0,0 +1,238 @@
+//! Address lookup tables of routes.
+//!
+//! The address lookup table program is not part of the Solana version this
+//! crate builds against, so its instructions and account layout are mirrored here.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use continuation_router_planner::{
+    Plan, PlanAction, Pool, StableSwapAccounts, SwapTokenAccounts, WrapperAccounts,
+};
+
+use crate::{find_router_config_address, ErrorCode};
+
+/// The address lookup table program.
+pub mod program {
+    anchor_lang::declare_id!("AddressLookupTab1e1111111111111111111111111");
+}
+
+/// Size of the metadata preceding the addresses of a lookup table account.
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Maximum number of addresses added by a single `extend_lookup_table` instruction
+/// such that the transaction fits within the packet size.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+/// An on-chain address lookup table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressLookupTableAccount {
+    /// Address of the table.
+    pub key: Pubkey,
+    /// Addresses stored in the table.
+    pub addresses: Vec<Pubkey>,
+}
+
+impl AddressLookupTableAccount {
+    /// Deserializes the data of a lookup table account.
+    pub fn deserialize(key: Pubkey, data: &[u8]) -> Result<Self> {
+        let addresses = data
+            .get(LOOKUP_TABLE_META_SIZE..)
+            .ok_or_else(|| error!(ErrorCode::InvalidLookupTable))?;
+        if data[..4] != 1u32.to_le_bytes() || addresses.len() % 32 != 0 {
+            return Err(error!(ErrorCode::InvalidLookupTable));
+        }
+        Ok(AddressLookupTableAccount {
+            key,
+            addresses: addresses.chunks_exact(32).map(Pubkey::new).collect(),
+        })
+    }
+}
+
+/// Finds the address of the lookup table created by `authority` at `recent_slot`.
+pub fn find_lookup_table_address(authority: Pubkey, recent_slot: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[authority.as_ref(), &recent_slot.to_le_bytes()],
+        &program::ID,
+    )
+}
+
+/// Creates a lookup table owned by `authority`.
+///
+/// `recent_slot` must be a recent slot, e.g. the slot of the latest finalized block.
+pub fn create_lookup_table(
+    authority: Pubkey,
+    payer: Pubkey,
+    recent_slot: u64,
+) -> (Instruction, Pubkey) {
+    let (lookup_table, bump_seed) = find_lookup_table_address(authority, recent_slot);
+    let mut data = 0u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&recent_slot.to_le_bytes());
+    data.push(bump_seed);
+    let instruction = Instruction {
+        program_id: program::ID,
+        accounts: vec![
+            AccountMeta::new(lookup_table, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    };
+    (instruction, lookup_table)
+}
+
+/// Adds `addresses` to a lookup table, with `payer` funding the extra rent.
+pub fn extend_lookup_table(
+    lookup_table: Pubkey,
+    authority: Pubkey,
+    payer: Pubkey,
+    addresses: &[Pubkey],
+) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&(addresses.len() as u64).to_le_bytes());
+    for address in addresses {
+        data.extend_from_slice(address.as_ref());
+    }
+    Instruction {
+        program_id: program::ID,
+        accounts: vec![
+            AccountMeta::new(lookup_table, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    }
+}
+
+/// Builds the instructions to create a lookup table containing the
+/// addresses used when routing through `pools`.
+///
+/// The first instruction creates the table, and each following instruction
+/// extends it and should be sent in its own transaction.
+pub fn create_pools_lookup_table(
+    authority: Pubkey,
+    payer: Pubkey,
+    recent_slot: u64,
+    pools: &[Pool],
+) -> (Vec<Instruction>, Pubkey) {
+    let (create, lookup_table) = create_lookup_table(authority, payer, recent_slot);
+    let mut instructions = vec![create];
+    instructions.extend(extend_pools_lookup_table(
+        lookup_table,
+        authority,
+        payer,
+        &[],
+        pools,
+    ));
+    (instructions, lookup_table)
+}
+
+/// Builds the instructions to add the addresses used when routing through
+/// `pools` to an existing lookup table. Addresses already in the table are skipped.
+pub fn extend_pools_lookup_table(
+    lookup_table: Pubkey,
+    authority: Pubkey,
+    payer: Pubkey,
+    existing_addresses: &[Pubkey],
+    pools: &[Pool],
+) -> Vec<Instruction> {
+    let mut addresses = common_addresses();
+    for pool in pools {
+        match pool {
+            Pool::StableSwap(pool) => add_all(&mut addresses, stable_swap_addresses(pool)),
+            Pool::AddDecimals(wrapper) => add_all(&mut addresses, wrapper_addresses(wrapper)),
+        }
+    }
+    addresses.retain(|address| !existing_addresses.contains(address));
+    addresses
+        .chunks(MAX_ADDRESSES_PER_EXTEND)
+        .map(|chunk| extend_lookup_table(lookup_table, authority, payer, chunk))
+        .collect()
+}
+
+/// The addresses used by a route which are the same for every user.
+///
+/// These are the router config, programs, sysvars, and the accounts of each pool.
+/// Token accounts of the user and the continuation are not included.
+pub fn route_addresses(plan: &Plan) -> Vec<Pubkey> {
+    let mut addresses = common_addresses();
+    for step in &plan.steps {
+        match step.action {
+            PlanAction::SSSwap { pool, .. }
+            | PlanAction::SSWithdrawOne { pool, .. }
+            | PlanAction::SSDepositA { pool }
+            | PlanAction::SSDepositB { pool } => {
+                add_all(&mut addresses, stable_swap_addresses(pool))
+            }
+            PlanAction::ADWithdraw { wrapper } | PlanAction::ADDeposit { wrapper } => {
+                add_all(&mut addresses, wrapper_addresses(wrapper))
+            }
+        }
+    }
+    addresses
+}
+
+/// Addresses used by all routes.
+fn common_addresses() -> Vec<Pubkey> {
+    vec![
+        find_router_config_address(),
+        anchor_spl::token::ID,
+        anchor_spl::associated_token::ID,
+        sysvar::clock::ID,
+        sysvar::rent::ID,
+    ]
+}
+
+fn stable_swap_addresses(pool: &StableSwapAccounts) -> Vec<Pubkey> {
+    let token = |token: &SwapTokenAccounts| [token.mint, token.reserve, token.admin_fees];
+    let mut addresses = vec![pool.program, pool.swap, pool.swap_authority, pool.pool_mint];
+    addresses.extend(token(&pool.token_a));
+    addresses.extend(token(&pool.token_b));
+    addresses
+}
+
+fn wrapper_addresses(wrapper: &WrapperAccounts) -> Vec<Pubkey> {
+    vec![
+        wrapper.program,
+        wrapper.wrapper,
+        wrapper.underlying_mint,
+        wrapper.wrapper_underlying_tokens,
+        wrapper.wrapped_mint,
+    ]
+}
+
+fn add_all(addresses: &mut Vec<Pubkey>, new_addresses: Vec<Pubkey>) {
+    for address in new_addresses {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let addresses = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut data = vec![0; LOOKUP_TABLE_META_SIZE];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        for address in &addresses {
+            data.extend_from_slice(address.as_ref());
+        }
+        let key = Pubkey::new_unique();
+        assert_eq!(
+            AddressLookupTableAccount::deserialize(key, &data).unwrap(),
+            AddressLookupTableAccount { key, addresses }
+        );
+
+        // uninitialized
+        data[0] = 0;
+        assert!(AddressLookupTableAccount::deserialize(key, &data).is_err());
+        assert!(AddressLookupTableAccount::deserialize(key, &[]).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Versioned transaction messages.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::Hash;
+use anchor_lang::solana_program::instruction::{CompiledInstruction, Instruction};
+use anchor_lang::solana_program::message::v0::{self, MessageAddressTableLookup};
+use anchor_lang::solana_program::message::{MessageHeader, VersionedMessage};
+
+use crate::lookup_table::AddressLookupTableAccount;
+use crate::ErrorCode;
+
+/// Maximum number of accounts which may be referenced by a message.
+const MAX_ACCOUNTS: usize = 256;
+
+/// How an account is used by the instructions of a message.
+#[derive(Copy, Clone, Debug, Default)]
+struct KeyMeta {
+    is_signer: bool,
+    is_writable: bool,
+    is_invoked: bool,
+}
+
+/// Compiles `instructions` into a v0 message, loading accounts from `lookup_tables` where possible.
+///
+/// Signers and invoked programs are never loaded from lookup tables.
+/// Lookup tables which do not contain any accounts of the message are omitted.
+pub fn compile_v0_message(
+    payer: Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedMessage> {
+    let mut keys: Vec<(Pubkey, KeyMeta)> = vec![(
+        payer,
+        KeyMeta {
+            is_signer: true,
+            is_writable: true,
+            is_invoked: false,
+        },
+    )];
+    for instruction in instructions {
+        key_meta(&mut keys, instruction.program_id).is_invoked = true;
+        for account in &instruction.accounts {
+            let meta = key_meta(&mut keys, account.pubkey);
+            meta.is_signer |= account.is_signer;
+            meta.is_writable |= account.is_writable;
+        }
+    }
+
+    // load all accounts which may be loaded from the first table containing them
+    let mut lookups = vec![];
+    let mut loaded_writable = vec![];
+    let mut loaded_readonly = vec![];
+    for table in lookup_tables {
+        let mut lookup = MessageAddressTableLookup {
+            account_key: table.key,
+            ..Default::default()
+        };
+        keys.retain(|(key, meta)| {
+            if meta.is_signer || meta.is_invoked {
+                return true;
+            }
+            let index = match table.addresses.iter().position(|address| address == key) {
+                Some(index) if index <= u8::MAX as usize => index as u8,
+                _ => return true,
+            };
+            if meta.is_writable {
+                lookup.writable_indexes.push(index);
+                loaded_writable.push(*key);
+            } else {
+                lookup.readonly_indexes.push(index);
+                loaded_readonly.push(*key);
+            }
+            false
+        });
+        if !lookup.writable_indexes.is_empty() || !lookup.readonly_indexes.is_empty() {
+            lookups.push(lookup);
+        }
+    }
+
+    // static keys are ordered as writable signers, readonly signers, writable, then readonly.
+    // the sort is stable, so the payer stays first.
+    keys.sort_by_key(|(_, meta)| (!meta.is_signer, !meta.is_writable));
+    let count = |f: fn(&KeyMeta) -> bool| keys.iter().filter(|(_, meta)| f(meta)).count() as u8;
+    let header = MessageHeader {
+        num_required_signatures: count(|m| m.is_signer),
+        num_readonly_signed_accounts: count(|m| m.is_signer && !m.is_writable),
+        num_readonly_unsigned_accounts: count(|m| !m.is_signer && !m.is_writable),
+    };
+
+    let account_keys: Vec<Pubkey> = keys.into_iter().map(|(key, _)| key).collect();
+    let all_keys: Vec<Pubkey> = account_keys
+        .iter()
+        .chain(&loaded_writable)
+        .chain(&loaded_readonly)
+        .copied()
+        .collect();
+    if all_keys.len() > MAX_ACCOUNTS {
+        return Err(error!(ErrorCode::TooManyAccounts));
+    }
+    let index_of = |pubkey: &Pubkey| -> Result<u8> {
+        all_keys
+            .iter()
+            .position(|key| key == pubkey)
+            .and_then(|index| u8::try_from(index).ok())
+            .ok_or_else(|| error!(ErrorCode::AccountNotInMessage))
+    };
+    let instructions = instructions
+        .iter()
+        .map(|instruction| {
+            Ok(CompiledInstruction {
+                program_id_index: index_of(&instruction.program_id)?,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|account| index_of(&account.pubkey))
+                    .collect::<Result<_>>()?,
+                data: instruction.data.clone(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(VersionedMessage::V0(v0::Message {
+        header,
+        account_keys,
+        recent_blockhash,
+        instructions,
+        address_table_lookups: lookups,
+    }))
+}
+
+/// The [KeyMeta] of `pubkey`, adding it to `keys` if it is not yet present.
+fn key_meta(keys: &mut Vec<(Pubkey, KeyMeta)>, pubkey: Pubkey) -> &mut KeyMeta {
+    let index = match keys.iter().position(|(key, _)| *key == pubkey) {
+        Some(index) => index,
+        None => {
+            keys.push((pubkey, KeyMeta::default()));
+            keys.len() - 1
+        }
+    };
+    &mut keys[index].1
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_v0_message() {
+        let payer = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let unlisted = Pubkey::new_unique();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![readonly, program, signer, writable],
+        };
+        let instructions = vec![Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(signer, true),
+                AccountMeta::new_readonly(unlisted, false),
+                AccountMeta::new_readonly(writable, false),
+            ],
+            data: vec![1, 2, 3],
+        }];
+        let message = compile_v0_message(
+            payer,
+            &instructions,
+            std::slice::from_ref(&table),
+            Hash::default(),
+        )
+        .unwrap();
+        let message = match message {
+            VersionedMessage::V0(message) => message,
+            VersionedMessage::Legacy(_) => unreachable!(),
+        };
+        assert_eq!(message.account_keys, vec![payer, signer, program, unlisted]);
+        assert_eq!(
+            message.header,
+            MessageHeader {
+                num_required_signatures: 2,
+                num_readonly_signed_accounts: 1,
+                num_readonly_unsigned_accounts: 2,
+            }
+        );
+        assert_eq!(
+            message.address_table_lookups,
+            vec![MessageAddressTableLookup {
+                account_key: table.key,
+                writable_indexes: vec![3],
+                readonly_indexes: vec![0],
+            }]
+        );
+        assert_eq!(
+            message.instructions,
+            vec![CompiledInstruction {
+                program_id_index: 2,
+                accounts: vec![5, 4, 1, 3, 4],
+                data: vec![1, 2, 3],
+            }]
+        );
+    }
+}
@@ -0,0 +1,542 @@
+//! Instructions of a route.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::Hash;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::message::VersionedMessage;
+use anchor_lang::solana_program::{system_instruction, system_program, sysvar};
+use anchor_lang::InstructionData;
+use anchor_spl::associated_token::get_associated_token_address;
+use continuation_router::accounts as router_accounts;
+use continuation_router::cpi_helpers::CONTINUATION_ACCOUNT_SIZE;
+use continuation_router::instruction as router_instruction;
+use continuation_router::{ActionType, RouteResult, RouteStep};
+use continuation_router_planner::{
+    Plan, PlanAction, StableSwapAccounts, SwapTokenAccounts, WrapperAccounts,
+};
+
+use crate::lookup_table::AddressLookupTableAccount;
+use crate::message::compile_v0_message;
+use crate::ErrorCode;
+
+/// Parameters of a route.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RouteParams {
+    /// Owner of all token accounts of the route. Pays for any created accounts.
+    pub owner: Pubkey,
+    /// The new continuation account. This must sign the transaction.
+    pub continuation: Pubkey,
+    /// The minimum amount of tokens out of the route.
+    pub minimum_amount_out: u64,
+    /// Unix timestamp after which the route may no longer be processed.
+    pub valid_until: Option<i64>,
+}
+
+/// Finds the address of the [continuation_router::RouterConfig].
+pub fn find_router_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID).0
+}
+
+/// Builds all instructions of a route:
+///
+/// 1. `create_ata_if_not_exists` for the token accounts of each step
+/// 2. Creating the continuation account
+/// 3. `begin_v2`
+/// 4. The action of each step
+/// 5. `end`
+///
+/// All token accounts are the associated token accounts of the owner.
+/// The input token account must already exist.
+pub fn route_instructions(plan: &Plan, params: &RouteParams) -> Result<Vec<Instruction>> {
+    let owner = params.owner;
+    let config = find_router_config_address();
+    let num_steps = u16::try_from(plan.steps.len()).map_err(|_| error!(ErrorCode::TooManySteps))?;
+
+    let mut create_atas = vec![];
+    let mut created = vec![plan.input_mint];
+    let mut create_ata = |mint: Pubkey| {
+        if !created.contains(&mint) {
+            created.push(mint);
+            create_atas.push(create_ata_if_not_exists(owner, mint));
+        }
+    };
+    let mut actions = vec![];
+    for step in &plan.steps {
+        let (instruction, mints) =
+            action_instruction(&step.action, owner, params.continuation, config);
+        mints.into_iter().for_each(&mut create_ata);
+        actions.push(instruction);
+    }
+    create_ata(plan.output_mint);
+
+    let input = get_associated_token_address(&owner, &plan.input_mint);
+    let output = get_associated_token_address(&owner, &plan.output_mint);
+    let mut instructions = create_atas;
+    instructions.push(system_instruction::create_account(
+        &owner,
+        &params.continuation,
+        Rent::default().minimum_balance(CONTINUATION_ACCOUNT_SIZE),
+        CONTINUATION_ACCOUNT_SIZE as u64,
+        &continuation_router::ID,
+    ));
+    instructions.push(Instruction {
+        program_id: continuation_router::ID,
+        accounts: router_accounts::BeginV2 {
+            continuation: params.continuation,
+            input,
+            output,
+            owner,
+        }
+        .to_account_metas(None),
+        data: router_instruction::BeginV2 {
+            amount_in: plan.amount_in,
+            minimum_amount_out: params.minimum_amount_out,
+            num_steps,
+            valid_until: params.valid_until,
+        }
+        .data(),
+    });
+    instructions.extend(actions);
+    instructions.push(Instruction {
+        program_id: continuation_router::ID,
+        accounts: router_accounts::End {
+            continuation: params.continuation,
+            output,
+            owner,
+            payer: owner,
+        }
+        .to_account_metas(None),
+        data: router_instruction::End {}.data(),
+    });
+    Ok(instructions)
+}
+
+/// Builds a v0 message of all instructions of a route, paid for by the owner.
+///
+/// Routes with several steps exceed the size limit of legacy transactions, so
+/// the accounts of [crate::route_addresses] should be loaded from `lookup_tables`.
+/// The message must be signed by the owner and the continuation.
+pub fn route_message(
+    plan: &Plan,
+    params: &RouteParams,
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedMessage> {
+    compile_v0_message(
+        params.owner,
+        &route_instructions(plan, params)?,
+        lookup_tables,
+        recent_blockhash,
+    )
+}
+
+/// Builds a `simulate_route` instruction, which quotes a plan against the current state of its pools.
+///
+/// The associated token accounts of the owner used by each step must exist,
+/// as the router deserializes them, but their balances do not matter.
+/// Accounts keep the writability they have in the action, so the owner
+/// need not sign, but the simulated transaction must lock them as writable.
+/// Read the results of a simulation with [simulation_results].
+pub fn simulate_route_instruction(plan: &Plan, owner: Pubkey) -> Result<Instruction> {
+    let config = find_router_config_address();
+    let mut remaining_accounts: Vec<AccountMeta> = vec![];
+    let mut steps = vec![];
+    for step in &plan.steps {
+        let (instruction, _) = action_instruction(&step.action, owner, Pubkey::default(), config);
+        // swap program, then the accounts following the continuation accounts,
+        // which the router deserializes with the same constraints as the action
+        let step_accounts = std::iter::once(&instruction.accounts[2])
+            .chain(&instruction.accounts[5..])
+            .map(|account| AccountMeta {
+                is_signer: false,
+                ..account.clone()
+            });
+        let accounts_start = remaining_accounts.len();
+        remaining_accounts.extend(step_accounts);
+        steps.push(RouteStep {
+            action_type: action_type(&step.action),
+            accounts_start: u8::try_from(accounts_start)
+                .map_err(|_| error!(ErrorCode::TooManyAccounts))?,
+            accounts_end: u8::try_from(remaining_accounts.len())
+                .map_err(|_| error!(ErrorCode::TooManyAccounts))?,
+        });
+    }
+
+    let mut accounts = router_accounts::SimulateRoute { config }.to_account_metas(None);
+    accounts.extend(remaining_accounts);
+    Ok(Instruction {
+        program_id: continuation_router::ID,
+        accounts,
+        data: router_instruction::SimulateRoute {
+            amount_in: plan.amount_in,
+            steps,
+        }
+        .data(),
+    })
+}
+
+/// Parses the return data of a `simulate_route` instruction into the expected amounts of each step.
+pub fn simulation_results(return_data: &[u8]) -> Result<Vec<RouteResult>> {
+    Ok(Vec::<RouteResult>::try_from_slice(return_data)?)
+}
+
+/// The [ActionType] of an action.
+fn action_type(action: &PlanAction) -> ActionType {
+    match action {
+        PlanAction::SSSwap { .. } => ActionType::SSSwap,
+        PlanAction::SSWithdrawOne { .. } => ActionType::SSWithdrawOne,
+        PlanAction::SSDepositA { .. } => ActionType::SSDepositA,
+        PlanAction::SSDepositB { .. } => ActionType::SSDepositB,
+        PlanAction::ADWithdraw { .. } => ActionType::ADWithdraw,
+        PlanAction::ADDeposit { .. } => ActionType::ADDeposit,
+    }
+}
+
+/// Builds a `create_ata_if_not_exists` instruction for an associated token account of the owner.
+fn create_ata_if_not_exists(owner: Pubkey, mint: Pubkey) -> Instruction {
+    Instruction {
+        program_id: continuation_router::ID,
+        accounts: router_accounts::CreateATAIfNotExists {
+            payer: owner,
+            ata: get_associated_token_address(&owner, &mint),
+            authority: owner,
+            mint,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+        }
+        .to_account_metas(None),
+        data: router_instruction::CreateAtaIfNotExists {}.data(),
+    }
+}
+
+/// Builds the instruction of an action, along with the mints of the token
+/// accounts of the owner which it requires to exist.
+fn action_instruction(
+    action: &PlanAction,
+    owner: Pubkey,
+    continuation: Pubkey,
+    config: Pubkey,
+) -> (Instruction, Vec<Pubkey>) {
+    let continuation_accounts = |swap_program: Pubkey| router_accounts::ContinuationAccounts {
+        continuation,
+        token_program: anchor_spl::token::ID,
+        swap_program,
+        config,
+        owner,
+    };
+    let user_token = |token: &SwapTokenAccounts| router_accounts::SwapToken {
+        user: get_associated_token_address(&owner, &token.mint),
+        reserve: token.reserve,
+    };
+    let user_output = |token: &SwapTokenAccounts| router_accounts::SwapOutput {
+        user_token: user_token(token),
+        fees: token.admin_fees,
+    };
+    let ata = |mint: &Pubkey| get_associated_token_address(&owner, mint);
+
+    let (accounts, data, mints) = match *action {
+        PlanAction::SSSwap { pool, input } => (
+            router_accounts::SSSwapAccounts {
+                continuation: continuation_accounts(pool.program),
+                action: router_accounts::SSSwap {
+                    swap: stable_swap(pool),
+                    input: user_token(pool.token(input)),
+                    output: user_output(pool.token(input.other())),
+                },
+            }
+            .to_account_metas(None),
+            router_instruction::SsSwap {}.data(),
+            vec![pool.token(input.other()).mint],
+        ),
+        PlanAction::SSWithdrawOne { pool, output } => (
+            router_accounts::SSWithdrawOneAccounts {
+                continuation: continuation_accounts(pool.program),
+                action: router_accounts::SSWithdrawOne {
+                    swap: stable_swap(pool),
+                    pool_mint: pool.pool_mint,
+                    input_lp: ata(&pool.pool_mint),
+                    quote_reserves: pool.token(output.other()).reserve,
+                    output: user_output(pool.token(output)),
+                },
+            }
+            .to_account_metas(None),
+            router_instruction::SsWithdrawOne {}.data(),
+            vec![pool.token(output).mint],
+        ),
+        PlanAction::SSDepositA { pool } => (
+            router_accounts::SSDepositAAccounts {
+                continuation: continuation_accounts(pool.program),
+                action: router_accounts::SSDepositA {
+                    inner: ss_deposit(pool, owner),
+                },
+            }
+            .to_account_metas(None),
+            router_instruction::SsDepositA {}.data(),
+            vec![pool.token_b.mint, pool.pool_mint],
+        ),
+        PlanAction::SSDepositB { pool } => (
+            router_accounts::SSDepositBAccounts {
+                continuation: continuation_accounts(pool.program),
+                action: router_accounts::SSDepositB {
+                    inner: ss_deposit(pool, owner),
+                },
+            }
+            .to_account_metas(None),
+            router_instruction::SsDepositB {}.data(),
+            vec![pool.token_a.mint, pool.pool_mint],
+        ),
+        PlanAction::ADWithdraw { wrapper } => {
+            let mut accounts = router_accounts::ADWithdrawAccounts {
+                continuation: continuation_accounts(wrapper.program),
+                action: router_accounts::ADWithdraw {
+                    input: ata(&wrapper.wrapped_mint),
+                    output: ata(&wrapper.underlying_mint),
+                },
+            }
+            .to_account_metas(None);
+            accounts.extend(user_stake(wrapper, owner));
+            (
+                accounts,
+                router_instruction::AdWithdraw {}.data(),
+                vec![wrapper.underlying_mint],
+            )
+        }
+        PlanAction::ADDeposit { wrapper } => {
+            let mut accounts = router_accounts::ADDepositAccounts {
+                continuation: continuation_accounts(wrapper.program),
+                action: router_accounts::ADDeposit {
+                    input: ata(&wrapper.underlying_mint),
+                    output: ata(&wrapper.wrapped_mint),
+                },
+            }
+            .to_account_metas(None);
+            accounts.extend(user_stake(wrapper, owner));
+            (
+                accounts,
+                router_instruction::AdDeposit {}.data(),
+                vec![wrapper.wrapped_mint],
+            )
+        }
+    };
+    (
+        Instruction {
+            program_id: continuation_router::ID,
+            accounts,
+            data,
+        },
+        mints,
+    )
+}
+
+fn stable_swap(pool: &StableSwapAccounts) -> router_accounts::StableSwap {
+    router_accounts::StableSwap {
+        swap: pool.swap,
+        swap_authority: pool.swap_authority,
+        clock: sysvar::clock::ID,
+    }
+}
+
+fn ss_deposit(pool: &StableSwapAccounts, owner: Pubkey) -> router_accounts::SSDeposit {
+    let user_token = |token: &SwapTokenAccounts| router_accounts::SwapToken {
+        user: get_associated_token_address(&owner, &token.mint),
+        reserve: token.reserve,
+    };
+    router_accounts::SSDeposit {
+        swap: stable_swap(pool),
+        input_a: user_token(&pool.token_a),
+        input_b: user_token(&pool.token_b),
+        pool_mint: pool.pool_mint,
+        output_lp: get_associated_token_address(&owner, &pool.pool_mint),
+    }
+}
+
+/// The accounts of the add-decimals instruction, passed as the remaining accounts of the action.
+fn user_stake(wrapper: &WrapperAccounts, owner: Pubkey) -> Vec<AccountMeta> {
+    add_decimals::accounts::UserStake {
+        wrapper: wrapper.wrapper,
+        wrapper_mint: wrapper.wrapped_mint,
+        wrapper_underlying_tokens: wrapper.wrapper_underlying_tokens,
+        owner,
+        user_underlying_tokens: get_associated_token_address(&owner, &wrapper.underlying_mint),
+        user_wrapped_tokens: get_associated_token_address(&owner, &wrapper.wrapped_mint),
+        token_program: anchor_spl::token::ID,
+    }
+    .to_account_metas(None)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::route_addresses;
+    use anchor_lang::solana_program::message::Message;
+    use continuation_router_planner::{find_best_plan, Pool};
+    use continuation_router_quote::{Fees, StableSwapPool, Wrapper};
+
+    /// Maximum size of a serialized transaction.
+    const PACKET_DATA_SIZE: usize = 1232;
+
+    fn token(mint: Pubkey) -> SwapTokenAccounts {
+        SwapTokenAccounts {
+            mint,
+            reserve: Pubkey::new_unique(),
+            admin_fees: Pubkey::new_unique(),
+        }
+    }
+
+    fn wrapper(underlying_mint: Pubkey) -> WrapperAccounts {
+        WrapperAccounts {
+            program: add_decimals::ID,
+            wrapper: Pubkey::new_unique(),
+            underlying_mint,
+            wrapper_underlying_tokens: Pubkey::new_unique(),
+            wrapped_mint: Pubkey::new_unique(),
+            state: Wrapper { multiplier: 1_000 },
+        }
+    }
+
+    /// wUSDC -> USDC -> USDT -> wUSDT
+    fn pools(swap_program: Pubkey) -> (Vec<Pool>, Pubkey, Pubkey) {
+        let usdc = Pubkey::new_unique();
+        let usdt = Pubkey::new_unique();
+        let wusdc = wrapper(usdc);
+        let wusdt = wrapper(usdt);
+        let mints = (wusdc.wrapped_mint, wusdt.wrapped_mint);
+        let pools = vec![
+            Pool::AddDecimals(wusdc),
+            Pool::StableSwap(Box::new(StableSwapAccounts {
+                program: swap_program,
+                swap: Pubkey::new_unique(),
+                swap_authority: Pubkey::new_unique(),
+                pool_mint: Pubkey::new_unique(),
+                token_a: token(usdc),
+                token_b: token(usdt),
+                state: StableSwapPool {
+                    amp_factor: 100,
+                    reserve_a: 1_000_000_000_000,
+                    reserve_b: 1_000_000_000_000,
+                    pool_token_supply: 2_000_000_000_000,
+                    fees: Fees::ZERO,
+                },
+            })),
+            Pool::AddDecimals(wusdt),
+        ];
+        (pools, mints.0, mints.1)
+    }
+
+    fn params(plan: &Plan) -> RouteParams {
+        RouteParams {
+            owner: Pubkey::new_unique(),
+            continuation: Pubkey::new_unique(),
+            minimum_amount_out: plan.minimum_amount_out(50),
+            valid_until: None,
+        }
+    }
+
+    #[test]
+    fn test_route_instructions() {
+        let swap_program = Pubkey::new_unique();
+        let (pools, wusdc_mint, wusdt_mint) = pools(swap_program);
+        let plan = find_best_plan(&pools, wusdc_mint, wusdt_mint, 1_000_000_000, 3).unwrap();
+        assert_eq!(plan.steps.len(), 3);
+        let params = params(&plan);
+        let instructions = route_instructions(&plan, &params).unwrap();
+
+        // ATAs for USDC, USDT, and wUSDT, then create, begin, 3 actions, end
+        assert_eq!(instructions.len(), 3 + 1 + 1 + 3 + 1);
+        assert_eq!(instructions[3].program_id, system_program::ID);
+        assert!(instructions
+            .iter()
+            .filter(|ix| ix.program_id != system_program::ID)
+            .all(|ix| ix.program_id == continuation_router::ID));
+
+        // every action includes the continuation accounts
+        for ix in &instructions[5..8] {
+            assert_eq!(ix.accounts[0].pubkey, params.continuation);
+            assert_eq!(ix.accounts[3].pubkey, find_router_config_address());
+            assert_eq!(ix.accounts[4].pubkey, params.owner);
+        }
+        assert_eq!(instructions[5].accounts[2].pubkey, add_decimals::ID);
+        assert_eq!(instructions[6].accounts[2].pubkey, swap_program);
+        assert_eq!(
+            instructions[8].accounts[1].pubkey,
+            get_associated_token_address(&params.owner, &wusdt_mint)
+        );
+    }
+
+    #[test]
+    fn test_simulate_route_instruction() {
+        let (pools, wusdc_mint, wusdt_mint) = pools(Pubkey::new_unique());
+        let plan = find_best_plan(&pools, wusdc_mint, wusdt_mint, 1_000_000_000, 3).unwrap();
+        let instruction = simulate_route_instruction(&plan, Pubkey::new_unique()).unwrap();
+
+        // config, then the swap program and accounts of each step
+        assert_eq!(instruction.accounts.len(), 1 + 10 + 9 + 10);
+        assert!(instruction
+            .accounts
+            .iter()
+            .all(|account| !account.is_signer));
+        assert_eq!(instruction.accounts[1].pubkey, add_decimals::ID);
+
+        // the swap program and accounts of each step are as writable as in the actions
+        let params = params(&plan);
+        let writable: Vec<bool> = route_instructions(&plan, &params).unwrap()[5..8]
+            .iter()
+            .flat_map(|action| std::iter::once(&action.accounts[2]).chain(&action.accounts[5..]))
+            .map(|account| account.is_writable)
+            .collect();
+        assert!(writable.iter().any(|is_writable| *is_writable));
+        assert!(instruction.accounts[1..]
+            .iter()
+            .map(|account| account.is_writable)
+            .eq(writable));
+
+        let data =
+            router_instruction::SimulateRoute::try_from_slice(&instruction.data[8..]).unwrap();
+        assert_eq!(data.amount_in, plan.amount_in);
+        let ranges: Vec<_> = data
+            .steps
+            .iter()
+            .map(|step| {
+                (
+                    u16::from(step.action_type),
+                    step.accounts_start,
+                    step.accounts_end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (ActionType::ADWithdraw.into(), 0, 10),
+                (ActionType::SSSwap.into(), 10, 19),
+                (ActionType::ADDeposit.into(), 19, 29),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_route_message() {
+        let (pools, wusdc_mint, wusdt_mint) = pools(Pubkey::new_unique());
+        let plan = find_best_plan(&pools, wusdc_mint, wusdt_mint, 1_000_000_000, 3).unwrap();
+        let params = params(&plan);
+        // owner and continuation
+        let signatures_size = 1 + 2 * 64;
+
+        let legacy = Message::new(
+            &route_instructions(&plan, &params).unwrap(),
+            Some(&params.owner),
+        );
+        assert!(signatures_size + legacy.serialize().len() > PACKET_DATA_SIZE);
+
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: route_addresses(&plan),
+        };
+        let message = route_message(&plan, &params, &[lookup_table], Hash::default()).unwrap();
+        assert_eq!(message.header().num_required_signatures, 2);
+        assert!(signatures_size + message.serialize().len() <= PACKET_DATA_SIZE);
+    }
+}
@@ -0,0 +1,99 @@
+//! Quotes plans by executing the instructions built by the client against
+//! pools of the mock swap program.
+
+#[path = "../../../programs/continuation-router/tests/harness/mod.rs"]
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_pack::Pack;
+use continuation_router::TokenAmount;
+use continuation_router_client::{simulate_route_instruction, simulation_results};
+use continuation_router_planner::{find_best_plan, Pool, StableSwapAccounts, SwapTokenAccounts};
+use continuation_router_quote::StableSwapPool;
+use harness::TestBank;
+use mock_swap::{Mode, PoolParams};
+use stable_swap_client::state::SwapInfo;
+
+const RESERVES: u64 = 1_000_000_000_000;
+
+/// The planner pool of a pool of the mock swap program, quoted from its current state.
+fn planner_pool(bank: &TestBank, pool: &harness::Pool) -> Pool {
+    let info = SwapInfo::unpack(&bank.account(&pool.swap).unwrap().data).unwrap();
+    let token = |index: usize| SwapTokenAccounts {
+        mint: pool.mints[index],
+        reserve: pool.reserves[index],
+        admin_fees: pool.admin_fees[index],
+    };
+    Pool::StableSwap(Box::new(StableSwapAccounts {
+        program: mock_swap::ID,
+        swap: pool.swap,
+        swap_authority: pool.config,
+        pool_mint: pool.pool_mint,
+        token_a: token(0),
+        token_b: token(1),
+        state: StableSwapPool::from_swap_info(
+            &info,
+            bank.token_account(&pool.reserves[0]).amount,
+            bank.token_account(&pool.reserves[1]).amount,
+            spl_token::state::Mint::unpack(&bank.account(&pool.pool_mint).unwrap().data)
+                .unwrap()
+                .supply,
+            0,
+        )
+        .unwrap(),
+    }))
+}
+
+#[test]
+fn test_simulate_route() {
+    let mut bank = TestBank::new();
+    let owner = bank.create_wallet(1_000_000_000);
+    bank.init_router(Pubkey::new_unique(), vec![mock_swap::ID]);
+    let params = PoolParams {
+        rate_bps: 10_000,
+        fee_bps: 30,
+        admin_fee_bps: 5_000,
+        mode: Mode::Normal,
+    };
+    let mints = [bank.create_mint(), bank.create_mint(), bank.create_mint()];
+    let pools = [
+        bank.create_pool([mints[0], mints[1]], [RESERVES, RESERVES], params),
+        bank.create_pool([mints[1], mints[2]], [RESERVES, RESERVES / 2], params),
+    ];
+    // only the input account holds tokens, as simulation moves none
+    bank.create_associated_token_account(mints[0], owner, 0);
+    bank.create_associated_token_account(mints[1], owner, 0);
+    bank.create_associated_token_account(mints[2], owner, 0);
+
+    let planner_pools: Vec<Pool> = pools
+        .iter()
+        .map(|pool| planner_pool(&bank, pool))
+        .collect();
+    let plan = find_best_plan(&planner_pools, mints[0], mints[2], 1_000_000, 2).unwrap();
+    assert_eq!(plan.steps.len(), 2);
+
+    let snapshot = bank.snapshot();
+    bank.process_instruction(simulate_route_instruction(&plan, owner).unwrap())
+        .unwrap();
+    assert_eq!(bank.snapshot(), snapshot);
+
+    let (program_id, return_data) = bank.return_data().unwrap();
+    assert_eq!(program_id, continuation_router::ID);
+    let results = simulation_results(&return_data).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results[0].amount_in,
+        TokenAmount {
+            mint: mints[0],
+            amount: plan.amount_in
+        }
+    );
+    assert_eq!(results[0].amount_out, results[1].amount_in);
+    assert_eq!(
+        results[1].amount_out,
+        TokenAmount {
+            mint: mints[2],
+            amount: plan.amount_out
+        }
+    );
+}
@@ -0,0 +1,20 @@
+//! Finds the best route between two tokens for the continuation router.
+//!
+//! This is the Rust equivalent of assembling an `ActionPlan` in the TypeScript SDK:
+//! pools are described by [Pool], and [find_best_plan] returns the [Plan]
+//! with the highest quoted output.
+//!
+//! Every planned step moves a single input token into a single output token, so
+//! the planner never produces `SSWithdraw` or `SSDepositBalanced`: both act on
+//! both tokens of a pool at once. Routes using them must be built by hand.
+#![deny(clippy::unwrap_used)]
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+
+pub mod plan;
+pub mod planner;
+pub mod pool;
+
+pub use plan::{Plan, PlanAction, PlanStep};
+pub use planner::{find_best_plan, find_plans};
+pub use pool::{Pool, StableSwapAccounts, SwapTokenAccounts, WrapperAccounts};
@@ -0,0 +1,145 @@
+//! Typed plans of routes.
+
+use continuation_router_quote::{Side, Step, StepQuote};
+use solana_program::pubkey::Pubkey;
+
+use crate::pool::{StableSwapAccounts, WrapperAccounts};
+
+/// An action of a [Plan], along with the pool it goes through.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlanAction<'a> {
+    /// Swaps the `input` token of the pool for the other token.
+    SSSwap {
+        /// The pool.
+        pool: &'a StableSwapAccounts,
+        /// The token swapped into the pool.
+        input: Side,
+    },
+    /// Withdraws LP tokens as only the `output` token.
+    SSWithdrawOne {
+        /// The pool.
+        pool: &'a StableSwapAccounts,
+        /// The token withdrawn.
+        output: Side,
+    },
+    /// Deposits token A for LP tokens.
+    SSDepositA {
+        /// The pool.
+        pool: &'a StableSwapAccounts,
+    },
+    /// Deposits token B for LP tokens.
+    SSDepositB {
+        /// The pool.
+        pool: &'a StableSwapAccounts,
+    },
+    /// Unwraps an add-decimals token.
+    ADWithdraw {
+        /// The wrapper.
+        wrapper: &'a WrapperAccounts,
+    },
+    /// Wraps a token with add-decimals.
+    ADDeposit {
+        /// The wrapper.
+        wrapper: &'a WrapperAccounts,
+    },
+}
+
+impl PlanAction<'_> {
+    /// Mint of the tokens into the action.
+    pub fn input_mint(&self) -> Pubkey {
+        match *self {
+            PlanAction::SSSwap { pool, input } => pool.token(input).mint,
+            PlanAction::SSWithdrawOne { pool, .. } => pool.pool_mint,
+            PlanAction::SSDepositA { pool } => pool.token_a.mint,
+            PlanAction::SSDepositB { pool } => pool.token_b.mint,
+            PlanAction::ADWithdraw { wrapper } => wrapper.wrapped_mint,
+            PlanAction::ADDeposit { wrapper } => wrapper.underlying_mint,
+        }
+    }
+
+    /// Mint of the tokens out of the action.
+    pub fn output_mint(&self) -> Pubkey {
+        match *self {
+            PlanAction::SSSwap { pool, input } => pool.token(input.other()).mint,
+            PlanAction::SSWithdrawOne { pool, output } => pool.token(output).mint,
+            PlanAction::SSDepositA { pool } | PlanAction::SSDepositB { pool } => pool.pool_mint,
+            PlanAction::ADWithdraw { wrapper } => wrapper.underlying_mint,
+            PlanAction::ADDeposit { wrapper } => wrapper.wrapped_mint,
+        }
+    }
+
+    /// The key of the swap or wrapper account.
+    pub fn pool_key(&self) -> Pubkey {
+        match *self {
+            PlanAction::SSSwap { pool, .. }
+            | PlanAction::SSWithdrawOne { pool, .. }
+            | PlanAction::SSDepositA { pool }
+            | PlanAction::SSDepositB { pool } => pool.swap,
+            PlanAction::ADWithdraw { wrapper } | PlanAction::ADDeposit { wrapper } => {
+                wrapper.wrapper
+            }
+        }
+    }
+
+    /// The [Step] used to quote the action.
+    pub fn step(&self) -> Step {
+        match *self {
+            PlanAction::SSSwap { pool, input } => Step::SSSwap {
+                pool: pool.state,
+                input,
+            },
+            PlanAction::SSWithdrawOne { pool, output } => Step::SSWithdrawOne {
+                pool: pool.state,
+                output,
+            },
+            PlanAction::SSDepositA { pool } => Step::SSDepositA { pool: pool.state },
+            PlanAction::SSDepositB { pool } => Step::SSDepositB { pool: pool.state },
+            PlanAction::ADWithdraw { wrapper } => Step::ADWithdraw {
+                wrapper: wrapper.state,
+            },
+            PlanAction::ADDeposit { wrapper } => Step::ADDeposit {
+                wrapper: wrapper.state,
+            },
+        }
+    }
+}
+
+/// A step of a [Plan].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlanStep<'a> {
+    /// The action.
+    pub action: PlanAction<'a>,
+    /// The quote of the action.
+    pub quote: StepQuote,
+}
+
+/// A route from one token to another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan<'a> {
+    /// Mint of the tokens into the route.
+    pub input_mint: Pubkey,
+    /// Mint of the tokens out of the route.
+    pub output_mint: Pubkey,
+    /// Amount of tokens into the route.
+    pub amount_in: u64,
+    /// Quoted amount of tokens out of the route.
+    pub amount_out: u64,
+    /// Steps of the route.
+    pub steps: Vec<PlanStep<'a>>,
+}
+
+impl Plan<'_> {
+    /// The fraction by which the output is lower than the output at the mid price.
+    pub fn price_impact(&self) -> f64 {
+        let mid_price: f64 = self.steps.iter().map(|step| step.quote.mid_price).product();
+        let exact_quote = mid_price * self.amount_in as f64;
+        (exact_quote - self.amount_out as f64) / exact_quote
+    }
+
+    /// The minimum amount out of the route given a slippage tolerance in basis points.
+    pub fn minimum_amount_out(&self, slippage_bps: u16) -> u64 {
+        let minimum =
+            (self.amount_out as u128) * (10_000u128.saturating_sub(slippage_bps.into())) / 10_000;
+        minimum as u64
+    }
+}
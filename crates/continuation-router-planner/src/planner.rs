@@ -0,0 +1,232 @@
+//! Search for the best route between two tokens.
+
+use solana_program::pubkey::Pubkey;
+
+use crate::plan::{Plan, PlanStep};
+use crate::pool::Pool;
+
+/// Finds all routes from `input_mint` to `output_mint` of at most `max_hops` actions.
+///
+/// Routes do not pass through a pool or a mint more than once. Routes are
+/// sorted by decreasing output, then by increasing number of hops.
+pub fn find_plans(
+    pools: &[Pool],
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount_in: u64,
+    max_hops: usize,
+) -> Vec<Plan<'_>> {
+    let mut search = Search {
+        pools,
+        input_mint,
+        output_mint,
+        amount_in,
+        max_hops,
+        mints: vec![input_mint],
+        steps: vec![],
+        plans: vec![],
+    };
+    search.visit(amount_in);
+
+    let mut plans = search.plans;
+    plans.sort_by(|a, b| {
+        b.amount_out
+            .cmp(&a.amount_out)
+            .then(a.steps.len().cmp(&b.steps.len()))
+    });
+    plans
+}
+
+/// Finds the route from `input_mint` to `output_mint` with the highest output.
+pub fn find_best_plan(
+    pools: &[Pool],
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount_in: u64,
+    max_hops: usize,
+) -> Option<Plan<'_>> {
+    find_plans(pools, input_mint, output_mint, amount_in, max_hops)
+        .into_iter()
+        .next()
+}
+
+/// Depth-first search state.
+struct Search<'a> {
+    pools: &'a [Pool],
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount_in: u64,
+    max_hops: usize,
+    /// Mints visited by the current path.
+    mints: Vec<Pubkey>,
+    /// Steps of the current path.
+    steps: Vec<PlanStep<'a>>,
+    plans: Vec<Plan<'a>>,
+}
+
+impl<'a> Search<'a> {
+    fn visit(&mut self, amount: u64) {
+        if self.steps.len() >= self.max_hops {
+            return;
+        }
+        let pools: &'a [Pool] = self.pools;
+        let mint = match self.mints.last() {
+            Some(mint) => *mint,
+            None => return,
+        };
+        for pool in pools {
+            let pool_key = pool.key();
+            if self.steps.iter().any(|s| s.action.pool_key() == pool_key) {
+                continue;
+            }
+            for action in pool.actions_from(&mint) {
+                let next_mint = action.output_mint();
+                if self.mints.contains(&next_mint) {
+                    continue;
+                }
+                let quote = match action.step().quote(amount) {
+                    Some(quote) if quote.amount_out > 0 => quote,
+                    _ => continue,
+                };
+
+                self.steps.push(PlanStep { action, quote });
+                if next_mint == self.output_mint {
+                    self.plans.push(Plan {
+                        input_mint: self.input_mint,
+                        output_mint: self.output_mint,
+                        amount_in: self.amount_in,
+                        amount_out: quote.amount_out,
+                        steps: self.steps.clone(),
+                    });
+                } else {
+                    self.mints.push(next_mint);
+                    self.visit(quote.amount_out);
+                    self.mints.pop();
+                }
+                self.steps.pop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::plan::PlanAction;
+    use crate::pool::{StableSwapAccounts, SwapTokenAccounts, WrapperAccounts};
+    use continuation_router_quote::{Fees, Side, StableSwapPool, Wrapper};
+
+    const FEES: Fees = Fees {
+        admin_trade_fee_numerator: 0,
+        admin_trade_fee_denominator: 1,
+        admin_withdraw_fee_numerator: 0,
+        admin_withdraw_fee_denominator: 1,
+        trade_fee_numerator: 4,
+        trade_fee_denominator: 10_000,
+        withdraw_fee_numerator: 0,
+        withdraw_fee_denominator: 1,
+    };
+
+    fn token(mint: Pubkey) -> SwapTokenAccounts {
+        SwapTokenAccounts {
+            mint,
+            reserve: Pubkey::new_unique(),
+            admin_fees: Pubkey::new_unique(),
+        }
+    }
+
+    fn stable_swap(mint_a: Pubkey, mint_b: Pubkey, reserve_a: u64, reserve_b: u64) -> Pool {
+        Pool::StableSwap(Box::new(StableSwapAccounts {
+            program: Pubkey::new_unique(),
+            swap: Pubkey::new_unique(),
+            swap_authority: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a: token(mint_a),
+            token_b: token(mint_b),
+            state: StableSwapPool {
+                amp_factor: 100,
+                reserve_a,
+                reserve_b,
+                pool_token_supply: reserve_a + reserve_b,
+                fees: FEES,
+            },
+        }))
+    }
+
+    fn wrapper(underlying_mint: Pubkey, multiplier: u64) -> Pool {
+        Pool::AddDecimals(WrapperAccounts {
+            program: Pubkey::new_unique(),
+            wrapper: Pubkey::new_unique(),
+            underlying_mint,
+            wrapper_underlying_tokens: Pubkey::new_unique(),
+            wrapped_mint: Pubkey::new_unique(),
+            state: Wrapper { multiplier },
+        })
+    }
+
+    fn wrapped_mint(pool: &Pool) -> Pubkey {
+        match pool {
+            Pool::AddDecimals(wrapper) => wrapper.wrapped_mint,
+            Pool::StableSwap(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_wrapped_route() {
+        let usdc = Pubkey::new_unique();
+        let usdt = Pubkey::new_unique();
+        let pools = vec![
+            wrapper(usdc, 1_000),
+            stable_swap(usdc, usdt, 1_000_000_000_000, 1_000_000_000_000),
+            wrapper(usdt, 1_000),
+        ];
+        let wusdc = wrapped_mint(&pools[0]);
+        let wusdt = wrapped_mint(&pools[2]);
+
+        let plan = find_best_plan(&pools, wusdc, wusdt, 1_000_000_000, 3).unwrap();
+        assert_eq!(plan.steps.len(), 3);
+        assert!(matches!(
+            plan.steps[0].action,
+            PlanAction::ADWithdraw { .. }
+        ));
+        assert!(matches!(
+            plan.steps[1].action,
+            PlanAction::SSSwap { input: Side::A, .. }
+        ));
+        assert!(matches!(plan.steps[2].action, PlanAction::ADDeposit { .. }));
+        assert_eq!(plan.amount_out, plan.steps[2].quote.amount_out);
+        assert!(plan.minimum_amount_out(50) < plan.amount_out);
+
+        assert!(find_best_plan(&pools, wusdc, wusdt, 1_000_000_000, 2).is_none());
+    }
+
+    #[test]
+    fn test_best_route() {
+        let usdc = Pubkey::new_unique();
+        let usdt = Pubkey::new_unique();
+        let pai = Pubkey::new_unique();
+        let pools = vec![
+            // shallow direct pool
+            stable_swap(usdc, usdt, 1_000_000, 1_000_000),
+            // deep indirect pools
+            stable_swap(usdc, pai, 1_000_000_000_000, 1_000_000_000_000),
+            stable_swap(pai, usdt, 1_000_000_000_000, 1_000_000_000_000),
+        ];
+
+        let plans = find_plans(&pools, usdc, usdt, 1_000_000, 2);
+        // direct and via PAI: routing through the LP tokens of the direct pool
+        // would use the pool twice
+        assert_eq!(plans.len(), 2);
+        assert!(plans.windows(2).all(|w| w[0].amount_out >= w[1].amount_out));
+
+        let best = &plans[0];
+        assert_eq!(best.steps.len(), 2);
+        assert_eq!(best.steps[0].action.output_mint(), pai);
+        assert_eq!(best.output_mint, usdt);
+
+        let direct = find_best_plan(&pools, usdc, usdt, 1_000_000, 1).unwrap();
+        assert_eq!(direct.steps.len(), 1);
+        assert!(direct.amount_out < best.amount_out);
+    }
+}
@@ -0,0 +1,130 @@
+//! Pools which may be routed through.
+
+use continuation_router_quote::{Side, StableSwapPool, Wrapper};
+use solana_program::pubkey::Pubkey;
+
+use crate::plan::PlanAction;
+
+/// Accounts of one token of a StableSwap pool.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SwapTokenAccounts {
+    /// Mint of the token.
+    pub mint: Pubkey,
+    /// Token account holding the reserves of the token.
+    pub reserve: Pubkey,
+    /// Token account receiving the admin fees of the token.
+    pub admin_fees: Pubkey,
+}
+
+/// A StableSwap pool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StableSwapAccounts {
+    /// The StableSwap program.
+    pub program: Pubkey,
+    /// The swap account.
+    pub swap: Pubkey,
+    /// The authority of the swap.
+    pub swap_authority: Pubkey,
+    /// Mint of the pool's LP token.
+    pub pool_mint: Pubkey,
+    /// Token A.
+    pub token_a: SwapTokenAccounts,
+    /// Token B.
+    pub token_b: SwapTokenAccounts,
+    /// State of the pool used for quoting.
+    pub state: StableSwapPool,
+}
+
+impl StableSwapAccounts {
+    /// The accounts of a token of the pool.
+    pub fn token(&self, side: Side) -> &SwapTokenAccounts {
+        match side {
+            Side::A => &self.token_a,
+            Side::B => &self.token_b,
+        }
+    }
+}
+
+/// An add-decimals wrapper.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrapperAccounts {
+    /// The add-decimals program.
+    pub program: Pubkey,
+    /// The `WrappedToken` account.
+    pub wrapper: Pubkey,
+    /// Mint of the underlying token.
+    pub underlying_mint: Pubkey,
+    /// Token account of the wrapper holding the underlying tokens.
+    pub wrapper_underlying_tokens: Pubkey,
+    /// Mint of the wrapped token.
+    pub wrapped_mint: Pubkey,
+    /// State of the wrapper used for quoting.
+    pub state: Wrapper,
+}
+
+/// A pool which may be routed through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pool {
+    /// A StableSwap pool.
+    StableSwap(Box<StableSwapAccounts>),
+    /// An add-decimals wrapper.
+    AddDecimals(WrapperAccounts),
+}
+
+impl Pool {
+    /// The key of the swap or wrapper account.
+    pub fn key(&self) -> Pubkey {
+        match self {
+            Pool::StableSwap(pool) => pool.swap,
+            Pool::AddDecimals(wrapper) => wrapper.wrapper,
+        }
+    }
+
+    /// All actions through this pool which take tokens of `mint` as input.
+    pub fn actions_from(&self, mint: &Pubkey) -> Vec<PlanAction<'_>> {
+        match self {
+            Pool::StableSwap(pool) => {
+                let pool = pool.as_ref();
+                if *mint == pool.token_a.mint {
+                    vec![
+                        PlanAction::SSSwap {
+                            pool,
+                            input: Side::A,
+                        },
+                        PlanAction::SSDepositA { pool },
+                    ]
+                } else if *mint == pool.token_b.mint {
+                    vec![
+                        PlanAction::SSSwap {
+                            pool,
+                            input: Side::B,
+                        },
+                        PlanAction::SSDepositB { pool },
+                    ]
+                } else if *mint == pool.pool_mint {
+                    vec![
+                        PlanAction::SSWithdrawOne {
+                            pool,
+                            output: Side::A,
+                        },
+                        PlanAction::SSWithdrawOne {
+                            pool,
+                            output: Side::B,
+                        },
+                    ]
+                } else {
+                    vec![]
+                }
+            }
+            Pool::AddDecimals(wrapper) => {
+                if *mint == wrapper.underlying_mint {
+                    vec![PlanAction::ADDeposit { wrapper }]
+                } else if *mint == wrapper.wrapped_mint {
+                    vec![PlanAction::ADWithdraw { wrapper }]
+                } else {
+                    vec![]
+                }
+            }
+        }
+    }
+}
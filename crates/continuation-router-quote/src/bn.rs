@@ -0,0 +1,19 @@
+//! Big number types.
+#![allow(clippy::assign_op_pattern)]
+#![allow(clippy::ptr_offset_with_cast)]
+#![allow(clippy::manual_range_contains)]
+#![allow(clippy::manual_div_ceil)]
+
+use uint::construct_uint;
+
+construct_uint! {
+    /// 192-bit unsigned integer.
+    pub struct U192(3);
+}
+
+impl U192 {
+    /// Converts [U192] to u64.
+    pub fn to_u64(self) -> Option<u64> {
+        u64::try_from(self).ok()
+    }
+}
@@ -0,0 +1,112 @@
+//! StableSwap fees.
+
+/// Number of coins in a swap.
+pub const N_COINS: u8 = 2;
+
+/// Fees of a StableSwap pool. Mirrors `stable_swap_client::fees::Fees`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fees {
+    /// Admin trade fee numerator
+    pub admin_trade_fee_numerator: u64,
+    /// Admin trade fee denominator
+    pub admin_trade_fee_denominator: u64,
+    /// Admin withdraw fee numerator
+    pub admin_withdraw_fee_numerator: u64,
+    /// Admin withdraw fee denominator
+    pub admin_withdraw_fee_denominator: u64,
+    /// Trade fee numerator
+    pub trade_fee_numerator: u64,
+    /// Trade fee denominator
+    pub trade_fee_denominator: u64,
+    /// Withdraw fee numerator
+    pub withdraw_fee_numerator: u64,
+    /// Withdraw fee denominator
+    pub withdraw_fee_denominator: u64,
+}
+
+impl Fees {
+    /// Fees which are all zero.
+    pub const ZERO: Fees = Fees {
+        admin_trade_fee_numerator: 0,
+        admin_trade_fee_denominator: 1,
+        admin_withdraw_fee_numerator: 0,
+        admin_withdraw_fee_denominator: 1,
+        trade_fee_numerator: 0,
+        trade_fee_denominator: 1,
+        withdraw_fee_numerator: 0,
+        withdraw_fee_denominator: 1,
+    };
+
+    /// Computes the admin trade fee.
+    pub fn admin_trade_fee(&self, fee_amount: u64) -> Option<u64> {
+        mul_div(
+            fee_amount,
+            self.admin_trade_fee_numerator,
+            self.admin_trade_fee_denominator,
+        )
+    }
+
+    /// Computes the admin withdraw fee.
+    pub fn admin_withdraw_fee(&self, fee_amount: u64) -> Option<u64> {
+        mul_div(
+            fee_amount,
+            self.admin_withdraw_fee_numerator,
+            self.admin_withdraw_fee_denominator,
+        )
+    }
+
+    /// Computes the trade fee of an amount.
+    pub fn trade_fee(&self, trade_amount: u64) -> Option<u64> {
+        mul_div(
+            trade_amount,
+            self.trade_fee_numerator,
+            self.trade_fee_denominator,
+        )
+    }
+
+    /// Computes the withdraw fee of an amount.
+    pub fn withdraw_fee(&self, withdraw_amount: u64) -> Option<u64> {
+        mul_div(
+            withdraw_amount,
+            self.withdraw_fee_numerator,
+            self.withdraw_fee_denominator,
+        )
+    }
+
+    /// Computes the normalized fee for imbalanced deposits and withdraws.
+    pub fn normalized_trade_fee(&self, amount: u64) -> Option<u64> {
+        // adjusted_fee_numerator = fee * N_COINS / (4 * (N_COINS - 1))
+        let adjusted_trade_fee_numerator = mul_div(
+            self.trade_fee_numerator,
+            N_COINS.into(),
+            (N_COINS.checked_sub(1)?).checked_mul(4)?.into(),
+        )?;
+        mul_div(
+            amount,
+            adjusted_trade_fee_numerator,
+            self.trade_fee_denominator,
+        )
+    }
+}
+
+#[cfg(feature = "stable-swap-client")]
+impl From<&stable_swap_client::fees::Fees> for Fees {
+    fn from(fees: &stable_swap_client::fees::Fees) -> Self {
+        Fees {
+            admin_trade_fee_numerator: fees.admin_trade_fee_numerator,
+            admin_trade_fee_denominator: fees.admin_trade_fee_denominator,
+            admin_withdraw_fee_numerator: fees.admin_withdraw_fee_numerator,
+            admin_withdraw_fee_denominator: fees.admin_withdraw_fee_denominator,
+            trade_fee_numerator: fees.trade_fee_numerator,
+            trade_fee_denominator: fees.trade_fee_denominator,
+            withdraw_fee_numerator: fees.withdraw_fee_numerator,
+            withdraw_fee_denominator: fees.withdraw_fee_denominator,
+        }
+    }
+}
+
+/// Multiplies two u64s then divides by the third number.
+pub fn mul_div(a: u64, b: u64, c: u64) -> Option<u64> {
+    let result = (a as u128).checked_mul(b.into())?.checked_div(c.into())?;
+    u64::try_from(result).ok()
+}
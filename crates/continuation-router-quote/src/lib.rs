@@ -0,0 +1,22 @@
+//! Off-chain quoting of continuation router routes.
+//!
+//! The math of each action is a port of the on-chain math of the StableSwap
+//! and add-decimals programs, so that quotes match the amounts that the router
+//! will observe. This crate is `no_std`.
+#![cfg_attr(not(test), no_std)]
+#![deny(clippy::unwrap_used)]
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+
+extern crate alloc;
+
+pub mod bn;
+pub mod fees;
+pub mod route;
+pub mod stable_swap;
+pub mod wrapper;
+
+pub use fees::Fees;
+pub use route::{quote_route, RouteQuote, Step, StepQuote};
+pub use stable_swap::{Side, StableSwapPool};
+pub use wrapper::Wrapper;
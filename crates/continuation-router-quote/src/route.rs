@@ -0,0 +1,335 @@
+//! Quoting of the actions of a route.
+
+use alloc::vec::Vec;
+
+use crate::stable_swap::{Side, StableSwapPool};
+use crate::wrapper::Wrapper;
+
+/// Fraction of the input reserves used to estimate the mid price of a pool.
+const MID_PRICE_REFERENCE_DIVISOR: u64 = 1_000;
+
+/// A step of a route. Each variant corresponds to an `ActionType` of the router.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Swaps the `input` token of the pool for the other token.
+    SSSwap {
+        /// The pool.
+        pool: StableSwapPool,
+        /// The token swapped into the pool.
+        input: Side,
+    },
+    /// Withdraws LP tokens as only the `output` token.
+    SSWithdrawOne {
+        /// The pool.
+        pool: StableSwapPool,
+        /// The token withdrawn.
+        output: Side,
+    },
+    /// Deposits token A for LP tokens.
+    SSDepositA {
+        /// The pool.
+        pool: StableSwapPool,
+    },
+    /// Deposits token B for LP tokens.
+    SSDepositB {
+        /// The pool.
+        pool: StableSwapPool,
+    },
+    /// Withdraws LP tokens as both tokens. The `output` token continues the route.
+    SSWithdraw {
+        /// The pool.
+        pool: StableSwapPool,
+        /// The token which continues the route.
+        output: Side,
+    },
+    /// Deposits the `input` token along with a proportional amount of the other token.
+    SSDepositBalanced {
+        /// The pool.
+        pool: StableSwapPool,
+        /// The token which comes from the route.
+        input: Side,
+    },
+    /// Unwraps an add-decimals token.
+    ADWithdraw {
+        /// The wrapper.
+        wrapper: Wrapper,
+    },
+    /// Wraps a token with add-decimals.
+    ADDeposit {
+        /// The wrapper.
+        wrapper: Wrapper,
+    },
+    /// Takes a referral fee.
+    TakeFee {
+        /// Fee in basis points of the amount in.
+        fee_bps: u16,
+        /// Maximum amount of tokens to take as a fee.
+        max_fee: u64,
+    },
+}
+
+impl Step {
+    /// Computes the amount which continues the route after processing `amount_in`.
+    pub fn amount_out(&self, amount_in: u64) -> Option<u64> {
+        match *self {
+            Step::SSSwap { pool, input } => pool.swap(input, amount_in),
+            Step::SSWithdrawOne { pool, output } => pool.withdraw_one(output, amount_in),
+            Step::SSDepositA { pool } => pool.deposit(amount_in, 0),
+            Step::SSDepositB { pool } => pool.deposit(0, amount_in),
+            Step::SSWithdraw { pool, output } => {
+                let (amount_a, amount_b) = pool.withdraw(amount_in)?;
+                Some(match output {
+                    Side::A => amount_a,
+                    Side::B => amount_b,
+                })
+            }
+            Step::SSDepositBalanced { pool, input } => {
+                let other_amount = compute_balanced_amount(
+                    amount_in,
+                    pool.reserve(input),
+                    pool.reserve(input.other()),
+                )?;
+                match input {
+                    Side::A => pool.deposit(amount_in, other_amount),
+                    Side::B => pool.deposit(other_amount, amount_in),
+                }
+            }
+            Step::ADWithdraw { wrapper } => wrapper.withdraw(amount_in),
+            Step::ADDeposit { wrapper } => wrapper.deposit(amount_in),
+            Step::TakeFee { fee_bps, max_fee } => {
+                amount_in.checked_sub(compute_fee(amount_in, fee_bps, max_fee)?)
+            }
+        }
+    }
+
+    /// This step without any fees.
+    pub fn without_fees(&self) -> Step {
+        match *self {
+            Step::SSSwap { pool, input } => Step::SSSwap {
+                pool: pool.without_fees(),
+                input,
+            },
+            Step::SSWithdrawOne { pool, output } => Step::SSWithdrawOne {
+                pool: pool.without_fees(),
+                output,
+            },
+            Step::SSDepositA { pool } => Step::SSDepositA {
+                pool: pool.without_fees(),
+            },
+            Step::SSDepositB { pool } => Step::SSDepositB {
+                pool: pool.without_fees(),
+            },
+            Step::SSWithdraw { pool, output } => Step::SSWithdraw {
+                pool: pool.without_fees(),
+                output,
+            },
+            Step::SSDepositBalanced { pool, input } => Step::SSDepositBalanced {
+                pool: pool.without_fees(),
+                input,
+            },
+            Step::ADWithdraw { .. } | Step::ADDeposit { .. } => *self,
+            Step::TakeFee { .. } => Step::TakeFee {
+                fee_bps: 0,
+                max_fee: 0,
+            },
+        }
+    }
+
+    /// Estimates the price of the input token in terms of the output token, excluding fees.
+    ///
+    /// For pools, this is estimated from the output of a small amount without fees.
+    pub fn mid_price(&self) -> Option<f64> {
+        let reference = match *self {
+            Step::SSSwap { pool, input } | Step::SSDepositBalanced { pool, input } => {
+                pool.reserve(input)
+            }
+            Step::SSWithdrawOne { pool, .. } | Step::SSWithdraw { pool, .. } => {
+                pool.pool_token_supply
+            }
+            Step::SSDepositA { pool } => pool.reserve_a,
+            Step::SSDepositB { pool } => pool.reserve_b,
+            Step::ADWithdraw { wrapper } => return Some(1.0 / wrapper.multiplier as f64),
+            Step::ADDeposit { wrapper } => return Some(wrapper.multiplier as f64),
+            Step::TakeFee { .. } => return Some(1.0),
+        };
+        let reference = (reference / MID_PRICE_REFERENCE_DIVISOR).max(1);
+        let amount_out = self.without_fees().amount_out(reference)?;
+        Some(amount_out as f64 / reference as f64)
+    }
+
+    /// Quotes this step.
+    pub fn quote(&self, amount_in: u64) -> Option<StepQuote> {
+        let amount_out = self.amount_out(amount_in)?;
+        let amount_out_without_fees = self.without_fees().amount_out(amount_in)?;
+        Some(StepQuote {
+            amount_in,
+            amount_out,
+            fee: amount_out_without_fees.saturating_sub(amount_out),
+            mid_price: self.mid_price()?,
+        })
+    }
+}
+
+/// The expected result of a [Step].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StepQuote {
+    /// Amount of tokens into the step.
+    pub amount_in: u64,
+    /// Amount of tokens which continue the route.
+    pub amount_out: u64,
+    /// Fees paid, in terms of the output token.
+    pub fee: u64,
+    /// Price of the input token in terms of the output token before the step, excluding fees.
+    pub mid_price: f64,
+}
+
+impl StepQuote {
+    /// The fraction by which the output is lower than the output at the mid price.
+    pub fn price_impact(&self) -> f64 {
+        price_impact(self.mid_price, self.amount_in, self.amount_out)
+    }
+}
+
+/// The expected result of a route.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteQuote {
+    /// Amount of tokens into the route.
+    pub amount_in: u64,
+    /// Amount of tokens out of the route.
+    pub amount_out: u64,
+    /// Quotes of each step.
+    pub steps: Vec<StepQuote>,
+}
+
+impl RouteQuote {
+    /// Price of the input token in terms of the output token before the route, excluding fees.
+    pub fn mid_price(&self) -> f64 {
+        self.steps.iter().map(|step| step.mid_price).product()
+    }
+
+    /// The fraction by which the output is lower than the output at the mid price.
+    pub fn price_impact(&self) -> f64 {
+        price_impact(self.mid_price(), self.amount_in, self.amount_out)
+    }
+}
+
+/// Quotes a route of `steps` starting with `amount_in` tokens.
+///
+/// Returns [None] if any step would fail.
+pub fn quote_route(steps: &[Step], amount_in: u64) -> Option<RouteQuote> {
+    let mut amount = amount_in;
+    let mut quotes = Vec::with_capacity(steps.len());
+    for step in steps {
+        let quote = step.quote(amount)?;
+        amount = quote.amount_out;
+        quotes.push(quote);
+    }
+    Some(RouteQuote {
+        amount_in,
+        amount_out: amount,
+        steps: quotes,
+    })
+}
+
+/// Computes the fee of `fee_bps` basis points of `amount`, capped at `max_fee`.
+pub fn compute_fee(amount: u64, fee_bps: u16, max_fee: u64) -> Option<u64> {
+    let fee = (amount as u128)
+        .checked_mul(fee_bps.into())?
+        .checked_div(10_000)?;
+    Some(u64::try_from(fee).ok()?.min(max_fee))
+}
+
+/// Computes the amount of the other token deposited alongside `amount_in` by
+/// a balanced deposit.
+pub fn compute_balanced_amount(
+    amount_in: u64,
+    input_reserve: u64,
+    other_reserve: u64,
+) -> Option<u64> {
+    if input_reserve == 0 {
+        return None;
+    }
+    let amount = (amount_in as u128)
+        .checked_mul(other_reserve.into())?
+        .checked_div(input_reserve.into())?;
+    u64::try_from(amount).ok()
+}
+
+fn price_impact(mid_price: f64, amount_in: u64, amount_out: u64) -> f64 {
+    let exact_quote = mid_price * amount_in as f64;
+    (exact_quote - amount_out as f64) / exact_quote
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::Fees;
+    use proptest::prelude::*;
+
+    const POOL: StableSwapPool = StableSwapPool {
+        amp_factor: 100,
+        reserve_a: 1_000_000_000_000,
+        reserve_b: 1_000_000_000_000,
+        pool_token_supply: 2_000_000_000_000,
+        fees: Fees {
+            admin_trade_fee_numerator: 0,
+            admin_trade_fee_denominator: 1,
+            admin_withdraw_fee_numerator: 0,
+            admin_withdraw_fee_denominator: 1,
+            trade_fee_numerator: 4,
+            trade_fee_denominator: 10_000,
+            withdraw_fee_numerator: 0,
+            withdraw_fee_denominator: 1,
+        },
+    };
+
+    proptest! {
+        #[test]
+        fn test_compute_fee(amount: u64, fee_bps: u16, max_fee: u64) {
+            assert_eq!(
+                compute_fee(amount, fee_bps, max_fee),
+                continuation_router::action::fee::compute_fee(amount, fee_bps, max_fee)
+            );
+        }
+
+        #[test]
+        fn test_compute_balanced_amount(amount_in: u64, input_reserve: u64, other_reserve: u64) {
+            assert_eq!(
+                compute_balanced_amount(amount_in, input_reserve, other_reserve),
+                continuation_router::action::stable_swap::compute_balanced_amount(
+                    amount_in,
+                    input_reserve,
+                    other_reserve
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_route() {
+        let wrapper = Wrapper { multiplier: 1_000 };
+        let steps = [
+            Step::ADWithdraw { wrapper },
+            Step::SSSwap {
+                pool: POOL,
+                input: Side::A,
+            },
+            Step::ADDeposit { wrapper },
+        ];
+        let quote = quote_route(&steps, 1_000_000_000).unwrap();
+        assert_eq!(quote.steps.len(), 3);
+        assert_eq!(quote.steps[0].amount_out, 1_000_000);
+        assert_eq!(quote.steps[1].amount_in, 1_000_000);
+        assert_eq!(
+            quote.amount_out,
+            POOL.swap(Side::A, 1_000_000).unwrap() * 1_000
+        );
+        assert!(quote.steps[1].fee > 0);
+        assert!(quote.price_impact() > 0.0);
+        assert!(quote.price_impact() < 0.001);
+
+        // failing steps fail the route
+        assert_eq!(quote_route(&steps, 999), None);
+    }
+}
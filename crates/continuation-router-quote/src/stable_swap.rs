@@ -0,0 +1,431 @@
+//! StableSwap math, ported from `stable-swap-math`.
+
+use crate::bn::U192;
+use crate::fees::{Fees, N_COINS};
+
+/// Computes the amplification coefficient (A) of a pool at `current_ts`.
+///
+/// The amplification coefficient ramps linearly from `initial_amp_factor`
+/// to `target_amp_factor` between `start_ramp_ts` and `stop_ramp_ts`.
+pub fn compute_amp_factor(
+    initial_amp_factor: u64,
+    target_amp_factor: u64,
+    current_ts: i64,
+    start_ramp_ts: i64,
+    stop_ramp_ts: i64,
+) -> Option<u64> {
+    if current_ts < stop_ramp_ts {
+        let time_range = u128::try_from(stop_ramp_ts.checked_sub(start_ramp_ts)?).ok()?;
+        let time_delta = u128::try_from(current_ts.checked_sub(start_ramp_ts)?).ok()?;
+        if target_amp_factor >= initial_amp_factor {
+            let amp_range = target_amp_factor.checked_sub(initial_amp_factor)?;
+            let amp_delta = (amp_range as u128)
+                .checked_mul(time_delta)?
+                .checked_div(time_range)?;
+            initial_amp_factor.checked_add(u64::try_from(amp_delta).ok()?)
+        } else {
+            let amp_range = initial_amp_factor.checked_sub(target_amp_factor)?;
+            let amp_delta = (amp_range as u128)
+                .checked_mul(time_delta)?
+                .checked_div(time_range)?;
+            initial_amp_factor.checked_sub(u64::try_from(amp_delta).ok()?)
+        }
+    } else {
+        Some(target_amp_factor)
+    }
+}
+
+/// A token of a StableSwap pool.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Side {
+    /// Token A.
+    A,
+    /// Token B.
+    B,
+}
+
+impl Side {
+    /// The other token of the pool.
+    pub fn other(self) -> Side {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+/// State of a StableSwap pool.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StableSwapPool {
+    /// The current amplification coefficient. See [compute_amp_factor].
+    pub amp_factor: u64,
+    /// Reserves of token A.
+    pub reserve_a: u64,
+    /// Reserves of token B.
+    pub reserve_b: u64,
+    /// Supply of the pool token.
+    pub pool_token_supply: u64,
+    /// Fees of the pool.
+    pub fees: Fees,
+}
+
+impl StableSwapPool {
+    /// Creates a [StableSwapPool] from the state of a swap at `current_ts`.
+    #[cfg(feature = "stable-swap-client")]
+    pub fn from_swap_info(
+        info: &stable_swap_client::state::SwapInfo,
+        reserve_a: u64,
+        reserve_b: u64,
+        pool_token_supply: u64,
+        current_ts: i64,
+    ) -> Option<StableSwapPool> {
+        Some(StableSwapPool {
+            amp_factor: compute_amp_factor(
+                info.initial_amp_factor,
+                info.target_amp_factor,
+                current_ts,
+                info.start_ramp_ts,
+                info.stop_ramp_ts,
+            )?,
+            reserve_a,
+            reserve_b,
+            pool_token_supply,
+            fees: (&info.fees).into(),
+        })
+    }
+
+    /// The reserves of a token.
+    pub fn reserve(&self, side: Side) -> u64 {
+        match side {
+            Side::A => self.reserve_a,
+            Side::B => self.reserve_b,
+        }
+    }
+
+    /// This pool without any fees.
+    pub fn without_fees(&self) -> StableSwapPool {
+        StableSwapPool {
+            fees: Fees::ZERO,
+            ..*self
+        }
+    }
+
+    fn compute_next_d(&self, d_init: U192, d_prod: U192, sum_x: u64) -> Option<U192> {
+        let ann = self.amp_factor.checked_mul(N_COINS.into())?;
+        let leverage = (sum_x as u128).checked_mul(ann.into())?;
+        // d = (ann * sum_x + d_prod * n_coins) * d / ((ann - 1) * d + (n_coins + 1) * d_prod)
+        let numerator = d_init.checked_mul(
+            d_prod
+                .checked_mul(N_COINS.into())?
+                .checked_add(leverage.into())?,
+        )?;
+        let denominator = d_init
+            .checked_mul(ann.checked_sub(1)?.into())?
+            .checked_add(d_prod.checked_mul((N_COINS.checked_add(1)?).into())?)?;
+        numerator.checked_div(denominator)
+    }
+
+    /// Computes the StableSwap invariant (D) for the given reserves.
+    pub fn compute_d(&self, amount_a: u64, amount_b: u64) -> Option<U192> {
+        let sum_x = amount_a.checked_add(amount_b)?;
+        if sum_x == 0 {
+            return Some(0.into());
+        }
+        let amount_a_times_coins = amount_a.checked_mul(N_COINS.into())?;
+        let amount_b_times_coins = amount_b.checked_mul(N_COINS.into())?;
+
+        // Newton's method to approximate D
+        let mut d: U192 = sum_x.into();
+        for _ in 0..256 {
+            let d_prod = d
+                .checked_mul(d)?
+                .checked_div(amount_a_times_coins.into())?
+                .checked_mul(d)?
+                .checked_div(amount_b_times_coins.into())?;
+            let d_prev = d;
+            d = self.compute_next_d(d, d_prod, sum_x)?;
+            if abs_diff(d, d_prev)? <= 1.into() {
+                break;
+            }
+        }
+        Some(d)
+    }
+
+    /// Computes the reserves of one token given the reserves `x` of the other token and the invariant `d`.
+    pub fn compute_y(&self, x: u64, d: U192) -> Option<u64> {
+        let ann = self.amp_factor.checked_mul(N_COINS.into())?;
+
+        // c = D ** (n + 1) / (n ** (2 * n) * prod' * A)
+        let c = d
+            .checked_mul(d)?
+            .checked_div(x.checked_mul(N_COINS.into())?.into())?
+            .checked_mul(d)?
+            .checked_div(ann.checked_mul(N_COINS.into())?.into())?;
+        // b = sum' - (A*n**n - 1) * D / (A * n**n)
+        let b = d.checked_div(ann.into())?.checked_add(x.into())?;
+
+        // Solve for y by approximating: y**2 + b*y = c
+        let mut y = d;
+        for _ in 0..256 {
+            let y_prev = y;
+            let y_numerator = y.checked_pow(2.into())?.checked_add(c)?;
+            let y_denominator = y.checked_mul(2.into())?.checked_add(b)?.checked_sub(d)?;
+            y = y_numerator.checked_div(y_denominator)?;
+            if abs_diff(y, y_prev)? <= 1.into() {
+                break;
+            }
+        }
+        y.to_u64()
+    }
+
+    /// Computes the amount of the other token received for swapping `amount_in` of the `input` token.
+    pub fn swap(&self, input: Side, amount_in: u64) -> Option<u64> {
+        let swap_source_amount = self.reserve(input);
+        let swap_destination_amount = self.reserve(input.other());
+        let y = self.compute_y(
+            swap_source_amount.checked_add(amount_in)?,
+            self.compute_d(swap_source_amount, swap_destination_amount)?,
+        )?;
+        let dy = swap_destination_amount.checked_sub(y)?.checked_sub(1)?;
+        let dy_fee = self.fees.trade_fee(dy)?;
+        dy.checked_sub(dy_fee)
+    }
+
+    /// Computes the amount of LP tokens minted for depositing the given amounts.
+    pub fn deposit(&self, amount_a: u64, amount_b: u64) -> Option<u64> {
+        let d_0 = self.compute_d(self.reserve_a, self.reserve_b)?;
+        let old_balances = [self.reserve_a, self.reserve_b];
+        let mut new_balances = [
+            self.reserve_a.checked_add(amount_a)?,
+            self.reserve_b.checked_add(amount_b)?,
+        ];
+        let d_1 = self.compute_d(new_balances[0], new_balances[1])?;
+        if d_1 <= d_0 {
+            return None;
+        }
+
+        // Recalculate the invariant accounting for fees
+        for i in 0..new_balances.len() {
+            let ideal_balance = d_1
+                .checked_mul(old_balances[i].into())?
+                .checked_div(d_0)?
+                .to_u64()?;
+            let difference = if ideal_balance > new_balances[i] {
+                ideal_balance.checked_sub(new_balances[i])?
+            } else {
+                new_balances[i].checked_sub(ideal_balance)?
+            };
+            let fee = self.fees.normalized_trade_fee(difference)?;
+            new_balances[i] = new_balances[i].checked_sub(fee)?;
+        }
+
+        let d_2 = self.compute_d(new_balances[0], new_balances[1])?;
+        U192::from(self.pool_token_supply)
+            .checked_mul(d_2.checked_sub(d_0)?)?
+            .checked_div(d_0)?
+            .to_u64()
+    }
+
+    /// Computes the amounts of token A and token B received for withdrawing `pool_token_amount` LP tokens.
+    pub fn withdraw(&self, pool_token_amount: u64) -> Option<(u64, u64)> {
+        Some((
+            self.withdraw_side(Side::A, pool_token_amount)?,
+            self.withdraw_side(Side::B, pool_token_amount)?,
+        ))
+    }
+
+    fn withdraw_side(&self, side: Side, pool_token_amount: u64) -> Option<u64> {
+        let amount = (pool_token_amount as u128)
+            .checked_mul(self.reserve(side).into())?
+            .checked_div(self.pool_token_supply.into())?;
+        let amount = u64::try_from(amount).ok()?;
+        amount.checked_sub(self.fees.withdraw_fee(amount)?)
+    }
+
+    /// Computes the amount of the `output` token received for withdrawing
+    /// `pool_token_amount` LP tokens as only that token.
+    pub fn withdraw_one(&self, output: Side, pool_token_amount: u64) -> Option<u64> {
+        let swap_base_amount = self.reserve(output);
+        let swap_quote_amount = self.reserve(output.other());
+
+        let d_0 = self.compute_d(swap_base_amount, swap_quote_amount)?;
+        let d_1 = d_0.checked_sub(
+            U192::from(pool_token_amount)
+                .checked_mul(d_0)?
+                .checked_div(self.pool_token_supply.into())?,
+        )?;
+        let new_y = self.compute_y(swap_quote_amount, d_1)?;
+
+        // expected_base_amount = swap_base_amount * d_1 / d_0 - new_y
+        let expected_base_amount = U192::from(swap_base_amount)
+            .checked_mul(d_1)?
+            .checked_div(d_0)?
+            .to_u64()?
+            .checked_sub(new_y)?;
+        // expected_quote_amount = swap_quote_amount - swap_quote_amount * d_1 / d_0
+        let expected_quote_amount = swap_quote_amount.checked_sub(
+            U192::from(swap_quote_amount)
+                .checked_mul(d_1)?
+                .checked_div(d_0)?
+                .to_u64()?,
+        )?;
+        let new_base_amount =
+            swap_base_amount.checked_sub(self.fees.normalized_trade_fee(expected_base_amount)?)?;
+        let new_quote_amount = swap_quote_amount
+            .checked_sub(self.fees.normalized_trade_fee(expected_quote_amount)?)?;
+        // withdraw less to account for rounding errors
+        let dy = new_base_amount
+            .checked_sub(self.compute_y(new_quote_amount, d_1)?)?
+            .checked_sub(1)?;
+
+        dy.checked_sub(self.fees.withdraw_fee(dy)?)
+    }
+}
+
+fn abs_diff(a: U192, b: U192) -> Option<U192> {
+    if a > b {
+        a.checked_sub(b)
+    } else {
+        b.checked_sub(a)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use stable_swap_math::curve::{StableSwap, MAX_AMP, MAX_TOKENS_IN, MIN_AMP};
+    use stable_swap_math::math::FeeCalculator;
+    use stable_swap_math::pool_converter::PoolTokenConverter;
+
+    fn client_fees(fees: &Fees) -> stable_swap_client::fees::Fees {
+        stable_swap_client::fees::Fees {
+            admin_trade_fee_numerator: fees.admin_trade_fee_numerator,
+            admin_trade_fee_denominator: fees.admin_trade_fee_denominator,
+            admin_withdraw_fee_numerator: fees.admin_withdraw_fee_numerator,
+            admin_withdraw_fee_denominator: fees.admin_withdraw_fee_denominator,
+            trade_fee_numerator: fees.trade_fee_numerator,
+            trade_fee_denominator: fees.trade_fee_denominator,
+            withdraw_fee_numerator: fees.withdraw_fee_numerator,
+            withdraw_fee_denominator: fees.withdraw_fee_denominator,
+        }
+    }
+
+    prop_compose! {
+        fn fees()
+            (trade_fee_numerator in 0..100u64, withdraw_fee_numerator in 0..100u64, admin_fee_numerator in 0..100u64)
+            -> Fees {
+                Fees {
+                    admin_trade_fee_numerator: admin_fee_numerator,
+                    admin_trade_fee_denominator: 100,
+                    admin_withdraw_fee_numerator: admin_fee_numerator,
+                    admin_withdraw_fee_denominator: 100,
+                    trade_fee_numerator,
+                    trade_fee_denominator: 10_000,
+                    withdraw_fee_numerator,
+                    withdraw_fee_denominator: 10_000,
+                }
+        }
+    }
+
+    prop_compose! {
+        fn pool()
+            (
+                amp_factor in MIN_AMP..=MAX_AMP,
+                reserve_a in 1..MAX_TOKENS_IN,
+                reserve_b in 1..MAX_TOKENS_IN,
+                pool_token_supply in 1..MAX_TOKENS_IN,
+                fees in fees(),
+            ) -> StableSwapPool {
+                StableSwapPool {
+                    amp_factor,
+                    reserve_a,
+                    reserve_b,
+                    pool_token_supply,
+                    fees,
+                }
+        }
+    }
+
+    fn invariant(pool: &StableSwapPool) -> StableSwap {
+        StableSwap::new(pool.amp_factor, pool.amp_factor, 0, 0, 0)
+    }
+
+    proptest! {
+        #[test]
+        fn test_compute_amp_factor(
+            initial_amp_factor in MIN_AMP..=MAX_AMP,
+            target_amp_factor in MIN_AMP..=MAX_AMP,
+            start_ramp_ts in 0..i64::MAX / 2,
+            ramp_duration in 1..1_000_000i64,
+            elapsed in 0..2_000_000i64,
+        ) {
+            let stop_ramp_ts = start_ramp_ts + ramp_duration;
+            let current_ts = start_ramp_ts + elapsed;
+            assert_eq!(
+                compute_amp_factor(initial_amp_factor, target_amp_factor, current_ts, start_ramp_ts, stop_ramp_ts),
+                StableSwap::new(initial_amp_factor, target_amp_factor, current_ts, start_ramp_ts, stop_ramp_ts)
+                    .compute_amp_factor()
+            );
+        }
+
+        #[test]
+        fn test_swap(pool in pool(), amount_in in 0..MAX_TOKENS_IN, a_to_b: bool) {
+            let (input, source, destination) = if a_to_b {
+                (Side::A, pool.reserve_a, pool.reserve_b)
+            } else {
+                (Side::B, pool.reserve_b, pool.reserve_a)
+            };
+            let expected = invariant(&pool)
+                .swap_to(amount_in, source, destination, &client_fees(&pool.fees))
+                .map(|r| r.amount_swapped);
+            assert_eq!(pool.swap(input, amount_in), expected);
+        }
+
+        #[test]
+        fn test_deposit(pool in pool(), amount_a in 0..MAX_TOKENS_IN, amount_b in 0..MAX_TOKENS_IN) {
+            let expected = invariant(&pool).compute_mint_amount_for_deposit(
+                amount_a,
+                amount_b,
+                pool.reserve_a,
+                pool.reserve_b,
+                pool.pool_token_supply,
+                &client_fees(&pool.fees),
+            );
+            assert_eq!(pool.deposit(amount_a, amount_b), expected);
+        }
+
+        #[test]
+        fn test_withdraw(pool in pool(), pool_token_amount in 0..MAX_TOKENS_IN) {
+            prop_assume!(pool_token_amount <= pool.pool_token_supply);
+            let fees = client_fees(&pool.fees);
+            let converter = PoolTokenConverter {
+                supply: pool.pool_token_supply,
+                token_a: pool.reserve_a,
+                token_b: pool.reserve_b,
+                fees: &fees,
+            };
+            let expected = converter
+                .token_a_rate(pool_token_amount)
+                .zip(converter.token_b_rate(pool_token_amount))
+                .map(|(a, b)| (a.0, b.0));
+            assert_eq!(pool.withdraw(pool_token_amount), expected);
+        }
+
+        #[test]
+        fn test_withdraw_one(pool in pool(), pool_token_amount in 0..MAX_TOKENS_IN, to_a: bool) {
+            prop_assume!(pool_token_amount <= pool.pool_token_supply);
+            let (output, base, quote) = if to_a {
+                (Side::A, pool.reserve_a, pool.reserve_b)
+            } else {
+                (Side::B, pool.reserve_b, pool.reserve_a)
+            };
+            let fees = client_fees(&pool.fees);
+            let expected = invariant(&pool)
+                .compute_withdraw_one(pool_token_amount, pool.pool_token_supply, base, quote, &fees)
+                .and_then(|(dy, _)| dy.checked_sub(fees.withdraw_fee(dy)?));
+            assert_eq!(pool.withdraw_one(output, pool_token_amount), expected);
+        }
+    }
+}
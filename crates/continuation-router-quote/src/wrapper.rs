@@ -0,0 +1,56 @@
+//! add-decimals math.
+
+/// An add-decimals wrapper of a token.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Wrapper {
+    /// Amount to multiply by to wrap the underlying token.
+    pub multiplier: u64,
+}
+
+impl Wrapper {
+    /// Computes the amount of wrapped tokens minted for depositing `amount` underlying tokens.
+    pub fn deposit(&self, amount: u64) -> Option<u64> {
+        if amount == 0 {
+            return None;
+        }
+        self.multiplier.checked_mul(amount)
+    }
+
+    /// Computes the amount of underlying tokens received for burning at most `amount` wrapped tokens.
+    /// The remainder of the division by the multiplier is left in the wrapped token account.
+    pub fn withdraw(&self, amount: u64) -> Option<u64> {
+        if amount == 0 {
+            return None;
+        }
+        amount.checked_div(self.multiplier)
+    }
+}
+
+#[cfg(feature = "add-decimals")]
+impl From<&add_decimals::WrappedToken> for Wrapper {
+    fn from(wrapper: &add_decimals::WrappedToken) -> Self {
+        Wrapper {
+            multiplier: wrapper.multiplier,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use add_decimals::WrappedToken;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_wrapper(added_decimals in 0..=9u32, amount in 1..u64::MAX) {
+            let mut wrapped_token = WrappedToken::default();
+            wrapped_token.multiplier = 10u64.pow(added_decimals);
+            let wrapper = Wrapper { multiplier: wrapped_token.multiplier };
+
+            assert_eq!(wrapper.deposit(amount), wrapped_token.to_wrapped_amount(amount));
+            assert_eq!(wrapper.withdraw(amount), wrapped_token.to_underlying_amount(amount));
+        }
+    }
+}
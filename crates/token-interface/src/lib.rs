@@ -0,0 +1,448 @@
+//! Token accounts and instructions of both the SPL Token and Token-2022 programs.
+//!
+//! Anchor's `Account<TokenAccount>`, `Account<Mint>` and `Program<Token>` only accept
+//! accounts of the SPL Token program. Programs which also accept Token-2022 take these
+//! accounts as `UncheckedAccount`s and load them with [TokenAccount::load] and [Mint::load],
+//! which check that the account is owned by either token program.
+//!
+//! The instructions mirror those of `anchor_spl::token`, but are sent to the token program
+//! of the [CpiContext] rather than to the SPL Token program.
+#![deny(clippy::unwrap_used)]
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::StateWithExtensions;
+use std::ops::Deref;
+
+pub use spl_token_2022;
+pub use spl_token_2022::instruction::AuthorityType;
+
+/// Returns true if `program_id` is the SPL Token or the Token-2022 program.
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::ID || *program_id == spl_token_2022::ID
+}
+
+/// Checks that `program` is the SPL Token or the Token-2022 program.
+pub fn assert_token_program(program: &AccountInfo) -> Result<()> {
+    if !is_token_program(program.key) {
+        return Err(error!(ErrorCode::InvalidProgramId));
+    }
+    Ok(())
+}
+
+/// Checks that `info` is owned by a token program.
+fn assert_owned_by_token_program(info: &AccountInfo) -> Result<()> {
+    if !is_token_program(info.owner) {
+        return Err(error!(ErrorCode::AccountOwnedByWrongProgram));
+    }
+    Ok(())
+}
+
+/// A token account of either token program.
+///
+/// Extensions of Token-2022 accounts are not loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenAccount {
+    /// The token program which owns the account.
+    pub token_program: Pubkey,
+    account: spl_token_2022::state::Account,
+}
+
+impl TokenAccount {
+    /// Loads an initialized token account owned by either token program.
+    pub fn load(info: &AccountInfo) -> Result<Self> {
+        assert_owned_by_token_program(info)?;
+        let data = info.try_borrow_data()?;
+        let account = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?.base;
+        Ok(Self {
+            token_program: *info.owner,
+            account,
+        })
+    }
+}
+
+impl Deref for TokenAccount {
+    type Target = spl_token_2022::state::Account;
+
+    fn deref(&self) -> &Self::Target {
+        &self.account
+    }
+}
+
+/// A mint of either token program.
+///
+/// Extensions of Token-2022 mints are not loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mint {
+    /// The token program which owns the mint.
+    pub token_program: Pubkey,
+    mint: spl_token_2022::state::Mint,
+}
+
+impl Mint {
+    /// Loads an initialized mint owned by either token program.
+    pub fn load(info: &AccountInfo) -> Result<Self> {
+        assert_owned_by_token_program(info)?;
+        let data = info.try_borrow_data()?;
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?.base;
+        Ok(Self {
+            token_program: *info.owner,
+            mint,
+        })
+    }
+}
+
+impl Deref for Mint {
+    type Target = spl_token_2022::state::Mint;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mint
+    }
+}
+
+/// Computes the Token-2022 transfer fee of transferring `amount` tokens of `mint` in `epoch`.
+///
+/// Mints without the transfer fee extension, including all SPL Token mints, charge no fee.
+pub fn transfer_fee(mint: &AccountInfo, epoch: u64, amount: u64) -> Result<u64> {
+    assert_owned_by_token_program(mint)?;
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    match state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or_else(|| ProgramError::InvalidArgument.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Transfers tokens without checking the mint.
+///
+/// Token-2022 rejects this for mints with a transfer fee, which must use [transfer_checked].
+pub fn transfer<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, Transfer<'info>>,
+    amount: u64,
+) -> Result<()> {
+    #[allow(deprecated)]
+    let ix = spl_token_2022::instruction::transfer(
+        ctx.program.key,
+        ctx.accounts.from.key,
+        ctx.accounts.to.key,
+        ctx.accounts.authority.key,
+        &[],
+        amount,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.from.clone(),
+            ctx.accounts.to.clone(),
+            ctx.accounts.authority.clone(),
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Transfers tokens, checking the mint and its decimals.
+///
+/// The recipient receives `amount` less the transfer fee of the mint, if any.
+pub fn transfer_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    let ix = spl_token_2022::instruction::transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from.key,
+        ctx.accounts.mint.key,
+        ctx.accounts.to.key,
+        ctx.accounts.authority.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.from.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.to.clone(),
+            ctx.accounts.authority.clone(),
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Mints tokens.
+pub fn mint_to<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, MintTo<'info>>,
+    amount: u64,
+) -> Result<()> {
+    let ix = spl_token_2022::instruction::mint_to(
+        ctx.program.key,
+        ctx.accounts.mint.key,
+        ctx.accounts.to.key,
+        ctx.accounts.authority.key,
+        &[],
+        amount,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.to.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.authority.clone(),
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Burns tokens.
+pub fn burn<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, Burn<'info>>,
+    amount: u64,
+) -> Result<()> {
+    let ix = spl_token_2022::instruction::burn(
+        ctx.program.key,
+        ctx.accounts.from.key,
+        ctx.accounts.mint.key,
+        ctx.accounts.authority.key,
+        &[],
+        amount,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.from.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.authority.clone(),
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Sets an authority of a mint or a token account.
+pub fn set_authority<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, SetAuthority<'info>>,
+    authority_type: AuthorityType,
+    new_authority: Option<Pubkey>,
+) -> Result<()> {
+    let ix = spl_token_2022::instruction::set_authority(
+        ctx.program.key,
+        ctx.accounts.account_or_mint.key,
+        new_authority.as_ref(),
+        authority_type,
+        ctx.accounts.current_authority.key,
+        &[],
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.account_or_mint.clone(),
+            ctx.accounts.current_authority.clone(),
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+#[derive(Accounts)]
+pub struct Transfer<'info> {
+    pub from: AccountInfo<'info>,
+    pub to: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferChecked<'info> {
+    pub from: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub to: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MintTo<'info> {
+    pub mint: AccountInfo<'info>,
+    pub to: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Burn<'info> {
+    pub mint: AccountInfo<'info>,
+    pub from: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAuthority<'info> {
+    pub current_authority: AccountInfo<'info>,
+    pub account_or_mint: AccountInfo<'info>,
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_option::COption;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use spl_token_2022::extension::transfer_fee::{
+        TransferFee, TransferFeeAmount, TransferFeeConfig,
+    };
+    use spl_token_2022::extension::{ExtensionType, StateWithExtensionsMut};
+
+    fn token_account_data(owner: Pubkey, mint: Pubkey, amount: u64) -> spl_token::state::Account {
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: spl_token::state::AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        }
+    }
+
+    fn load_account(program_id: &Pubkey, data: &mut [u8]) -> Result<TokenAccount> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info = AccountInfo::new(&key, false, true, &mut lamports, data, program_id, false, 0);
+        TokenAccount::load(&info)
+    }
+
+    #[test]
+    fn test_load_token_account() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(token_account_data(owner, mint, 42), &mut data).unwrap();
+
+        let account = load_account(&spl_token::ID, &mut data).unwrap();
+        assert_eq!(account.token_program, spl_token::ID);
+        assert_eq!(account.owner, owner);
+        assert_eq!(account.mint, mint);
+        assert_eq!(account.amount, 42);
+    }
+
+    #[test]
+    fn test_load_token_2022_account_with_extensions() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let len = ExtensionType::get_account_len::<spl_token_2022::state::Account>(&[
+            ExtensionType::TransferFeeAmount,
+        ]);
+        let mut data = vec![0; len];
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(
+                &mut data,
+            )
+            .unwrap();
+        state.init_extension::<TransferFeeAmount>().unwrap();
+        let base = token_account_data(owner, mint, 42);
+        state.base = spl_token_2022::state::Account {
+            mint: base.mint,
+            owner: base.owner,
+            amount: base.amount,
+            state: spl_token_2022::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let account = load_account(&spl_token_2022::ID, &mut data).unwrap();
+        assert_eq!(account.token_program, spl_token_2022::ID);
+        assert_eq!(account.owner, owner);
+        assert_eq!(account.mint, mint);
+        assert_eq!(account.amount, 42);
+    }
+
+    #[test]
+    fn test_load_rejects_other_programs() {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(
+            token_account_data(Pubkey::new_unique(), Pubkey::new_unique(), 42),
+            &mut data,
+        )
+        .unwrap();
+
+        let err = load_account(&Pubkey::new_unique(), &mut data).unwrap_err();
+        assert_eq!(
+            ProgramError::from(err),
+            ProgramError::from(error!(ErrorCode::AccountOwnedByWrongProgram))
+        );
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        let len = ExtensionType::get_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+        ]);
+        let mut data = vec![0; len];
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                .unwrap();
+        let config = state.init_extension::<TransferFeeConfig>().unwrap();
+        config.newer_transfer_fee = TransferFee {
+            epoch: 10.into(),
+            maximum_fee: 5_000.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        state.base = spl_token_2022::state::Mint {
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let (key, mut lamports) = (Pubkey::new_unique(), 0);
+        let mint = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &spl_token_2022::ID,
+            false,
+            0,
+        );
+        // the fee is rounded up and capped, and only applies from its epoch
+        assert_eq!(transfer_fee(&mint, 10, 1).unwrap(), 1);
+        assert_eq!(transfer_fee(&mint, 10, 10_000).unwrap(), 100);
+        assert_eq!(transfer_fee(&mint, 10, 1_000_000).unwrap(), 5_000);
+        assert_eq!(transfer_fee(&mint, 9, 10_000).unwrap(), 0);
+
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(
+            spl_token::state::Mint {
+                is_initialized: true,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        let mint = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &spl_token::ID,
+            false,
+            0,
+        );
+        assert_eq!(transfer_fee(&mint, 10, 10_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_is_token_program() {
+        assert!(is_token_program(&spl_token::ID));
+        assert!(is_token_program(&spl_token_2022::ID));
+        assert!(!is_token_program(&Pubkey::new_unique()));
+    }
+}
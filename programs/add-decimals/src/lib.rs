@@ -2,13 +2,20 @@
 //!
 //! The resulting token is an SPL Token that has more decimals than
 //! its underlying token.
+//!
+//! Either token may also be a Token-2022 token. The underlying token is transferred
+//! by the token program of the wrapper mint, unless the underlying mint and its token
+//! program are passed as the remaining accounts of a [UserStake], in that order.
+//! These are required for underlying mints with a transfer fee, and only the underlying
+//! tokens received by the wrapper are wrapped.
 #![deny(clippy::unwrap_used)]
 #![deny(rustdoc::all)]
 #![allow(rustdoc::missing_doc_code_examples)]
 
-use anchor_lang::{prelude::*, solana_program::pubkey::PUBKEY_BYTES};
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_pack::Pack, pubkey::PUBKEY_BYTES};
 use continuation_router::{ActionType, RouterActionProcessor};
+use saber_token_interface::{self as token_interface, Mint, TokenAccount};
 use vipers::prelude::*;
 use vipers::program_err;
 
@@ -41,14 +48,11 @@ pub mod add_decimals {
     /// 4. Run the initialize_wrapper instruction.
     #[access_control(ctx.accounts.validate())]
     pub fn initialize_wrapper(ctx: Context<InitializeWrapper>, _nonce: u8) -> Result<()> {
-        let decimals = ctx.accounts.wrapper_mint.decimals;
-        require!(
-            decimals >= ctx.accounts.underlying_mint.decimals,
-            InitWrapperDecimalsTooLow
-        );
+        let decimals = Mint::load(&ctx.accounts.wrapper_mint)?.decimals;
+        let underlying_decimals = Mint::load(&ctx.accounts.underlying_mint)?.decimals;
+        require!(decimals >= underlying_decimals, InitWrapperDecimalsTooLow);
 
-        let added_decimals =
-            unwrap_int!(decimals.checked_sub(ctx.accounts.underlying_mint.decimals));
+        let added_decimals = unwrap_int!(decimals.checked_sub(underlying_decimals));
         let multiplier = unwrap_int!(10u64.checked_pow(added_decimals as u32));
 
         let wrapper = &mut ctx.accounts.wrapper;
@@ -71,24 +75,36 @@ pub mod add_decimals {
     }
 
     /// Deposits underlying tokens to mint wrapped tokens.
+    ///
+    /// Only the underlying tokens received by the wrapper are wrapped,
+    /// which excludes any Token-2022 transfer fee.
     #[access_control(ctx.accounts.validate())]
-    pub fn deposit(ctx: Context<UserStake>, deposit_amount: u64) -> Result<()> {
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, UserStake<'info>>,
+        deposit_amount: u64,
+    ) -> Result<()> {
         require!(deposit_amount > 0, ZeroAmount);
+        let user_underlying_tokens = TokenAccount::load(&ctx.accounts.user_underlying_tokens)?;
         require!(
-            ctx.accounts.user_underlying_tokens.amount >= deposit_amount,
+            user_underlying_tokens.amount >= deposit_amount,
             InsufficientUnderlyingBalance
         );
 
-        let mint_amount = unwrap_int!(ctx.accounts.wrapper.to_wrapped_amount(deposit_amount));
-
         // Deposit underlying and mint wrapped
-        ctx.accounts.deposit_underlying(deposit_amount)?;
+        let initial_balance = TokenAccount::load(&ctx.accounts.wrapper_underlying_tokens)?.amount;
+        ctx.accounts
+            .deposit_underlying(ctx.remaining_accounts, deposit_amount)?;
+        let received_amount =
+            unwrap_int!(TokenAccount::load(&ctx.accounts.wrapper_underlying_tokens)?
+                .amount
+                .checked_sub(initial_balance));
+        let mint_amount = unwrap_int!(ctx.accounts.wrapper.to_wrapped_amount(received_amount));
         ctx.accounts.mint_wrapped(mint_amount)?;
 
         emit!(DepositEvent {
-            owner: ctx.accounts.user_underlying_tokens.owner,
-            underlying_mint: ctx.accounts.user_underlying_tokens.mint,
-            wrapped_mint: ctx.accounts.user_wrapped_tokens.mint,
+            owner: user_underlying_tokens.owner,
+            underlying_mint: user_underlying_tokens.mint,
+            wrapped_mint: ctx.accounts.wrapper_mint.key(),
             deposit_amount,
             mint_amount
         });
@@ -97,10 +113,13 @@ pub mod add_decimals {
 
     /// Deposits wrapped tokens to withdraw underlying tokens.
     #[access_control(ctx.accounts.validate())]
-    pub fn withdraw(ctx: Context<UserStake>, max_burn_amount: u64) -> Result<()> {
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, UserStake<'info>>,
+        max_burn_amount: u64,
+    ) -> Result<()> {
         require!(max_burn_amount > 0, ZeroAmount);
         require!(
-            ctx.accounts.user_wrapped_tokens.amount >= max_burn_amount,
+            TokenAccount::load(&ctx.accounts.user_wrapped_tokens)?.amount >= max_burn_amount,
             InsufficientWrappedBalance
         );
 
@@ -112,12 +131,13 @@ pub mod add_decimals {
 
         // Burn wrapped and withdraw underlying
         ctx.accounts.burn_wrapped(burn_amount)?;
-        ctx.accounts.withdraw_underlying(withdraw_amount)?;
+        ctx.accounts
+            .withdraw_underlying(ctx.remaining_accounts, withdraw_amount)?;
 
         emit!(WithdrawEvent {
-            owner: ctx.accounts.user_underlying_tokens.owner,
-            underlying_mint: ctx.accounts.user_underlying_tokens.mint,
-            wrapped_mint: ctx.accounts.user_wrapped_tokens.mint,
+            owner: ctx.accounts.owner.key(),
+            underlying_mint: ctx.accounts.wrapper.wrapper_underlying_mint,
+            wrapped_mint: ctx.accounts.wrapper_mint.key(),
             withdraw_amount,
             burn_amount,
             dust_amount,
@@ -126,8 +146,8 @@ pub mod add_decimals {
     }
 
     /// Burn all wrapped tokens to withdraw the underlying tokens.
-    pub fn withdraw_all(ctx: Context<UserStake>) -> Result<()> {
-        let max_burn_amount = ctx.accounts.user_wrapped_tokens.amount;
+    pub fn withdraw_all<'info>(ctx: Context<'_, '_, '_, 'info, UserStake<'info>>) -> Result<()> {
+        let max_burn_amount = TokenAccount::load(&ctx.accounts.user_wrapped_tokens)?.amount;
         withdraw(ctx, max_burn_amount)
     }
 
@@ -136,7 +156,7 @@ pub mod add_decimals {
 
     impl<'info> RouterActionProcessor<'info, UserStake<'info>> for AddDecimals {
         fn process_action(
-            ctx: Context<UserStake>,
+            ctx: Context<'_, '_, '_, 'info, UserStake<'info>>,
             action: u16,
             amount_in: u64,
             _minimum_amount_out: u64,
@@ -165,7 +185,7 @@ pub struct InitializeWrapper<'info> {
         seeds = [
             b"anchor".as_ref(),
             underlying_mint.to_account_info().key.as_ref(),
-            &[wrapper_mint.decimals]
+            &[Mint::load(&wrapper_mint)?.decimals]
         ],
         bump,
         space = 8 + WrappedToken::LEN,
@@ -174,13 +194,16 @@ pub struct InitializeWrapper<'info> {
     pub wrapper: Account<'info, WrappedToken>,
 
     /// Token account containing the underlying tokens.
-    pub wrapper_underlying_tokens: Account<'info, TokenAccount>,
+    /// CHECK: Loaded as a token account of either token program in [InitializeWrapper::validate].
+    pub wrapper_underlying_tokens: UncheckedAccount<'info>,
 
     /// Mint of the underlying token.
-    pub underlying_mint: Account<'info, Mint>,
+    /// CHECK: Loaded as a mint of either token program by [add_decimals::initialize_wrapper].
+    pub underlying_mint: UncheckedAccount<'info>,
 
     /// Mint of the wrapper.
-    pub wrapper_mint: Account<'info, Mint>,
+    /// CHECK: Loaded as a mint of either token program in [InitializeWrapper::validate].
+    pub wrapper_mint: UncheckedAccount<'info>,
 
     /// Payer of the newly created decimal wrapper.
     #[account(mut)]
@@ -197,35 +220,39 @@ impl<'info> InitializeWrapper<'info> {
     /// Validates ownership of the accounts of the wrapper.
     pub fn validate(&self) -> Result<()> {
         // underlying account checks
-        require!(
-            self.wrapper_underlying_tokens.amount == 0,
-            InitNonEmptyAccount
-        );
+        let wrapper_underlying_tokens = TokenAccount::load(&self.wrapper_underlying_tokens)?;
+        require!(wrapper_underlying_tokens.amount == 0, InitNonEmptyAccount);
         assert_keys_eq!(
-            self.wrapper_underlying_tokens.owner,
+            wrapper_underlying_tokens.owner,
             self.wrapper,
             InitWrapperUnderlyingOwnerMismatch
         );
         assert_keys_eq!(
-            self.wrapper_underlying_tokens.mint,
+            wrapper_underlying_tokens.mint,
             self.underlying_mint,
             InitWrapperUnderlyingMintMismatch
         );
-        invariant!(self.wrapper_underlying_tokens.delegate.is_none());
-        invariant!(self.wrapper_underlying_tokens.close_authority.is_none());
+        invariant!(wrapper_underlying_tokens.delegate.is_none());
+        invariant!(wrapper_underlying_tokens.close_authority.is_none());
 
         // mint checks
+        let wrapper_mint = Mint::load(&self.wrapper_mint)?;
         assert_keys_eq!(
-            self.wrapper_mint.mint_authority.unwrap(),
+            wrapper_mint.mint_authority.unwrap(),
             self.wrapper,
             InitMintAuthorityMismatch
         );
         assert_keys_eq!(
-            self.wrapper_mint.freeze_authority.unwrap(),
+            wrapper_mint.freeze_authority.unwrap(),
             self.wrapper,
             InitFreezeAuthorityMismatch
         );
-        require!(self.wrapper_mint.supply == 0, InitWrapperSupplyNonZero);
+        require!(wrapper_mint.supply == 0, InitWrapperSupplyNonZero);
+        // extensions such as a mint close authority could change the wrapper mint
+        require!(
+            self.wrapper_mint.data_len() == token_interface::spl_token_2022::state::Mint::LEN,
+            InitWrapperMintExtensions
+        );
         Ok(())
     }
 }
@@ -237,26 +264,32 @@ pub struct UserStake<'info> {
     pub wrapper: Account<'info, WrappedToken>,
 
     /// Mint of the wrapper.
+    /// CHECK: Checked against the wrapper.
     #[account(mut)]
-    pub wrapper_mint: Account<'info, Mint>,
+    pub wrapper_mint: UncheckedAccount<'info>,
 
     /// Wrapper's token account containing the underlying tokens.
+    /// CHECK: Checked against the wrapper.
     #[account(mut)]
-    pub wrapper_underlying_tokens: Account<'info, TokenAccount>,
+    pub wrapper_underlying_tokens: UncheckedAccount<'info>,
 
     /// Owner of the user underlying token accounts.
     pub owner: Signer<'info>,
 
     /// User's token account for the underlying tokens.
+    /// CHECK: Loaded as a token account of either token program in [UserStake::validate].
     #[account(mut)]
-    pub user_underlying_tokens: Account<'info, TokenAccount>,
+    pub user_underlying_tokens: UncheckedAccount<'info>,
 
     /// User's token account for wrapped tokens.
+    /// CHECK: Loaded as a token account of either token program in [UserStake::validate].
     #[account(mut)]
-    pub user_wrapped_tokens: Account<'info, TokenAccount>,
+    pub user_wrapped_tokens: UncheckedAccount<'info>,
 
-    /// SPL Token program.
-    pub token_program: Program<'info, Token>,
+    /// Token program of the wrapper mint, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::InvalidTokenProgram)]
+    pub token_program: UncheckedAccount<'info>,
 }
 
 impl<'info> Validate<'info> for UserStake<'info> {
@@ -267,13 +300,15 @@ impl<'info> Validate<'info> for UserStake<'info> {
             self.wrapper.wrapper_underlying_tokens,
             self.wrapper_underlying_tokens
         );
-        assert_keys_eq!(self.user_underlying_tokens.owner, self.owner);
+        let user_underlying_tokens = TokenAccount::load(&self.user_underlying_tokens)?;
+        assert_keys_eq!(user_underlying_tokens.owner, self.owner);
         assert_keys_eq!(
-            self.user_underlying_tokens.mint,
+            user_underlying_tokens.mint,
             self.wrapper.wrapper_underlying_mint
         );
-        assert_keys_eq!(self.user_wrapped_tokens.owner, self.owner);
-        assert_keys_eq!(self.user_wrapped_tokens.mint, self.wrapper_mint);
+        let user_wrapped_tokens = TokenAccount::load(&self.user_wrapped_tokens)?;
+        assert_keys_eq!(user_wrapped_tokens.owner, self.owner);
+        assert_keys_eq!(user_wrapped_tokens.mint, self.wrapper_mint);
         Ok(())
     }
 }
@@ -356,6 +391,13 @@ pub enum ErrorCode {
 
     #[msg("Freeze authority mismatch")]
     InitFreezeAuthorityMismatch,
+
+    #[msg("Token program must be the SPL Token or the Token-2022 program")]
+    InvalidTokenProgram,
+    #[msg("Remaining accounts must be the underlying mint and its token program")]
+    InvalidUnderlyingAccounts,
+    #[msg("Wrapper mint must not have Token-2022 extensions")]
+    InitWrapperMintExtensions,
 }
 
 #[cfg(test)]
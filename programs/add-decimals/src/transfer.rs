@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token;
+use saber_token_interface as token_interface;
+use vipers::prelude::*;
 
 /// Returns the program-derived-address seeds used for creating the associated
 /// account.
@@ -17,16 +18,15 @@ use crate::UserStake;
 
 /// Creates a token instruction signed by the user.
 macro_rules! perform_as_user {
-    ($self:expr, $method:ident, $accounts:expr, $amount:expr) => {{
-        let cpi_program = $self.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, $accounts);
-        token::$method(cpi_ctx, $amount)
+    ($cpi_program:expr, $method:ident, $accounts:expr, $($arg:expr),+) => {{
+        let cpi_ctx = CpiContext::new($cpi_program, $accounts);
+        token_interface::$method(cpi_ctx, $($arg),+)
     }};
 }
 
 /// Creates a token instruction performed by the wrapper.
 macro_rules! perform_as_wrapper {
-    ($self:expr, $accounts:expr, $method:ident, $amount:expr) => {{
+    ($self:expr, $cpi_program:expr, $accounts:expr, $method:ident, $($arg:expr),+) => {{
         let seeds = $crate::associated_seeds!(
             $self.wrapper,
             $self.wrapper.wrapper_underlying_mint.as_ref(),
@@ -34,51 +34,150 @@ macro_rules! perform_as_wrapper {
             &[$self.wrapper.nonce()]
         );
         let signer = &[&seeds[..]];
-        let cpi_program = $self.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, $accounts, signer);
-        token::$method(cpi_ctx, $amount)
+        let cpi_ctx = CpiContext::new_with_signer($cpi_program, $accounts, signer);
+        token_interface::$method(cpi_ctx, $($arg),+)
     }};
 }
 
 /// Helper methods for interacting with the user stake.
 impl<'info> UserStake<'info> {
     /// Transfer user's tokens to wrapper.
-    pub fn deposit_underlying(&self, amount: u64) -> Result<()> {
-        let cpi_accounts = token::Transfer {
-            from: self.user_underlying_tokens.to_account_info(),
-            to: self.wrapper_underlying_tokens.to_account_info(),
-            authority: self.owner.to_account_info(),
-        };
-        perform_as_user!(self, transfer, cpi_accounts, amount)
+    pub fn deposit_underlying(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        amount: u64,
+    ) -> Result<()> {
+        let from = self.user_underlying_tokens.to_account_info();
+        let to = self.wrapper_underlying_tokens.to_account_info();
+        let authority = self.owner.to_account_info();
+        match self.underlying_accounts(remaining_accounts)? {
+            Some((mint, token_program)) => {
+                let decimals = token_interface::Mint::load(&mint)?.decimals;
+                let cpi_accounts = token_interface::TransferChecked {
+                    from,
+                    mint,
+                    to,
+                    authority,
+                };
+                perform_as_user!(
+                    token_program,
+                    transfer_checked,
+                    cpi_accounts,
+                    amount,
+                    decimals
+                )
+            }
+            None => {
+                let cpi_accounts = token_interface::Transfer {
+                    from,
+                    to,
+                    authority,
+                };
+                perform_as_user!(
+                    self.token_program.to_account_info(),
+                    transfer,
+                    cpi_accounts,
+                    amount
+                )
+            }
+        }
     }
 
     /// Burn user's wrapper tokens.
     pub fn burn_wrapped(&self, amount: u64) -> Result<()> {
-        let cpi_accounts = token::Burn {
+        let cpi_accounts = token_interface::Burn {
             mint: self.wrapper_mint.to_account_info(),
             from: self.user_wrapped_tokens.to_account_info(),
             authority: self.owner.to_account_info(),
         };
-        perform_as_user!(self, burn, cpi_accounts, amount)
+        perform_as_user!(
+            self.token_program.to_account_info(),
+            burn,
+            cpi_accounts,
+            amount
+        )
     }
 
     /// Mint wrapped tokens to user wrapped token account.
     pub fn mint_wrapped(&self, amount: u64) -> Result<()> {
-        let cpi_accounts = token::MintTo {
+        let cpi_accounts = token_interface::MintTo {
             mint: self.wrapper_mint.to_account_info(),
             to: self.user_wrapped_tokens.to_account_info(),
             authority: self.wrapper.to_account_info(),
         };
-        perform_as_wrapper!(self, cpi_accounts, mint_to, amount)
+        perform_as_wrapper!(
+            self,
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            mint_to,
+            amount
+        )
     }
 
     /// Transfer underlying tokens from wrapper to user.
-    pub fn withdraw_underlying(&self, amount: u64) -> Result<()> {
-        let cpi_accounts = token::Transfer {
-            from: self.wrapper_underlying_tokens.to_account_info(),
-            to: self.user_underlying_tokens.to_account_info(),
-            authority: self.wrapper.to_account_info(),
-        };
-        perform_as_wrapper!(self, cpi_accounts, transfer, amount)
+    pub fn withdraw_underlying(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+        amount: u64,
+    ) -> Result<()> {
+        let from = self.wrapper_underlying_tokens.to_account_info();
+        let to = self.user_underlying_tokens.to_account_info();
+        let authority = self.wrapper.to_account_info();
+        match self.underlying_accounts(remaining_accounts)? {
+            Some((mint, token_program)) => {
+                let decimals = token_interface::Mint::load(&mint)?.decimals;
+                let cpi_accounts = token_interface::TransferChecked {
+                    from,
+                    mint,
+                    to,
+                    authority,
+                };
+                perform_as_wrapper!(
+                    self,
+                    token_program,
+                    cpi_accounts,
+                    transfer_checked,
+                    amount,
+                    decimals
+                )
+            }
+            None => {
+                let cpi_accounts = token_interface::Transfer {
+                    from,
+                    to,
+                    authority,
+                };
+                perform_as_wrapper!(
+                    self,
+                    self.token_program.to_account_info(),
+                    cpi_accounts,
+                    transfer,
+                    amount
+                )
+            }
+        }
+    }
+
+    /// Returns the underlying mint and its token program, if they are the remaining accounts.
+    ///
+    /// Otherwise, the underlying token is of the token program of the wrapper mint,
+    /// and may not have a Token-2022 transfer fee.
+    fn underlying_accounts(
+        &self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<Option<(AccountInfo<'info>, AccountInfo<'info>)>> {
+        match remaining_accounts {
+            [] => Ok(None),
+            [mint, token_program] => {
+                assert_keys_eq!(
+                    mint.key(),
+                    self.wrapper.wrapper_underlying_mint,
+                    InvalidUnderlyingAccounts
+                );
+                token_interface::assert_token_program(token_program)?;
+                Ok(Some((mint.clone(), token_program.clone())))
+            }
+            _ => Err(error!(crate::ErrorCode::InvalidUnderlyingAccounts)),
+        }
     }
 }
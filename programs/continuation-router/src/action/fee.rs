@@ -0,0 +1,82 @@
+//! Step implementation for taking a fee.
+
+use saber_token_interface::TokenAccount;
+
+use crate::action::ProcessAction;
+use crate::*;
+
+/// Computes the fee of `fee_bps` basis points of `amount`, capped at `max_fee`.
+pub fn compute_fee(amount: u64, fee_bps: u16, max_fee: u64) -> Option<u64> {
+    let fee = (amount as u128)
+        .checked_mul(fee_bps.into())?
+        .checked_div(10_000)?;
+    Some(u64::try_from(fee).ok()?.min(max_fee))
+}
+
+impl<'info> ProcessAction<'info> for TakeFee<'info> {
+    /// Transfers the fee to the referrer.
+    ///
+    /// Fees in a Token-2022 mint with a transfer fee require the mint as the first
+    /// remaining account, see [processor::transfer].
+    fn process(
+        ctx: &ActionContext<'_, '_, '_, 'info, Self>,
+        amount_in: u64,
+        _minimum_amount_out: u64,
+    ) -> Result<()> {
+        let (fee_bps, max_fee) = match ctx.args {
+            ActionArgs::TakeFee { fee_bps, max_fee } => (fee_bps, max_fee),
+            _ => return Err(error!(ErrorCode::InvalidActionArgs)),
+        };
+        let action = ctx.action;
+        let mint = TokenAccount::load(&action.input)?.mint;
+        assert_keys_eq!(
+            TokenAccount::load(&action.referrer)?.mint,
+            mint,
+            ReferrerMintMismatch
+        );
+
+        let fee = unwrap_int!(compute_fee(amount_in, fee_bps, max_fee));
+        if fee > 0 {
+            processor::transfer(
+                &ctx.token_program,
+                &action.input,
+                &action.referrer,
+                &ctx.owner,
+                mint,
+                ctx.remaining_accounts,
+                fee,
+            )?;
+        }
+
+        emit!(FeeTakenEvent {
+            owner: ctx.owner.key(),
+            referrer: action.referrer.key(),
+            fee: TokenAmount::new(mint, fee),
+        });
+        Ok(())
+    }
+
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input.to_account_info()
+    }
+
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.input.to_account_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_fee() {
+        assert_eq!(compute_fee(1_000_000, 30, u64::MAX), Some(3_000));
+        assert_eq!(compute_fee(1_000_000, 30, 1_000), Some(1_000));
+        assert_eq!(compute_fee(1_000_000, 0, u64::MAX), Some(0));
+        assert_eq!(
+            compute_fee(u64::MAX, MAX_FEE_BPS, u64::MAX),
+            Some(u64::MAX / 10)
+        );
+    }
+}
@@ -1,8 +1,8 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
 
 use crate::processor::ActionContext;
 
+pub mod fee;
 pub mod stable_swap;
 
 pub trait ProcessAction<'info>: Sized {
@@ -13,7 +13,9 @@ pub trait ProcessAction<'info>: Sized {
         minimum_amount_out: u64,
     ) -> Result<()>;
 
-    fn input_account(&self) -> &Account<'info, TokenAccount>;
+    /// The token account the action takes its input from.
+    fn input_account(&self) -> AccountInfo<'info>;
 
-    fn output_account(&self) -> &Account<'info, TokenAccount>;
+    /// The token account the action sends its output to.
+    fn output_account(&self) -> AccountInfo<'info>;
 }
@@ -16,6 +16,29 @@ macro_rules! build_swap_context {
     }};
 }
 
+/// Returns true if `reserve` is the token A reserve account of the `swap`.
+fn is_token_a_reserve(swap: &AccountInfo, reserve: &Pubkey) -> Result<bool> {
+    let swap_info =
+        stable_swap_anchor::SwapInfo::try_deserialize(&mut &swap.try_borrow_data()?[..])?;
+    Ok(swap_info.token_a.reserves == *reserve)
+}
+
+/// Computes the amount of the other token to deposit alongside `amount_in`
+/// such that the deposit matches the ratio of the pool's reserves.
+pub fn compute_balanced_amount(
+    amount_in: u64,
+    input_reserve: u64,
+    other_reserve: u64,
+) -> Option<u64> {
+    if input_reserve == 0 {
+        return None;
+    }
+    let amount = (amount_in as u128)
+        .checked_mul(other_reserve.into())?
+        .checked_div(input_reserve.into())?;
+    u64::try_from(amount).ok()
+}
+
 impl<'info> Deref for SSDepositA<'info> {
     type Target = SSDeposit<'info>;
     fn deref(&self) -> &Self::Target {
@@ -49,12 +72,12 @@ impl<'info> ProcessAction<'info> for SSDepositA<'info> {
         stable_swap_anchor::deposit(cpi_ctx, amount_in, 0, minimum_amount_out)
     }
 
-    fn input_account(&self) -> &Account<'info, TokenAccount> {
-        &self.input_a.user
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input_a.user.to_account_info()
     }
 
-    fn output_account(&self) -> &Account<'info, TokenAccount> {
-        &self.output_lp
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.output_lp.to_account_info()
     }
 }
 
@@ -77,12 +100,71 @@ impl<'info> ProcessAction<'info> for SSDepositB<'info> {
         stable_swap_anchor::deposit(cpi_ctx, 0, amount_in, minimum_amount_out)
     }
 
-    fn input_account(&self) -> &Account<'info, TokenAccount> {
-        &self.input_b.user
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input_b.user.to_account_info()
     }
 
-    fn output_account(&self) -> &Account<'info, TokenAccount> {
-        &self.output_lp
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.output_lp.to_account_info()
+    }
+}
+
+impl<'info> ProcessAction<'info> for SSDepositBalanced<'info> {
+    /// Runs the deposit component instruction with both tokens.
+    fn process(
+        ctx: &ActionContext<'_, '_, '_, 'info, Self>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<()> {
+        let deposit = ctx.action;
+        assert_keys_eq!(
+            deposit.other_input.user.owner,
+            ctx.route_owner,
+            InputOwnerMismatch
+        );
+
+        let input_reserve: Account<TokenAccount> = Account::try_from(&deposit.input.reserve)?;
+        let other_reserve: Account<TokenAccount> = Account::try_from(&deposit.other_input.reserve)?;
+        let other_amount = unwrap_int!(compute_balanced_amount(
+            amount_in,
+            input_reserve.amount,
+            other_reserve.amount
+        ));
+
+        let (input_a, input_b, amount_a, amount_b) =
+            if is_token_a_reserve(&deposit.swap.swap, deposit.input.reserve.key)? {
+                (
+                    &deposit.input,
+                    &deposit.other_input,
+                    amount_in,
+                    other_amount,
+                )
+            } else {
+                (
+                    &deposit.other_input,
+                    &deposit.input,
+                    other_amount,
+                    amount_in,
+                )
+            };
+
+        let cpi_accounts = stable_swap_anchor::Deposit {
+            user: build_swap_context!(deposit, ctx),
+            input_a: input_a.into(),
+            input_b: input_b.into(),
+            output_lp: deposit.output_lp.to_account_info(),
+            pool_mint: deposit.pool_mint.clone(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.swap_program.clone(), cpi_accounts);
+        stable_swap_anchor::deposit(cpi_ctx, amount_a, amount_b, minimum_amount_out)
+    }
+
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input.user.to_account_info()
+    }
+
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.output_lp.to_account_info()
     }
 }
 
@@ -105,12 +187,53 @@ impl<'info> ProcessAction<'info> for SSWithdrawOne<'info> {
         stable_swap_anchor::withdraw_one(cpi_ctx, amount_in, minimum_amount_out)
     }
 
-    fn input_account(&self) -> &Account<'info, TokenAccount> {
-        &self.input_lp
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input_lp.to_account_info()
     }
 
-    fn output_account(&self) -> &Account<'info, TokenAccount> {
-        &self.output.user_token.user
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.output.user_token.user.to_account_info()
+    }
+}
+
+impl<'info> ProcessAction<'info> for SSWithdraw<'info> {
+    /// Runs the withdraw component instruction.
+    fn process(
+        ctx: &ActionContext<'_, '_, '_, 'info, Self>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<()> {
+        let action = ctx.action;
+        assert_keys_eq!(
+            action.other_output.user_token.user.owner,
+            ctx.route_owner,
+            OutputOwnerMismatch
+        );
+
+        let (output_a, output_b, minimum_a, minimum_b) =
+            if is_token_a_reserve(&action.swap.swap, action.output.user_token.reserve.key)? {
+                (&action.output, &action.other_output, minimum_amount_out, 0)
+            } else {
+                (&action.other_output, &action.output, 0, minimum_amount_out)
+            };
+
+        let cpi_accounts = stable_swap_anchor::Withdraw {
+            user: build_swap_context!(action, ctx),
+            input_lp: action.input_lp.to_account_info(),
+            pool_mint: action.pool_mint.clone(),
+            output_a: output_a.into(),
+            output_b: output_b.into(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.swap_program.clone(), cpi_accounts);
+        stable_swap_anchor::withdraw(cpi_ctx, amount_in, minimum_a, minimum_b)
+    }
+
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input_lp.to_account_info()
+    }
+
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.output.user_token.user.to_account_info()
     }
 }
 
@@ -131,12 +254,12 @@ impl<'info> ProcessAction<'info> for SSSwap<'info> {
         stable_swap_anchor::swap(cpi_ctx, amount_in, minimum_amount_out)
     }
 
-    fn input_account(&self) -> &Account<'info, TokenAccount> {
-        &self.input.user
+    fn input_account(&self) -> AccountInfo<'info> {
+        self.input.user.to_account_info()
     }
 
-    fn output_account(&self) -> &Account<'info, TokenAccount> {
-        &self.output.user_token.user
+    fn output_account(&self) -> AccountInfo<'info> {
+        self.output.user_token.user.to_account_info()
     }
 }
 
@@ -157,3 +280,17 @@ impl<'info> From<&SwapOutput<'info>> for stable_swap_anchor::SwapOutput<'info> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_balanced_amount() {
+        assert_eq!(compute_balanced_amount(100, 1_000, 2_000), Some(200));
+        assert_eq!(compute_balanced_amount(3, 1_000, 1_000), Some(3));
+        assert_eq!(compute_balanced_amount(1, 3, 2), Some(0));
+        assert_eq!(compute_balanced_amount(100, 0, 2_000), None);
+        assert_eq!(compute_balanced_amount(u64::MAX, 1, 2), None);
+    }
+}
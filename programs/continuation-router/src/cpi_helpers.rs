@@ -0,0 +1,59 @@
+//! Helpers for calling the router via CPI.
+//!
+//! The Anchor-generated [crate::cpi] module (enabled by the `cpi` feature) contains a
+//! function for every instruction, and [crate::cpi::accounts] contains the accounts of each.
+//! This module contains helpers for the parts of a route that the generated code does not cover.
+//!
+//! Routing on behalf of a PDA works like routing on behalf of a wallet, except that every
+//! [CpiContext] must be signed with the seeds of the PDA:
+//!
+//! 1. Allocate a continuation account with [create_continuation].
+//! 2. Call [crate::cpi::begin_v2] with the PDA as the owner. [crate::cpi::begin] cannot be used
+//!    since it requires the payer to sign for a system transfer.
+//! 3. Call an action instruction such as [crate::cpi::ss_swap] for every step.
+//! 4. Call [end], which returns the realized amounts of the route.
+
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+use vipers::unwrap_opt;
+
+use crate::{ContinuationV2, RouteResult};
+
+/// Size of a [ContinuationV2] account, including the discriminator.
+pub const CONTINUATION_ACCOUNT_SIZE: usize = 8 + ContinuationV2::LEN;
+
+/// Creates an empty, rent-exempt [ContinuationV2] account to be passed to [crate::cpi::begin_v2].
+///
+/// The `continuation` must sign, either as a keypair or with the provided `signer_seeds`.
+pub fn create_continuation<'info>(
+    payer: AccountInfo<'info>,
+    continuation: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            system_program,
+            system_program::CreateAccount {
+                from: payer,
+                to: continuation,
+            },
+            signer_seeds,
+        ),
+        Rent::get()?.minimum_balance(CONTINUATION_ACCOUNT_SIZE),
+        CONTINUATION_ACCOUNT_SIZE as u64,
+        &crate::ID,
+    )
+}
+
+/// Ends a route via [crate::cpi::end] and returns its realized amounts.
+pub fn end<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, crate::cpi::accounts::End<'info>>,
+) -> Result<RouteResult> {
+    crate::cpi::end(ctx)?;
+    Ok(unwrap_opt!(
+        RouteResult::from_return_data(),
+        MissingReturnData
+    ))
+}
@@ -7,33 +7,62 @@
 
 use continuation_router_syn::router_action;
 
-use anchor_lang::{prelude::*, solana_program::pubkey::PUBKEY_BYTES};
-use anchor_spl::token::{Token, TokenAccount};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{bpf_loader_upgradeable, program_option::COption, pubkey::PUBKEY_BYTES},
+};
+use anchor_spl::token::{self, Token, TokenAccount};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use saber_token_interface as token_interface;
 use vipers::prelude::*;
 
 pub mod action;
+#[cfg(feature = "cpi")]
+pub mod cpi_helpers;
 pub mod processor;
+pub mod return_data;
+pub mod route;
+pub mod simulate;
+pub mod state;
 
 use crate::action::ProcessAction;
-use crate::processor::{ActionContext, Processor};
+use crate::processor::{ActionArgs, ActionContext, Processor};
+pub use crate::return_data::RouteResult;
 
 declare_id!("Crt7UoUR6QgrFrN7j8rmSQpUTNWNSitSwWvsWGf1qZ5t");
 
 macro_rules! process_action {
-    ($ctx:expr) => {{
+    // the swap program must have been checked against the config by the caller
+    (@checked $ctx:expr, $args:expr) => {{
         let ctx = $ctx;
-        let cont = &mut ctx.accounts.continuation.continuation;
-        let action = &ctx.accounts.action;
-        let action_ctx = &ActionContext {
-            program_id: ctx.program_id,
-            action,
-            remaining_accounts: ctx.remaining_accounts,
-            token_program: ctx.accounts.continuation.token_program.clone(),
-            swap_program: ctx.accounts.continuation.swap_program.to_account_info(),
-            owner: ctx.accounts.continuation.owner.to_account_info(),
-        };
-        Processor::process(action_ctx, cont)
+        let accounts = &ctx.accounts.continuation;
+        state::load_mut(&accounts.continuation, |cont| {
+            state::assert_has_one(cont.authority(), accounts.owner.key())?;
+            let action = &ctx.accounts.action;
+            let action_ctx = &ActionContext {
+                program_id: ctx.program_id,
+                action,
+                remaining_accounts: ctx.remaining_accounts,
+                token_program: accounts.token_program.clone(),
+                swap_program: accounts.swap_program.to_account_info(),
+                owner: accounts.owner.to_account_info(),
+                route_owner: cont.owner,
+                args: $args,
+            };
+            Processor::process(action_ctx, cont)
+        })
+    }};
+    ($ctx:expr) => {
+        process_action!($ctx, ActionArgs::None)
+    };
+    ($ctx:expr, $args:expr) => {{
+        let ctx = $ctx;
+        let accounts = &ctx.accounts.continuation;
+        invariant!(
+            accounts.config.is_approved(accounts.swap_program.key),
+            SwapProgramNotApproved
+        );
+        process_action!(@checked ctx, $args)
     }};
 }
 
@@ -41,6 +70,150 @@ macro_rules! process_action {
 pub mod continuation_router {
     use super::*;
 
+    /// Creates the [RouterConfig]. Only the upgrade authority of the router may create it,
+    /// and becomes its admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.bump = unwrap_bump!(ctx, "config");
+        config.admin = ctx.accounts.admin.key();
+        Ok(())
+    }
+
+    /// Transfers the admin role of the [RouterConfig] to another account.
+    pub fn set_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+        config.admin = new_admin;
+
+        emit!(AdminSetEvent {
+            previous_admin,
+            admin: new_admin,
+        });
+        Ok(())
+    }
+
+    /// Approves a program to be used as the swap program of actions.
+    pub fn add_approved_program(ctx: Context<UpdateConfig>, program: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        invariant!(
+            !config.approved_programs.contains(&program),
+            ProgramAlreadyApproved
+        );
+        invariant!(
+            config.approved_programs.len() < MAX_APPROVED_PROGRAMS,
+            TooManyApprovedPrograms
+        );
+        config.approved_programs.push(program);
+
+        emit!(ApprovedProgramAddedEvent {
+            admin: config.admin,
+            program,
+        });
+        Ok(())
+    }
+
+    /// Revokes the approval of a program.
+    pub fn remove_approved_program(ctx: Context<UpdateConfig>, program: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let index = unwrap_opt!(
+            config.approved_programs.iter().position(|p| *p == program),
+            ProgramNotApproved
+        );
+        config.approved_programs.swap_remove(index);
+
+        emit!(ApprovedProgramRemovedEvent {
+            admin: config.admin,
+            program,
+        });
+        Ok(())
+    }
+
+    /// Registers an adapter program which owns the given action code.
+    ///
+    /// The adapter must implement [RouterActionProcessor], and is called with
+    /// `action_code` by [continuation_router::external_action].
+    pub fn register_adapter(
+        ctx: Context<UpdateConfig>,
+        program: Pubkey,
+        action_code: u16,
+    ) -> Result<()> {
+        invariant!(
+            ActionType::try_from(action_code).is_err(),
+            ActionCodeReserved
+        );
+        let config = &mut ctx.accounts.config;
+        invariant!(
+            config.adapter_program(action_code).is_none(),
+            ActionCodeAlreadyRegistered
+        );
+        invariant!(config.adapters.len() < MAX_ADAPTERS, TooManyAdapters);
+        config.adapters.push(Adapter {
+            program,
+            action_code,
+        });
+
+        emit!(AdapterRegisteredEvent {
+            admin: config.admin,
+            program,
+            action_code,
+        });
+        Ok(())
+    }
+
+    /// Removes the adapter which owns the given action code.
+    pub fn remove_adapter(ctx: Context<UpdateConfig>, action_code: u16) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let index = unwrap_opt!(
+            config
+                .adapters
+                .iter()
+                .position(|a| a.action_code == action_code),
+            AdapterNotRegistered
+        );
+        let adapter = config.adapters.swap_remove(index);
+
+        emit!(AdapterRemovedEvent {
+            admin: config.admin,
+            program: adapter.program,
+            action_code,
+        });
+        Ok(())
+    }
+
+    /// Wraps lamports of the owner into their wSOL account, so that it may be
+    /// used as the input of a route.
+    pub fn wrap_sol(ctx: Context<WrapSOL>, amount: u64) -> Result<()> {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: ctx.accounts.wsol.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        anchor_lang::solana_program::program::invoke(
+            &spl_token::instruction::sync_native(&token::ID, &ctx.accounts.wsol.key())?,
+            &[ctx.accounts.wsol.to_account_info()],
+        )?;
+        Ok(())
+    }
+
+    /// Closes a wSOL account of the owner, unwrapping its lamports.
+    ///
+    /// This may be used to clean up the wSOL account of an aborted route.
+    pub fn unwrap_sol(ctx: Context<UnwrapSOL>) -> Result<()> {
+        token::close_account(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            token::CloseAccount {
+                account: ctx.accounts.wsol.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        ))
+    }
+
     /// Creates an ATA if it does not yet exist.
     pub fn create_ata_if_not_exists(ctx: Context<CreateATAIfNotExists>) -> Result<()> {
         if !ctx.accounts.ata.try_borrow_data()?.is_empty() {
@@ -63,88 +236,419 @@ pub mod continuation_router {
     }
 
     /// Begins a swap transaction.
+    ///
+    /// If `valid_until` is provided, the route must be completed by that unix timestamp.
     pub fn begin(
         ctx: Context<Begin>,
         amount_in: u64,
         minimum_amount_out: u64,
         num_steps: u16,
+        valid_until: Option<i64>,
     ) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        let input = processor::load_owned_token_account(&ctx.accounts.input, owner, "input")?;
+        let output = processor::load_owned_token_account(&ctx.accounts.output, owner, "output")?;
+
         let continuation = &mut ctx.accounts.continuation;
         continuation.owner = *ctx.accounts.owner.key;
         continuation.payer = *ctx.accounts.payer.key;
 
         continuation.input = *ctx.accounts.input.to_account_info().key;
-        continuation.initial_amount_in = TokenAmount::new(ctx.accounts.input.mint, amount_in);
+        continuation.initial_amount_in = TokenAmount::new(input.mint, amount_in);
         continuation.output = *ctx.accounts.output.to_account_info().key;
-        continuation.output_initial_balance = ctx.accounts.output.amount;
+        continuation.output_initial_balance = output.amount;
 
-        continuation.amount_in = TokenAmount::new(ctx.accounts.input.mint, amount_in);
-        continuation.minimum_amount_out =
-            TokenAmount::new(ctx.accounts.output.mint, minimum_amount_out);
+        continuation.amount_in = TokenAmount::new(input.mint, amount_in);
+        continuation.minimum_amount_out = TokenAmount::new(output.mint, minimum_amount_out);
         continuation.steps_left = num_steps;
+        continuation.num_steps = num_steps;
+        continuation.valid_until = valid_until;
+        continuation.created_at = Clock::get()?.unix_timestamp;
         continuation.__nonce = *unwrap_int!(ctx.bumps.get("continuation"));
 
         Ok(())
     }
 
     /// Begins a swap transaction.
-    /// More optimized.
+    /// More optimized: the continuation is a zero-copy [ContinuationV2], which must be
+    /// allocated with at least [ContinuationV2::LEN] bytes after the discriminator.
     pub fn begin_v2(
         ctx: Context<BeginV2>,
         amount_in: u64,
         minimum_amount_out: u64,
         num_steps: u16,
+        valid_until: Option<i64>,
     ) -> Result<()> {
-        let continuation = &mut ctx.accounts.continuation;
+        let (input, output) = ctx.accounts.load_token_accounts()?;
+
+        let continuation = &mut ctx.accounts.continuation.load_init()?;
+        continuation.owner = ctx.accounts.owner.key();
+        continuation.payer = ctx.accounts.owner.key();
+
+        continuation.input = ctx.accounts.input.key();
+        continuation.initial_amount_in = TokenAmount::new(input.mint, amount_in);
+        continuation.output = ctx.accounts.output.key();
+        continuation.output_initial_balance = output.amount;
+
+        continuation.amount_in = TokenAmount::new(input.mint, amount_in);
+        continuation.minimum_amount_out = TokenAmount::new(output.mint, minimum_amount_out);
+        continuation.steps_left = num_steps;
+        continuation.num_steps = num_steps;
+        continuation.set_valid_until(valid_until);
+        continuation.created_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Begins an exact-output swap transaction.
+    ///
+    /// The route swaps `amount_in`, as quoted by the client to produce at least `amount_out`
+    /// of the output token. It must be ended with [end_exact_out], which leaves exactly
+    /// `amount_out` in the output account and checks that the input account was debited
+    /// by at most `maximum_amount_in`.
+    /// If `valid_until` is provided, the route must be completed by that unix timestamp.
+    pub fn begin_exact_out(
+        ctx: Context<BeginV2>,
+        amount_in: u64,
+        maximum_amount_in: u64,
+        amount_out: u64,
+        num_steps: u16,
+        valid_until: Option<i64>,
+    ) -> Result<()> {
+        require!(amount_in <= maximum_amount_in, MaximumAmountInExceeded);
+        let (input, output) = ctx.accounts.load_token_accounts()?;
+
+        let continuation = &mut ctx.accounts.continuation.load_init()?;
         continuation.owner = ctx.accounts.owner.key();
         continuation.payer = ctx.accounts.owner.key();
 
         continuation.input = ctx.accounts.input.key();
-        continuation.initial_amount_in = TokenAmount::new(ctx.accounts.input.mint, amount_in);
+        continuation.initial_amount_in = TokenAmount::new(input.mint, amount_in);
         continuation.output = ctx.accounts.output.key();
-        continuation.output_initial_balance = ctx.accounts.output.amount;
+        continuation.output_initial_balance = output.amount;
 
-        continuation.amount_in = TokenAmount::new(ctx.accounts.input.mint, amount_in);
-        continuation.minimum_amount_out =
-            TokenAmount::new(ctx.accounts.output.mint, minimum_amount_out);
+        continuation.amount_in = TokenAmount::new(input.mint, amount_in);
+        continuation.minimum_amount_out = TokenAmount::new(output.mint, amount_out);
         continuation.steps_left = num_steps;
+        continuation.num_steps = num_steps;
+        continuation.set_valid_until(valid_until);
+        continuation.created_at = Clock::get()?.unix_timestamp;
+
+        continuation.mode = RouteMode::ExactOut.into();
+        continuation.maximum_amount_in = TokenAmount::new(input.mint, maximum_amount_in);
+        continuation.initial_input = ctx.accounts.input.key();
+        continuation.input_initial_balance = input.amount;
         Ok(())
     }
 
+    /// Begins a route from an input account on which the signer is an approved delegate.
+    ///
+    /// The delegate signs every later instruction of the route in place of the owner,
+    /// and pays for the continuation. The delegated amount must cover the amount in of
+    /// each step, and every output account must be owned by the owner of the input.
+    pub fn begin_delegated(
+        ctx: Context<BeginDelegated>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        num_steps: u16,
+        valid_until: Option<i64>,
+    ) -> Result<()> {
+        let (input, output) = ctx.accounts.load_token_accounts()?;
+        invariant!(
+            input.delegated_amount >= amount_in,
+            InsufficientDelegatedAmount
+        );
+
+        let continuation = &mut ctx.accounts.continuation.load_init()?;
+        continuation.owner = input.owner;
+        continuation.payer = ctx.accounts.delegate.key();
+        continuation.delegate = ctx.accounts.delegate.key();
+
+        continuation.input = ctx.accounts.input.key();
+        continuation.initial_amount_in = TokenAmount::new(input.mint, amount_in);
+        continuation.output = ctx.accounts.output.key();
+        continuation.output_initial_balance = output.amount;
+
+        continuation.amount_in = TokenAmount::new(input.mint, amount_in);
+        continuation.minimum_amount_out = TokenAmount::new(output.mint, minimum_amount_out);
+        continuation.steps_left = num_steps;
+        continuation.num_steps = num_steps;
+        continuation.set_valid_until(valid_until);
+        continuation.created_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Splits the current amount of the route into multiple parallel legs.
+    ///
+    /// Each leg starts at the current input account and is routed independently
+    /// until a [merge] step joins them back together.
+    pub fn split(ctx: Context<Split>, amounts: Vec<u64>) -> Result<()> {
+        state::load_mut(&ctx.accounts.continuation, |continuation| {
+            state::assert_has_one(continuation.authority(), ctx.accounts.owner.key())?;
+            invariant!(continuation.steps_left > 0, NoMoreSteps);
+            invariant!(continuation.legs().is_empty(), AlreadySplit);
+            invariant!(
+                amounts.len() >= 2 && amounts.len() <= MAX_LEGS,
+                InvalidLegCount
+            );
+            invariant!(amounts.iter().all(|amount| *amount != 0), ZeroSwap);
+
+            let total = amounts
+                .iter()
+                .try_fold(0_u64, |acc, amount| acc.checked_add(*amount));
+            invariant!(
+                total == Some(continuation.amount_in.amount),
+                SplitAmountMismatch
+            );
+
+            let mint = continuation.amount_in.mint;
+            let input = continuation.input;
+            continuation.amount_in = TokenAmount::new(mint, amounts[0]);
+            let legs: Vec<Leg> = amounts[1..]
+                .iter()
+                .map(|amount| Leg {
+                    input,
+                    amount_in: TokenAmount::new(mint, *amount),
+                })
+                .collect();
+            continuation.set_legs(&legs)?;
+            continuation.steps_left -= 1;
+
+            emit!(SplitEvent {
+                owner: continuation.owner,
+                input,
+                amounts: amounts
+                    .iter()
+                    .map(|amount| TokenAmount::new(mint, *amount))
+                    .collect(),
+            });
+            Ok(())
+        })
+    }
+
+    /// Merges all legs of a [split] route back into a single path.
+    ///
+    /// Every leg must have ended at the current input account.
+    pub fn merge(ctx: Context<Merge>) -> Result<()> {
+        state::load_mut(&ctx.accounts.continuation, |continuation| {
+            state::assert_has_one(continuation.authority(), ctx.accounts.owner.key())?;
+            invariant!(continuation.steps_left > 0, NoMoreSteps);
+            invariant!(!continuation.legs().is_empty(), NotSplit);
+
+            let mut amount_in = continuation.amount_in;
+            for leg in continuation.legs().iter() {
+                assert_keys_eq!(leg.input, continuation.input, PathInputOutputMismatch);
+                assert_keys_eq!(leg.amount_in.mint, amount_in.mint, InputMintMismatch);
+                amount_in.amount = unwrap_int!(amount_in.amount.checked_add(leg.amount_in.amount));
+            }
+
+            continuation.amount_in = amount_in;
+            continuation.set_legs(&[])?;
+            continuation.steps_left -= 1;
+
+            emit!(MergeEvent {
+                owner: continuation.owner,
+                output_account: continuation.input,
+                amount: amount_in,
+            });
+            Ok(())
+        })
+    }
+
+    /// Sets the minimum amount of tokens each step of the route must output.
+    ///
+    /// The minimum at index `i` applies to the `i`th step of the route.
+    /// Must be called before any step is processed.
+    pub fn set_step_minimums(ctx: Context<SetStepMinimums>, step_minimums: Vec<u64>) -> Result<()> {
+        state::load_mut(&ctx.accounts.continuation, |continuation| {
+            state::assert_has_one(continuation.authority(), ctx.accounts.owner.key())?;
+            invariant!(
+                continuation.steps_left == continuation.num_steps,
+                StepsAlreadyProcessed
+            );
+            invariant!(
+                step_minimums.len() <= continuation.num_steps as usize,
+                TooManyStepMinimums
+            );
+            continuation.set_step_minimums(&step_minimums)
+        })
+    }
+
     /// Cleans up the transaction and checks several invariants.
     pub fn end(ctx: Context<End>) -> Result<()> {
-        let continuation = &ctx.accounts.continuation;
-        require!(continuation.steps_left == 0, EndIncomplete);
+        let accounts = &ctx.accounts;
+        processor::end_route(
+            &accounts.continuation,
+            &accounts.output,
+            accounts.owner.key(),
+            &accounts.payer,
+        )
+    }
 
-        let result_balance = ctx.accounts.output.amount;
-        require!(
-            result_balance >= continuation.output_initial_balance,
-            BalanceLower
-        );
-        require!(
-            ctx.accounts.output.mint == continuation.minimum_amount_out.mint,
-            OutputMintMismatch,
+    /// Ends a route begun by [begin_exact_out].
+    ///
+    /// Output above the requested amount out is moved to the refund account, so the output
+    /// account receives exactly the amount out. The tokens spent by the route are measured
+    /// from the balance of the input account, and must not exceed the maximum amount in.
+    ///
+    /// Refunds in a Token-2022 mint with a transfer fee require the mint as the first
+    /// remaining account, see [processor::transfer].
+    pub fn end_exact_out<'info>(ctx: Context<'_, '_, '_, 'info, EndExactOut<'info>>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let output = token_interface::TokenAccount::load(&accounts.output)?;
+        let refund = token_interface::TokenAccount::load(&accounts.refund)?;
+        invariant!(refund.mint == output.mint, OutputMintMismatch);
+        invariant!(
+            accounts.refund.key() != accounts.output.key(),
+            InvalidRefundAccount
         );
+        let surplus = state::load(&accounts.continuation, |continuation| {
+            state::assert_has_one(continuation.authority(), accounts.owner.key())?;
+            state::assert_has_one(continuation.payer, accounts.payer.key())?;
+            state::assert_has_one(continuation.output, accounts.output.key())?;
+            assert_keys_eq!(refund.owner, continuation.owner, OutputOwnerMismatch);
+            continuation.assert_not_expired()?;
+            processor::complete_exact_out_route(continuation, &accounts.input, &output)
+        })?;
+        if surplus > 0 {
+            processor::transfer(
+                &accounts.token_program,
+                &accounts.output,
+                &accounts.refund,
+                &accounts.owner,
+                output.mint,
+                ctx.remaining_accounts,
+                surplus,
+            )?;
+        }
+        state::close(&accounts.continuation, &accounts.payer)
+    }
+
+    /// Ends a route whose output is wSOL, then closes the output account
+    /// to unwrap its lamports to the owner.
+    pub fn end_unwrap_sol(ctx: Context<EndUnwrapSOL>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        processor::end_route(
+            &accounts.continuation,
+            &accounts.output.to_account_info(),
+            accounts.owner.key(),
+            &accounts.payer,
+        )?;
+        token::close_account(CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::CloseAccount {
+                account: accounts.output.to_account_info(),
+                destination: accounts.owner.to_account_info(),
+                authority: accounts.owner.to_account_info(),
+            },
+        ))
+    }
 
-        let mut amount_out = result_balance - continuation.output_initial_balance;
-        // if input token = output token, add the initial amount in to the difference
-        if continuation.initial_amount_in.mint == ctx.accounts.output.mint {
-            amount_out += continuation.initial_amount_in.amount;
+    /// Executes all steps of a route in a single instruction, without a continuation account.
+    ///
+    /// The accounts of each step are the remaining accounts in the range
+    /// `accounts_start..accounts_end`: the swap program of the step followed by
+    /// the accounts of its action. Actions which take arguments cannot be routed.
+    /// The owner of the input and output must sign, so delegates must route with
+    /// [begin_delegated] instead.
+    pub fn route<'info>(
+        ctx: Context<'_, '_, '_, 'info, Route<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        steps: Vec<RouteStep>,
+    ) -> Result<()> {
+        let num_steps = unwrap_opt!(u16::try_from(steps.len()).ok(), InvalidRouteStep);
+        let owner = ctx.accounts.owner.key();
+        let input = processor::load_owned_token_account(&ctx.accounts.input, owner, "input")?;
+        let output = processor::load_owned_token_account(&ctx.accounts.output, owner, "output")?;
+        let mut continuation = ContinuationV2 {
+            owner,
+            payer: owner,
+            initial_amount_in: TokenAmount::new(input.mint, amount_in),
+            input: ctx.accounts.input.key(),
+            amount_in: TokenAmount::new(input.mint, amount_in),
+            steps_left: num_steps,
+            num_steps,
+            output: ctx.accounts.output.key(),
+            output_initial_balance: output.amount,
+            minimum_amount_out: TokenAmount::new(output.mint, minimum_amount_out),
+            ..ContinuationV2::default()
+        };
+        for step in steps.iter() {
+            route::process_step(&ctx, step, &mut continuation)?;
         }
 
-        require!(
-            amount_out >= continuation.minimum_amount_out.amount,
-            MinimumOutNotMet,
-        );
+        let output = token_interface::TokenAccount::load(&ctx.accounts.output)?;
+        processor::complete_route(&continuation, &output)
+    }
 
-        emit!(SwapCompleteEvent {
-            owner: continuation.owner,
-            amount_in: continuation.initial_amount_in,
-            amount_out: TokenAmount::new(continuation.minimum_amount_out.mint, amount_out),
-        });
+    /// Computes the expected amounts of each step of a route without moving any tokens.
+    ///
+    /// The remaining accounts are those of [continuation_router::route]. Each step
+    /// is quoted from the current state of its pool, with the output of a step being
+    /// the input of the next. The return data is set to the Borsh-serialized
+    /// `Vec<RouteResult>` of the steps, so clients can quote a route by simulating
+    /// this instruction. At most [simulate::MAX_SIMULATED_STEPS] may be simulated.
+    pub fn simulate_route<'info>(
+        ctx: Context<'_, '_, '_, 'info, SimulateRoute<'info>>,
+        amount_in: u64,
+        steps: Vec<RouteStep>,
+    ) -> Result<()> {
+        invariant!(
+            steps.len() <= simulate::MAX_SIMULATED_STEPS,
+            InvalidRouteStep
+        );
+        let mut results: Vec<RouteResult> = Vec::with_capacity(steps.len());
+        let mut amount = amount_in;
+        for step in steps.iter() {
+            let result = simulate::simulate_step(&ctx, step, amount)?;
+            if let Some(previous) = results.last() {
+                assert_keys_eq!(
+                    result.amount_in.mint,
+                    previous.amount_out.mint,
+                    InputMintMismatch
+                );
+            }
+            amount = result.amount_out.amount;
+            results.push(result);
+        }
+        anchor_lang::solana_program::program::set_return_data(&results.try_to_vec()?);
         Ok(())
     }
 
+    /// Closes a continuation before the route is complete, returning its lamports to the payer.
+    ///
+    /// The owner and the delegate of a route may abort it at any time. The payer may only abort a route
+    /// once it has expired or [PAYER_ABORT_DELAY_SECONDS] after it was created.
+    pub fn abort(ctx: Context<Abort>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        state::load(&ctx.accounts.continuation, |continuation| {
+            state::assert_has_one(continuation.payer, ctx.accounts.payer.key())?;
+            if authority != continuation.owner && authority != continuation.authority() {
+                assert_keys_eq!(authority, continuation.payer, Unauthorized);
+                let now = Clock::get()?.unix_timestamp;
+                let abortable_at = unwrap_int!(continuation
+                    .created_at
+                    .checked_add(PAYER_ABORT_DELAY_SECONDS));
+                let expired = continuation
+                    .valid_until()
+                    .map(|valid_until| now > valid_until)
+                    .unwrap_or(false);
+                invariant!(expired || now >= abortable_at, AbortTooEarly);
+            }
+
+            emit!(AbortEvent {
+                owner: continuation.owner,
+                authority,
+                num_steps: continuation.num_steps,
+                steps_left: continuation.steps_left,
+                input: continuation.input,
+                amount_in: continuation.amount_in,
+            });
+            Ok(())
+        })?;
+        state::close(&ctx.accounts.continuation, &ctx.accounts.payer)
+    }
+
     pub fn ss_swap<'info>(ctx: Context<'_, '_, '_, 'info, SSSwapAccounts<'info>>) -> Result<()> {
         process_action!(ctx)
     }
@@ -155,6 +659,12 @@ pub mod continuation_router {
         process_action!(ctx)
     }
 
+    pub fn ss_withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, SSWithdrawAccounts<'info>>,
+    ) -> Result<()> {
+        process_action!(ctx)
+    }
+
     pub fn ss_deposit_a<'info>(
         ctx: Context<'_, '_, '_, 'info, SSDepositAAccounts<'info>>,
     ) -> Result<()> {
@@ -167,6 +677,12 @@ pub mod continuation_router {
         process_action!(ctx)
     }
 
+    pub fn ss_deposit_balanced<'info>(
+        ctx: Context<'_, '_, '_, 'info, SSDepositBalancedAccounts<'info>>,
+    ) -> Result<()> {
+        process_action!(ctx)
+    }
+
     pub fn ad_withdraw<'info>(
         ctx: Context<'_, '_, '_, 'info, ADWithdrawAccounts<'info>>,
     ) -> Result<()> {
@@ -179,31 +695,67 @@ pub mod continuation_router {
         process_action!(ctx)
     }
 
+    /// Routes through a registered adapter program using an action code that it owns.
+    ///
+    /// The swap program must be the adapter registered for `action_code`, and
+    /// need not be an approved program.
+    pub fn external_action<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExternalActionAccounts<'info>>,
+        action_code: u16,
+    ) -> Result<()> {
+        let continuation = &ctx.accounts.continuation;
+        invariant!(
+            continuation.config.adapter_program(action_code)
+                == Some(continuation.swap_program.key()),
+            AdapterNotRegistered
+        );
+        process_action!(@checked ctx, ActionArgs::External { action_code })
+    }
+
+    /// Takes a fee of `fee_bps` basis points of the current amount, up to `max_fee` tokens,
+    /// and sends it to the referrer.
+    pub fn take_fee<'info>(
+        ctx: Context<'_, '_, '_, 'info, TakeFeeAccounts<'info>>,
+        fee_bps: u16,
+        max_fee: u64,
+    ) -> Result<()> {
+        invariant!(fee_bps <= MAX_FEE_BPS, FeeTooHigh);
+        process_action!(ctx, ActionArgs::TakeFee { fee_bps, max_fee })
+    }
+
     pub fn __dummy_swap_token<'info>(
-        ctx: Context<'_, '_, '_, 'info, SwapToken<'info>>,
+        _ctx: Context<'_, '_, '_, 'info, SwapToken<'info>>,
     ) -> Result<()> {
         Ok(())
     }
 
     pub fn __dummy_swap_output<'info>(
-        ctx: Context<'_, '_, '_, 'info, SwapOutput<'info>>,
+        _ctx: Context<'_, '_, '_, 'info, SwapOutput<'info>>,
     ) -> Result<()> {
         Ok(())
     }
 
-    pub fn __dummy_ss_swap<'info>(
-        ctx: Context<'_, '_, '_, 'info, SSSwap<'info>>,
-    ) -> Result<()> {
+    pub fn __dummy_ss_swap<'info>(_ctx: Context<'_, '_, '_, 'info, SSSwap<'info>>) -> Result<()> {
         Ok(())
     }
 
     pub fn __dummy_stable_swap<'info>(
-        ctx: Context<'_, '_, '_, 'info, StableSwap<'info>>,
+        _ctx: Context<'_, '_, '_, 'info, StableSwap<'info>>,
     ) -> Result<()> {
         Ok(())
     }
 
+    pub fn __dummy_continuation_accounts<'info>(
+        _ctx: Context<'_, '_, '_, 'info, ContinuationAccounts<'info>>,
+    ) -> Result<()> {
+        Ok(())
+    }
 
+    pub fn __dummy_ss_deposit<'info>(
+        _ctx: Context<'_, '_, '_, 'info, SSDeposit<'info>>,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 // --------------------------------
@@ -241,6 +793,26 @@ pub struct SSWithdrawOne<'info> {
     pub output: SwapOutput<'info>,
 }
 
+/// Withdraws both tokens of a StableSwap pool.
+/// The `output` continues the route, while the `other_output` receives the other token.
+#[router_action]
+#[derive(Accounts)]
+pub struct SSWithdraw<'info> {
+    /// Swap and authority
+    pub swap: StableSwap<'info>,
+    /// The pool mint of the swap.
+    /// CHECK: Checked by [stable_swap_anchor] program.
+    #[account(mut)]
+    pub pool_mint: AccountInfo<'info>,
+    /// The input account for LP tokens.
+    #[account(mut)]
+    pub input_lp: Account<'info, TokenAccount>,
+    /// The output of this component of the route.
+    pub output: SwapOutput<'info>,
+    /// The output of the token which does not continue the route.
+    pub other_output: SwapOutput<'info>,
+}
+
 #[router_action]
 #[derive(Accounts)]
 pub struct SSDepositA<'info> {
@@ -253,24 +825,117 @@ pub struct SSDepositB<'info> {
     pub inner: SSDeposit<'info>,
 }
 
+/// Deposits both tokens into a StableSwap pool in proportion to the pool's reserves.
+/// The amount of `other_input` deposited is computed from the route's amount of `input`.
+#[router_action]
+#[derive(Accounts)]
+pub struct SSDepositBalanced<'info> {
+    /// Swap and authority
+    pub swap: StableSwap<'info>,
+    /// The input of this component of the route.
+    pub input: SwapToken<'info>,
+    /// The input of the other token, which does not come from the route.
+    pub other_input: SwapToken<'info>,
+    /// The pool mint of the swap.
+    /// CHECK: Checked by [stable_swap_anchor] program.
+    #[account(mut)]
+    pub pool_mint: AccountInfo<'info>,
+    /// The destination account for LP tokens.
+    #[account(mut)]
+    pub output_lp: Account<'info, TokenAccount>,
+}
+
 #[router_action(pass_through)]
 #[derive(Accounts)]
 pub struct ADWithdraw<'info> {
-    pub input: Account<'info, TokenAccount>,
-    pub output: Account<'info, TokenAccount>,
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    pub input: UncheckedAccount<'info>,
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    pub output: UncheckedAccount<'info>,
 }
 
 #[router_action(pass_through)]
 #[derive(Accounts)]
 pub struct ADDeposit<'info> {
-    pub input: Account<'info, TokenAccount>,
-    pub output: Account<'info, TokenAccount>,
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    pub input: UncheckedAccount<'info>,
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    pub output: UncheckedAccount<'info>,
+}
+
+/// An action processed by a registered adapter program.
+/// The remaining accounts are passed to the adapter.
+#[router_action(pass_through)]
+#[derive(Accounts)]
+pub struct ExternalAction<'info> {
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    pub input: UncheckedAccount<'info>,
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    pub output: UncheckedAccount<'info>,
+}
+
+#[router_action]
+#[derive(Accounts)]
+pub struct TakeFee<'info> {
+    /// The token account the fee is taken from. This account continues the route.
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    #[account(mut)]
+    pub input: UncheckedAccount<'info>,
+    /// The token account which receives the fee.
+    /// CHECK: Loaded as a token account of either token program by the processor.
+    #[account(mut)]
+    pub referrer: UncheckedAccount<'info>,
 }
 
 // --------------------------------
 // Instructions
 // --------------------------------
 
+/// Creates the [RouterConfig].
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    /// The [RouterConfig].
+    #[account(
+        init,
+        seeds = [b"RouterConfig".as_ref()],
+        bump,
+        space = 8 + RouterConfig::LEN,
+        payer = payer
+    )]
+    pub config: Box<Account<'info, RouterConfig>>,
+
+    /// Admin of the [RouterConfig], which must be the upgrade authority of the router.
+    pub admin: Signer<'info>,
+
+    /// The program data account of the router.
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ ErrorCode::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// Payer of the [RouterConfig] account.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// System program.
+    pub system_program: Program<'info, System>,
+}
+
+/// Updates the [RouterConfig].
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// The [RouterConfig].
+    #[account(mut, has_one = admin)]
+    pub config: Box<Account<'info, RouterConfig>>,
+
+    /// Admin of the [RouterConfig].
+    pub admin: Signer<'info>,
+}
+
 /// Token accounts for the destination of a [StableSwap] instruction.
 #[derive(Accounts)]
 pub struct CreateATAIfNotExists<'info> {
@@ -325,12 +990,14 @@ pub struct Begin<'info> {
     pub random: UncheckedAccount<'info>,
 
     /// Input token account.
-    #[account(has_one = owner)]
-    pub input: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Loaded as a token account of either token program by [begin],
+    /// which checks `owner`.
+    pub input: UncheckedAccount<'info>,
 
     /// Output token account.
-    #[account(has_one = owner)]
-    pub output: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Loaded as a token account of either token program by [begin],
+    /// which checks `owner`.
+    pub output: UncheckedAccount<'info>,
 
     /// Owner of all token accounts in the chain.
     pub owner: Signer<'info>,
@@ -352,104 +1019,380 @@ pub struct Begin<'info> {
 pub struct BeginV2<'info> {
     /// Continuation state.
     #[account(zero)]
-    pub continuation: Box<Account<'info, Continuation>>,
+    pub continuation: AccountLoader<'info, ContinuationV2>,
 
     /// Input token account.
-    #[account(has_one = owner)]
-    pub input: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Loaded as a token account of either token program, then checked against `owner`.
+    pub input: UncheckedAccount<'info>,
 
     /// Output token account.
-    #[account(has_one = owner)]
-    pub output: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Loaded as a token account of either token program, then checked against `owner`.
+    pub output: UncheckedAccount<'info>,
 
     /// Owner of all token accounts in the chain.
     pub owner: Signer<'info>,
 }
 
-/// Ends a route.
+impl<'info> BeginV2<'info> {
+    /// Loads the input and output token accounts, which must be owned by `owner`.
+    fn load_token_accounts(
+        &self,
+    ) -> Result<(token_interface::TokenAccount, token_interface::TokenAccount)> {
+        let owner = self.owner.key();
+        Ok((
+            processor::load_owned_token_account(&self.input, owner, "input")?,
+            processor::load_owned_token_account(&self.output, owner, "output")?,
+        ))
+    }
+}
+
+/// Begins a route on behalf of the owner of the input account.
 #[derive(Accounts)]
-pub struct End<'info> {
+pub struct BeginDelegated<'info> {
     /// Continuation state.
-    #[account(
-        mut,
-        close = payer,
-        has_one = owner,
-        has_one = payer,
-        has_one = output,
-    )]
-    pub continuation: Box<Account<'info, Continuation>>,
+    #[account(zero)]
+    pub continuation: AccountLoader<'info, ContinuationV2>,
 
-    /// Output token account
-    pub output: Box<Account<'info, TokenAccount>>,
+    /// Input token account, which must be delegated to `delegate`.
+    /// CHECK: Loaded as a token account of either token program, then checked against `delegate`.
+    pub input: UncheckedAccount<'info>,
 
-    /// Owner of all accounts in the chain.
-    pub owner: Signer<'info>,
+    /// Output token account, which must be owned by the owner of the input.
+    /// CHECK: Loaded as a token account of either token program, then checked against `input`.
+    pub output: UncheckedAccount<'info>,
 
-    /// Funds the continuation in the beginning transaction and receives
-    /// the staked lamports of the continuation in the end transaction.
-    /// CHECK: Arbitrary.
-    #[account(mut)]
-    pub payer: UncheckedAccount<'info>,
+    /// Delegate of the input account, which signs the instructions of the route.
+    pub delegate: Signer<'info>,
 }
 
-#[derive(Accounts)]
-pub struct SSSwapAccounts<'info> {
-    pub continuation: ContinuationAccounts<'info>,
-    pub action: SSSwap<'info>,
+impl<'info> BeginDelegated<'info> {
+    /// Loads the input token account, which must be delegated to `delegate`,
+    /// and the output token account, which must be owned by the owner of the input.
+    fn load_token_accounts(
+        &self,
+    ) -> Result<(token_interface::TokenAccount, token_interface::TokenAccount)> {
+        let input = token_interface::TokenAccount::load(&self.input)?;
+        invariant!(
+            input.delegate == COption::Some(self.delegate.key()),
+            InputNotDelegated
+        );
+        let output = token_interface::TokenAccount::load(&self.output)?;
+        invariant!(output.owner == input.owner, OutputOwnerMismatch);
+        Ok((input, output))
+    }
 }
 
+/// Executes a route in a single instruction.
 #[derive(Accounts)]
-pub struct SSWithdrawOneAccounts<'info> {
-    pub continuation: ContinuationAccounts<'info>,
-    pub action: SSWithdrawOne<'info>,
-}
+pub struct Route<'info> {
+    /// Input token account.
+    /// CHECK: Loaded as a token account of either token program by
+    /// [continuation_router::route], which checks `owner`.
+    pub input: UncheckedAccount<'info>,
 
-#[derive(Accounts)]
-pub struct SSDepositAAccounts<'info> {
-    pub continuation: ContinuationAccounts<'info>,
-    pub action: SSDepositA<'info>,
-}
+    /// Output token account.
+    /// CHECK: Loaded as a token account of either token program by
+    /// [continuation_router::route], which checks `owner`.
+    pub output: UncheckedAccount<'info>,
 
-#[derive(Accounts)]
-pub struct SSDepositBAccounts<'info> {
-    pub continuation: ContinuationAccounts<'info>,
-    pub action: SSDepositB<'info>,
+    /// Owner of all token accounts in the chain.
+    pub owner: Signer<'info>,
+
+    /// The token program of the steps, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::InvalidTokenProgram)]
+    pub token_program: UncheckedAccount<'info>,
+
+    /// The registry of approved swap programs.
+    #[account(seeds = [b"RouterConfig".as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, RouterConfig>>,
 }
 
+/// Simulates a route.
 #[derive(Accounts)]
-pub struct ADWithdrawAccounts<'info> {
-    pub continuation: ContinuationAccounts<'info>,
-    pub action: ADWithdraw<'info>,
+pub struct SimulateRoute<'info> {
+    /// The registry of approved swap programs.
+    #[account(seeds = [b"RouterConfig".as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, RouterConfig>>,
 }
 
+/// Aborts a route.
 #[derive(Accounts)]
-pub struct ADDepositAccounts<'info> {
-    pub continuation: ContinuationAccounts<'info>,
-    pub action: ADDeposit<'info>,
-}
+pub struct Abort<'info> {
+    /// Continuation state, closed to the payer.
+    /// CHECK: Loaded as either layout by [state::load], which checks `payer`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
 
-// --------------------------------
-// Various accounts
-// --------------------------------
+    /// The owner, the delegate or the payer of the continuation.
+    pub authority: Signer<'info>,
 
-/// Context common to all router operations.
+    /// Funds the continuation in the beginning transaction and receives
+    /// the staked lamports of the continuation when it is aborted.
+    /// CHECK: Arbitrary.
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Ends a route.
 #[derive(Accounts)]
-pub struct ContinuationAccounts<'info> {
-    /// Continuation state
+pub struct End<'info> {
+    /// Continuation state, closed to the payer.
+    /// CHECK: Loaded as either layout by [processor::end_route], which checks
+    /// `owner`, `payer` and `output`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// Output token account
+    /// CHECK: Loaded as a token account of either token program by [processor::end_route].
+    pub output: UncheckedAccount<'info>,
+
+    /// Owner of all accounts in the chain, or the delegate of a delegated route.
+    pub owner: Signer<'info>,
+
+    /// Funds the continuation in the beginning transaction and receives
+    /// the staked lamports of the continuation in the end transaction.
+    /// CHECK: Arbitrary.
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Ends an exact-output route.
+#[derive(Accounts)]
+pub struct EndExactOut<'info> {
+    /// Continuation state, closed to the payer.
+    /// CHECK: Loaded as either layout by [state::load], which checks
+    /// `owner`, `payer`, `input` and `output`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// Input token account of the route, whose balance gives the amount spent.
+    /// CHECK: Loaded as a token account of either token program by
+    /// [processor::complete_exact_out_route], which checks it against the continuation.
+    pub input: UncheckedAccount<'info>,
+
+    /// Output token account, which keeps exactly the amount out.
+    /// CHECK: Loaded as a token account of either token program, then checked
+    /// against the continuation.
+    #[account(mut)]
+    pub output: UncheckedAccount<'info>,
+
+    /// Token account of the owner which receives the output above the amount out.
+    /// CHECK: Loaded as a token account of either token program, then checked
+    /// against `output` and the continuation.
+    #[account(mut)]
+    pub refund: UncheckedAccount<'info>,
+
+    /// Owner of all accounts in the chain, or the delegate of a delegated route.
+    pub owner: Signer<'info>,
+
+    /// Funds the continuation in the beginning transaction and receives
+    /// the staked lamports of the continuation in the end transaction.
+    /// CHECK: Arbitrary.
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    /// Token program of the output, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::InvalidTokenProgram)]
+    pub token_program: UncheckedAccount<'info>,
+}
+
+/// Ends a route whose output is wSOL.
+#[derive(Accounts)]
+pub struct EndUnwrapSOL<'info> {
+    /// Continuation state, closed to the payer.
+    /// CHECK: Loaded as either layout by [processor::end_route], which checks
+    /// `owner`, `payer` and `output`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// Output wSOL account. This is closed after the route ends.
+    /// Must be owned by the signer, so delegated routes cannot unwrap to their delegate.
     #[account(
         mut,
         has_one = owner,
+        constraint = output.mint == spl_token::native_mint::ID @ ErrorCode::NotNativeMint
     )]
-    pub continuation: Box<Account<'info, Continuation>>,
+    pub output: Box<Account<'info, TokenAccount>>,
 
-    /// The spl_token program.
+    /// Owner of all accounts in the chain. Receives the unwrapped lamports.
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Funds the continuation in the beginning transaction and receives
+    /// the staked lamports of the continuation in the end transaction.
+    /// CHECK: Arbitrary.
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    /// Token program.
     pub token_program: Program<'info, Token>,
+}
+
+/// Wraps SOL into a wSOL account.
+#[derive(Accounts)]
+pub struct WrapSOL<'info> {
+    /// Owner of the wSOL account, which pays the wrapped lamports.
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The wSOL account.
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = wsol.mint == spl_token::native_mint::ID @ ErrorCode::NotNativeMint
+    )]
+    pub wsol: Box<Account<'info, TokenAccount>>,
+
+    /// System program.
+    pub system_program: Program<'info, System>,
+
+    /// Token program.
+    pub token_program: Program<'info, Token>,
+}
+
+/// Unwraps SOL from a wSOL account.
+#[derive(Accounts)]
+pub struct UnwrapSOL<'info> {
+    /// Owner of the wSOL account, which receives the unwrapped lamports.
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The wSOL account. This is closed.
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = wsol.mint == spl_token::native_mint::ID @ ErrorCode::NotNativeMint
+    )]
+    pub wsol: Box<Account<'info, TokenAccount>>,
+
+    /// Token program.
+    pub token_program: Program<'info, Token>,
+}
+
+/// Sets the per-step minimums of a route.
+#[derive(Accounts)]
+pub struct SetStepMinimums<'info> {
+    /// Continuation state.
+    /// CHECK: Loaded as either layout by [state::load_mut], then checked against `owner`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// Owner of all accounts in the chain, or the delegate of a delegated route.
+    pub owner: Signer<'info>,
+}
+
+/// Splits a route into multiple legs.
+#[derive(Accounts)]
+pub struct Split<'info> {
+    /// Continuation state.
+    /// CHECK: Loaded as either layout by [state::load_mut], then checked against `owner`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// Owner of all accounts in the chain, or the delegate of a delegated route.
+    pub owner: Signer<'info>,
+}
+
+/// Merges the legs of a route.
+#[derive(Accounts)]
+pub struct Merge<'info> {
+    /// Continuation state.
+    /// CHECK: Loaded as either layout by [state::load_mut], then checked against `owner`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// Owner of all accounts in the chain, or the delegate of a delegated route.
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SSSwapAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: SSSwap<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SSWithdrawOneAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: SSWithdrawOne<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SSWithdrawAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: SSWithdraw<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SSDepositAAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: SSDepositA<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SSDepositBAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: SSDepositB<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SSDepositBalancedAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: SSDepositBalanced<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ADWithdrawAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: ADWithdraw<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ADDepositAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: ADDeposit<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExternalActionAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: ExternalAction<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TakeFeeAccounts<'info> {
+    pub continuation: ContinuationAccounts<'info>,
+    pub action: TakeFee<'info>,
+}
+
+// --------------------------------
+// Various accounts
+// --------------------------------
+
+/// Context common to all router operations.
+#[derive(Accounts)]
+pub struct ContinuationAccounts<'info> {
+    /// Continuation state
+    /// CHECK: Loaded as either layout by [state::load_mut], then checked against `owner`.
+    #[account(mut)]
+    pub continuation: UncheckedAccount<'info>,
+
+    /// The token program of the action, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::InvalidTokenProgram)]
+    pub token_program: UncheckedAccount<'info>,
 
     /// The relevant swap program.
-    /// CHECK: Arbitrary.
+    /// CHECK: Checked against the [RouterConfig] in the action instruction.
     pub swap_program: UncheckedAccount<'info>,
 
-    /// The owner of all involved token accounts.
+    /// The registry of approved swap programs.
+    #[account(seeds = [b"RouterConfig".as_ref()], bump = config.bump)]
+    pub config: Box<Account<'info, RouterConfig>>,
+
+    /// The owner of all involved token accounts, or the delegate of a delegated route.
     pub owner: Signer<'info>,
 }
 
@@ -507,7 +1450,71 @@ pub struct SwapOutput<'info> {
     pub fees: AccountInfo<'info>,
 }
 
-/// Continuation state of the owner.
+/// Registry of programs that the router may call into.
+#[account]
+#[derive(Default)]
+pub struct RouterConfig {
+    /// Bump seed.
+    pub bump: u8,
+
+    /// Account which may add and remove approved programs.
+    pub admin: Pubkey,
+
+    /// Programs which may be passed as the swap program of an action.
+    pub approved_programs: Vec<Pubkey>,
+
+    /// Adapter programs which may be routed through via [continuation_router::external_action].
+    pub adapters: Vec<Adapter>,
+}
+
+impl RouterConfig {
+    pub const LEN: usize = 1
+        + PUBKEY_BYTES
+        + 4
+        + PUBKEY_BYTES * MAX_APPROVED_PROGRAMS
+        + 4
+        + Adapter::LEN * MAX_ADAPTERS;
+
+    /// Returns true if the program may be passed as the swap program of a built-in action.
+    ///
+    /// The token programs are always approved. Adapters are only checked by
+    /// [continuation_router::external_action], against the action code they own.
+    pub fn is_approved(&self, program: &Pubkey) -> bool {
+        token_interface::is_token_program(program) || self.approved_programs.contains(program)
+    }
+
+    /// Returns the adapter program which owns the action code, if any.
+    pub fn adapter_program(&self, action_code: u16) -> Option<Pubkey> {
+        self.adapters
+            .iter()
+            .find(|a| a.action_code == action_code)
+            .map(|a| a.program)
+    }
+}
+
+/// Maximum number of programs in the [RouterConfig].
+pub const MAX_APPROVED_PROGRAMS: usize = 32;
+
+/// Maximum number of adapters in the [RouterConfig].
+pub const MAX_ADAPTERS: usize = 16;
+
+/// A program implementing [RouterActionProcessor] which owns an action code.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Adapter {
+    /// The adapter program.
+    pub program: Pubkey,
+    /// The action code passed to [RouterActionProcessor::process_action].
+    /// This may not be the code of an [ActionType].
+    pub action_code: u16,
+}
+
+impl Adapter {
+    pub const LEN: usize = PUBKEY_BYTES + 2;
+}
+
+/// Continuation state of the owner, in the Borsh layout created by [continuation_router::begin].
+///
+/// Instructions convert this to a [ContinuationV2] when loading it; see [state].
 #[account]
 #[derive(Default)]
 pub struct Continuation {
@@ -521,14 +1528,19 @@ pub struct Continuation {
     pub initial_amount_in: TokenAmount,
 
     /// The next input account.
+    /// If the route is split, this is the input account of the active leg.
     pub input: Pubkey,
 
     /// The next amount of tokens to input.
+    /// If the route is split, this is the amount of the active leg.
     pub amount_in: TokenAmount,
 
     /// The total number of steps that still need to be executed.
     pub steps_left: u16,
 
+    /// The total number of steps of the route.
+    pub num_steps: u16,
+
     /// The final output account.
     pub output: Pubkey,
 
@@ -536,8 +1548,30 @@ pub struct Continuation {
     pub output_initial_balance: u64,
 
     /// The minimum amount of tokens to output at the end of the transaction.
+    /// For [RouteMode::ExactOut] routes, this is the exact output requested.
     pub minimum_amount_out: TokenAmount,
 
+    /// Whether the route was started with an exact input or an exact output.
+    pub mode: RouteMode,
+
+    /// The maximum amount of tokens the route may spend.
+    /// Only enforced for [RouteMode::ExactOut] routes.
+    pub maximum_amount_in: TokenAmount,
+
+    /// The suspended legs of a split route, excluding the active leg.
+    /// Empty if the route is not split.
+    pub legs: Vec<Leg>,
+
+    /// The minimum amount of tokens each step must output, indexed by step.
+    /// Steps without an entry have no minimum.
+    pub step_minimums: Vec<u64>,
+
+    /// Unix timestamp after which the route may no longer be processed.
+    pub valid_until: Option<i64>,
+
+    /// Unix timestamp of when the route was started.
+    pub created_at: i64,
+
     /// Nonce field to the struct to hold the bump seed for the program derived address,
     /// sourced from `<https://github.com/project-serum/anchor/blob/ec6888a3b9f702bc41bd3266e7dd70116df3549c/lang/attribute/account/src/lib.rs#L220-L221.>`.
     __nonce: u8,
@@ -549,10 +1583,277 @@ impl Continuation {
         + PUBKEY_BYTES
         + TokenAmount::LEN
         + 2
+        + 2
+        + PUBKEY_BYTES
+        + 8
+        + TokenAmount::LEN
+        + 1
+        + TokenAmount::LEN
+        + 4
+        + Leg::LEN * (MAX_LEGS - 1)
+        + 4
+        + 8 * MAX_STEP_MINIMUMS
+        + 1
+        + 8
+        + 8
+        + 1;
+}
+
+/// Continuation state of the owner, in the zero-copy layout created by [continuation_router::begin_v2].
+///
+/// Unlike [Continuation], this is read and written in place by the instructions of the route
+/// instead of being deserialized and re-serialized by each of them.
+#[account(zero_copy)]
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ContinuationV2 {
+    /// The owner of the continuation.
+    pub owner: Pubkey,
+
+    /// The payer of the continuation.
+    pub payer: Pubkey,
+
+    /// The initial amount of tokens in.
+    pub initial_amount_in: TokenAmount,
+
+    /// The next input account.
+    /// If the route is split, this is the input account of the active leg.
+    pub input: Pubkey,
+
+    /// The next amount of tokens to input.
+    /// If the route is split, this is the amount of the active leg.
+    pub amount_in: TokenAmount,
+
+    /// The final output account.
+    pub output: Pubkey,
+
+    /// The initial balance of the output account.
+    pub output_initial_balance: u64,
+
+    /// The minimum amount of tokens to output at the end of the transaction.
+    /// For [RouteMode::ExactOut] routes, this is the exact output requested.
+    pub minimum_amount_out: TokenAmount,
+
+    /// The maximum amount of tokens the route may spend.
+    /// Only enforced for [RouteMode::ExactOut] routes.
+    pub maximum_amount_in: TokenAmount,
+
+    /// The input account of the route when it began.
+    /// Only set for [RouteMode::ExactOut] routes.
+    pub initial_input: Pubkey,
+
+    /// The balance of the initial input account when the route began.
+    /// Only set for [RouteMode::ExactOut] routes.
+    pub input_initial_balance: u64,
+
+    /// The delegate of the input account which drives the route in place of the owner.
+    /// Only set for routes started with [continuation_router::begin_delegated].
+    pub delegate: Pubkey,
+
+    /// The suspended legs of a split route, excluding the active leg.
+    /// Only the first `num_legs` are used. Holds `MAX_LEGS - 1` legs.
+    pub legs: [Leg; 2],
+
+    /// The minimum amount of tokens each step must output, indexed by step.
+    /// Only the first `num_step_minimums` are used. Holds `MAX_STEP_MINIMUMS` minimums.
+    pub step_minimums: [u64; 8],
+
+    /// Unix timestamp after which the route may no longer be processed.
+    /// Only set if `has_valid_until` is non-zero.
+    pub valid_until: i64,
+
+    /// Unix timestamp of when the route was started.
+    pub created_at: i64,
+
+    /// The total number of steps that still need to be executed.
+    pub steps_left: u16,
+
+    /// The total number of steps of the route.
+    pub num_steps: u16,
+
+    /// The number of suspended legs.
+    pub num_legs: u8,
+
+    /// The number of per-step minimums.
+    pub num_step_minimums: u8,
+
+    /// The [RouteMode] of the route.
+    pub mode: u8,
+
+    /// Whether the route has a `valid_until` timestamp.
+    pub has_valid_until: u8,
+}
+
+impl ContinuationV2 {
+    pub const LEN: usize = PUBKEY_BYTES * 2
+        + TokenAmount::LEN
+        + PUBKEY_BYTES
+        + TokenAmount::LEN
         + PUBKEY_BYTES
         + 8
         + TokenAmount::LEN
+        + TokenAmount::LEN
+        + PUBKEY_BYTES
+        + 8
+        + PUBKEY_BYTES
+        + Leg::LEN * (MAX_LEGS - 1)
+        + 8 * MAX_STEP_MINIMUMS
+        + 8
+        + 8
+        + 2
+        + 2
+        + 1
+        + 1
+        + 1
         + 1;
+
+    /// Unix timestamp after which the route may no longer be processed, if any.
+    pub fn valid_until(&self) -> Option<i64> {
+        if self.has_valid_until == 0 {
+            None
+        } else {
+            Some(self.valid_until)
+        }
+    }
+
+    pub fn set_valid_until(&mut self, valid_until: Option<i64>) {
+        self.has_valid_until = valid_until.is_some().into();
+        self.valid_until = valid_until.unwrap_or_default();
+    }
+
+    /// The delegate of the input account driving the route, if any.
+    pub fn delegate(&self) -> Option<Pubkey> {
+        if self.delegate == Pubkey::default() {
+            None
+        } else {
+            Some(self.delegate)
+        }
+    }
+
+    /// The signer of the instructions of the route: its delegate, or else its owner.
+    pub fn authority(&self) -> Pubkey {
+        self.delegate().unwrap_or(self.owner)
+    }
+
+    /// Whether the route was started with an exact input or an exact output.
+    pub fn mode(&self) -> RouteMode {
+        RouteMode::try_from(self.mode).unwrap_or_default()
+    }
+
+    /// The suspended legs of a split route, excluding the active leg.
+    pub fn legs(&self) -> &[Leg] {
+        &self.legs[..self.num_legs as usize]
+    }
+
+    pub fn set_legs(&mut self, legs: &[Leg]) -> Result<()> {
+        invariant!(legs.len() < MAX_LEGS, InvalidLegCount);
+        self.legs = Default::default();
+        self.legs[..legs.len()].copy_from_slice(legs);
+        self.num_legs = legs.len() as u8;
+        Ok(())
+    }
+
+    /// The minimum amount of tokens each step must output, indexed by step.
+    pub fn step_minimums(&self) -> &[u64] {
+        &self.step_minimums[..self.num_step_minimums as usize]
+    }
+
+    pub fn set_step_minimums(&mut self, step_minimums: &[u64]) -> Result<()> {
+        invariant!(
+            step_minimums.len() <= MAX_STEP_MINIMUMS,
+            TooManyStepMinimums
+        );
+        self.step_minimums = Default::default();
+        self.step_minimums[..step_minimums.len()].copy_from_slice(step_minimums);
+        self.num_step_minimums = step_minimums.len() as u8;
+        Ok(())
+    }
+
+    /// Ensures that the route has not passed its `valid_until` timestamp.
+    pub fn assert_not_expired(&self) -> Result<()> {
+        if let Some(valid_until) = self.valid_until() {
+            invariant!(Clock::get()?.unix_timestamp <= valid_until, RouteExpired);
+        }
+        Ok(())
+    }
+
+    /// Index of the step currently being processed.
+    pub fn current_step(&self) -> u16 {
+        self.num_steps.saturating_sub(self.steps_left)
+    }
+
+    /// Minimum amount of tokens the current step must output.
+    pub fn current_step_minimum(&self) -> u64 {
+        self.step_minimums()
+            .get(self.current_step() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Makes the leg starting at `input` the active leg, suspending the current one.
+    ///
+    /// Does nothing if the active leg already starts at `input` or if no leg does.
+    pub fn activate_leg(&mut self, input: Pubkey) {
+        if self.input == input {
+            return;
+        }
+        let num_legs = self.num_legs as usize;
+        if let Some(leg) = self.legs[..num_legs]
+            .iter_mut()
+            .find(|leg| leg.input == input)
+        {
+            std::mem::swap(&mut leg.input, &mut self.input);
+            std::mem::swap(&mut leg.amount_in, &mut self.amount_in);
+        }
+    }
+}
+
+/// Maximum fee that may be taken by a [TakeFee] action, in basis points.
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Number of seconds after which the payer of a route may abort it.
+pub const PAYER_ABORT_DELAY_SECONDS: i64 = 60 * 60;
+
+/// Maximum number of per-step minimums of a route.
+pub const MAX_STEP_MINIMUMS: usize = 8;
+
+/// Maximum number of parallel legs of a split route.
+pub const MAX_LEGS: usize = 3;
+
+/// A parallel path of a split route.
+#[zero_copy]
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Default, Eq, PartialEq)]
+pub struct Leg {
+    /// The next input account of the leg.
+    pub input: Pubkey,
+    /// The next amount of tokens to input into the leg.
+    pub amount_in: TokenAmount,
+}
+
+impl Leg {
+    pub const LEN: usize = PUBKEY_BYTES + TokenAmount::LEN;
+}
+
+/// How the amounts of a route are specified.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    IntoPrimitive,
+    TryFromPrimitive,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+)]
+#[repr(u8)]
+pub enum RouteMode {
+    /// The input amount is fixed and the output has a minimum.
+    #[default]
+    ExactIn,
+    /// The output amount is fixed and the input has a maximum.
+    /// Ended by [continuation_router::end_exact_out].
+    ExactOut,
 }
 
 /// --------------------------------
@@ -587,12 +1888,125 @@ pub enum ErrorCode {
     MinimumOutNotMet,
     #[msg("Output mint does not match continuation output mint.")]
     OutputMintMismatch,
+    #[msg("Amount in exceeds the maximum amount in.")]
+    MaximumAmountInExceeded,
+
+    #[msg("Route is already split.")]
+    AlreadySplit,
+    #[msg("Route is not split.")]
+    NotSplit,
+    #[msg("Invalid number of legs.")]
+    InvalidLegCount,
+    #[msg("Split amounts do not add up to the amount in.")]
+    SplitAmountMismatch,
+    #[msg("Not all legs were merged.")]
+    LegsNotMerged,
+
+    #[msg("Step output is below the step minimum.")]
+    StepMinimumOutNotMet,
+    #[msg("Too many step minimums.")]
+    TooManyStepMinimums,
+    #[msg("Steps have already been processed.")]
+    StepsAlreadyProcessed,
+
+    #[msg("Route has expired.")]
+    RouteExpired,
+
+    #[msg("Only the owner or payer may abort a route.")]
+    Unauthorized,
+    #[msg("Payer cannot abort the route yet.")]
+    AbortTooEarly,
+
+    #[msg("Fee exceeds the maximum fee.")]
+    FeeTooHigh,
+    #[msg("Referrer mint does not match the input mint.")]
+    ReferrerMintMismatch,
+    #[msg("Invalid arguments for the action.")]
+    InvalidActionArgs,
+
+    #[msg("Swap program is not approved.")]
+    SwapProgramNotApproved,
+    #[msg("Program is already approved.")]
+    ProgramAlreadyApproved,
+    #[msg("Program is not approved.")]
+    ProgramNotApproved,
+    #[msg("Too many approved programs.")]
+    TooManyApprovedPrograms,
+
+    #[msg("Action code is reserved by the router.")]
+    ActionCodeReserved,
+    #[msg("Action code is already registered.")]
+    ActionCodeAlreadyRegistered,
+    #[msg("No adapter is registered for the action code and program.")]
+    AdapterNotRegistered,
+    #[msg("Too many adapters.")]
+    TooManyAdapters,
+
+    #[msg("Account is not a wSOL account.")]
+    NotNativeMint,
+
+    #[msg("Router did not set return data.")]
+    MissingReturnData,
+
+    #[msg("Invalid route step.")]
+    InvalidRouteStep,
+
+    #[msg("Token program must be the SPL Token or the Token-2022 program.")]
+    InvalidTokenProgram,
+
+    #[msg("Step could not be simulated.")]
+    SimulationFailed,
+
+    #[msg("Input account is not delegated to the delegate of the route.")]
+    InputNotDelegated,
+    #[msg("Delegated amount of the input account is less than the amount in.")]
+    InsufficientDelegatedAmount,
+
+    #[msg("Route mode does not match the instruction.")]
+    RouteModeMismatch,
+    #[msg("Refund account must differ from the output account.")]
+    InvalidRefundAccount,
+
+    #[msg("Signer is not the upgrade authority of the router.")]
+    NotUpgradeAuthority,
 }
 
 // --------------------------------
 // Events
 // --------------------------------
 
+#[event]
+pub struct AdminSetEvent {
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct ApprovedProgramAddedEvent {
+    pub admin: Pubkey,
+    pub program: Pubkey,
+}
+
+#[event]
+pub struct ApprovedProgramRemovedEvent {
+    pub admin: Pubkey,
+    pub program: Pubkey,
+}
+
+#[event]
+pub struct AdapterRegisteredEvent {
+    pub admin: Pubkey,
+    pub program: Pubkey,
+    pub action_code: u16,
+}
+
+#[event]
+pub struct AdapterRemovedEvent {
+    pub admin: Pubkey,
+    pub program: Pubkey,
+    pub action_code: u16,
+}
+
 #[event]
 pub struct SwapActionEvent {
     pub action_type: ActionType,
@@ -602,6 +2016,13 @@ pub struct SwapActionEvent {
     pub output_amount: TokenAmount,
 }
 
+#[event]
+pub struct FeeTakenEvent {
+    pub owner: Pubkey,
+    pub referrer: Pubkey,
+    pub fee: TokenAmount,
+}
+
 #[event]
 pub struct SwapCompleteEvent {
     pub owner: Pubkey,
@@ -609,8 +2030,44 @@ pub struct SwapCompleteEvent {
     pub amount_out: TokenAmount,
 }
 
+#[event]
+pub struct AbortEvent {
+    pub owner: Pubkey,
+    pub authority: Pubkey,
+    pub num_steps: u16,
+    pub steps_left: u16,
+    pub input: Pubkey,
+    pub amount_in: TokenAmount,
+}
+
+#[event]
+pub struct SplitEvent {
+    pub owner: Pubkey,
+    pub input: Pubkey,
+    pub amounts: Vec<TokenAmount>,
+}
+
+#[event]
+pub struct MergeEvent {
+    pub owner: Pubkey,
+    pub output_account: Pubkey,
+    pub amount: TokenAmount,
+}
+
+#[event]
+pub struct SwapExactOutCompleteEvent {
+    pub owner: Pubkey,
+    /// The tokens spent from the input account.
+    pub amount_in: TokenAmount,
+    pub maximum_amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    /// The output above the amount out, moved to the refund account.
+    pub refunded: TokenAmount,
+}
+
 /// An amount of tokens.
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[zero_copy]
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Default, Eq, PartialEq)]
 pub struct TokenAmount {
     /// Mint of the token.
     pub mint: Pubkey,
@@ -618,6 +2075,17 @@ pub struct TokenAmount {
     pub amount: u64,
 }
 
+/// A step of a [continuation_router::route].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct RouteStep {
+    /// The action of the step.
+    pub action_type: ActionType,
+    /// Index of the first remaining account of the step.
+    pub accounts_start: u8,
+    /// Index after the last remaining account of the step.
+    pub accounts_end: u8,
+}
+
 impl TokenAmount {
     pub const LEN: usize = PUBKEY_BYTES + 8;
 
@@ -632,10 +2100,13 @@ pub trait Action {
 }
 
 /// Interface for programs that can be routed through.
+///
+/// The remaining accounts of the context share the lifetime of its accounts,
+/// so adapters may pass both to the same CPI.
 #[interface]
 pub trait RouterActionProcessor<'info, T: Accounts<'info>> {
     fn process_action(
-        ctx: Context<T>,
+        ctx: Context<'_, '_, '_, 'info, T>,
         action: u16,
         amount_in: u64,
         minimum_amount_out: u64,
@@ -652,7 +2123,49 @@ pub enum ActionType {
     SSWithdrawOne = 1,
     SSDepositA = 2,
     SSDepositB = 3,
+    SSWithdraw = 4,
+    SSDepositBalanced = 5,
 
     ADWithdraw = 10,
     ADDeposit = 11,
+
+    TakeFee = 20,
+
+    /// An action of a registered adapter. The adapter receives its own action code.
+    ExternalAction = 30,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_router_config_len() {
+        let config = RouterConfig {
+            approved_programs: vec![Pubkey::default(); MAX_APPROVED_PROGRAMS],
+            adapters: vec![Adapter::default(); MAX_ADAPTERS],
+            ..RouterConfig::default()
+        };
+        assert_eq!(config.try_to_vec().unwrap().len(), RouterConfig::LEN);
+    }
+
+    #[test]
+    fn test_continuation_len() {
+        let continuation = Continuation {
+            legs: vec![Leg::default(); MAX_LEGS - 1],
+            step_minimums: vec![0; MAX_STEP_MINIMUMS],
+            valid_until: Some(0),
+            ..Continuation::default()
+        };
+        assert_eq!(continuation.try_to_vec().unwrap().len(), Continuation::LEN);
+    }
+
+    #[test]
+    fn test_continuation_v2_len() {
+        let continuation = ContinuationV2::default();
+        assert_eq!(continuation.legs.len(), MAX_LEGS - 1);
+        assert_eq!(continuation.step_minimums.len(), MAX_STEP_MINIMUMS);
+        // the zero-copy layout has no padding
+        assert_eq!(std::mem::size_of::<ContinuationV2>(), ContinuationV2::LEN);
+    }
 }
@@ -1,12 +1,46 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount};
-use vipers::{assert_keys_eq, invariant};
+use anchor_lang::solana_program::program_option::COption;
+use saber_token_interface::{self as token_interface, TokenAccount};
+use vipers::{assert_keys_eq, invariant, unwrap_int};
 
-use crate::{Action, Continuation, SwapActionEvent, TokenAmount};
+use crate::{
+    state, Action, ActionType, ContinuationV2, RouteMode, RouteResult, SwapActionEvent,
+    SwapCompleteEvent, SwapExactOutCompleteEvent, TokenAmount,
+};
 
 pub trait ActionInputOutput<'info>: Action {
-    fn input_account(&self) -> &Account<'info, TokenAccount>;
-    fn output_account(&self) -> &Account<'info, TokenAccount>;
+    fn input_account(&self) -> AccountInfo<'info>;
+    fn output_account(&self) -> AccountInfo<'info>;
+}
+
+/// Instruction arguments of an action.
+#[derive(Clone, Copy, Debug)]
+pub enum ActionArgs {
+    /// The action has no arguments.
+    None,
+    /// Arguments of a [crate::TakeFee] action.
+    TakeFee {
+        /// Fee in basis points of the amount in.
+        fee_bps: u16,
+        /// Maximum amount of tokens to take as a fee.
+        max_fee: u64,
+    },
+    /// Arguments of a [crate::ExternalAction].
+    External {
+        /// The action code owned by the adapter.
+        action_code: u16,
+    },
+}
+
+impl ActionArgs {
+    /// The action code to pass to [crate::RouterActionProcessor::process_action],
+    /// if it differs from the [crate::ActionType] of the action.
+    pub fn action_code(&self) -> Option<u16> {
+        match self {
+            ActionArgs::External { action_code } => Some(*action_code),
+            _ => None,
+        }
+    }
 }
 
 pub struct ActionContext<'a, 'b, 'c, 'info, T> {
@@ -17,31 +51,39 @@ pub struct ActionContext<'a, 'b, 'c, 'info, T> {
     /// Remaining accounts given but not deserialized or validated.
     /// Be very careful when using this directly.
     pub remaining_accounts: &'c [AccountInfo<'info>],
-    /// The spl_token program.
-    pub token_program: Program<'info, Token>,
+    /// The token program of the action, either SPL Token or Token-2022.
+    pub token_program: UncheckedAccount<'info>,
     /// The relevant swap program.
     /// CHECK: Checked by executor
     pub swap_program: AccountInfo<'info>,
-    /// The owner of all involved token accounts.
+    /// The owner of all involved token accounts,
+    /// or the delegate of their input accounts if the route is delegated.
     /// CHECK: Arbitrary
     pub owner: AccountInfo<'info>,
+    /// The owner of all involved token accounts, even if the route is delegated.
+    pub route_owner: Pubkey,
+    /// Instruction arguments of the action.
+    pub args: ActionArgs,
 }
 
 /// Processes a context.
 pub trait Processor<'info>: ActionInputOutput<'info> {
     fn process_unchecked(&self, amount_in: u64, minimum_amount_out: u64) -> Result<()>;
 
-    fn process(&self, continuation: &mut Account<'info, Continuation>) -> Result<()> {
+    fn process(&self, continuation: &mut ContinuationV2) -> Result<()> {
         msg!("Router action: {:?}", Self::TYPE);
         let continuation = continuation;
         invariant!(continuation.steps_left > 0, NoMoreSteps);
+        continuation.assert_not_expired()?;
 
-        let input_account = self.input_account();
+        let input_info = self.input_account();
+        continuation.activate_leg(input_info.key());
         assert_keys_eq!(
-            input_account.key(),
+            input_info.key(),
             continuation.input,
             PathInputOutputMismatch
         );
+        let input_account = TokenAccount::load(&input_info)?;
         assert_keys_eq!(input_account.owner, continuation.owner, InputOwnerMismatch);
         assert_keys_eq!(
             input_account.mint,
@@ -59,8 +101,21 @@ pub trait Processor<'info>: ActionInputOutput<'info> {
             InsufficientInputBalance
         );
 
+        // ensure the delegate of a delegated route may spend the amount in
+        if let Some(delegate) = continuation.delegate() {
+            invariant!(
+                input_account.delegate == COption::Some(delegate),
+                InputNotDelegated
+            );
+            invariant!(
+                input_account.delegated_amount >= amount_in.amount,
+                InsufficientDelegatedAmount
+            );
+        }
+
         // ensure output account is owned by the owner
-        let output_account = self.output_account();
+        let output_info = self.output_account();
+        let output_account = TokenAccount::load(&output_info)?;
         assert_keys_eq!(
             output_account.owner,
             continuation.owner,
@@ -69,7 +124,7 @@ pub trait Processor<'info>: ActionInputOutput<'info> {
 
         // process step
         let initial_balance = output_account.amount;
-        let minimum_amount_out = if continuation.steps_left == 1 {
+        let minimum_amount_out = if continuation.steps_left == 1 && continuation.legs().is_empty() {
             assert_keys_eq!(
                 continuation.minimum_amount_out.mint,
                 output_account.mint,
@@ -80,19 +135,47 @@ pub trait Processor<'info>: ActionInputOutput<'info> {
             0
         };
         self.process_unchecked(amount_in.amount, minimum_amount_out)?;
-        let output_account = &mut output_account.clone();
-        output_account.reload()?;
+        // the balance delta is what the output received, net of any Token-2022 transfer fee
+        // withheld from it, so the next step never routes more than the route holds
+        let output_account = TokenAccount::load(&output_info)?;
         let result_balance = output_account.amount;
 
-        // ensure that the new balance is higher than the old balance
-        invariant!(result_balance >= initial_balance, BalanceLower);
-        let next_amount_in = result_balance - initial_balance;
+        let next_amount_in = if matches!(Self::TYPE, ActionType::TakeFee) {
+            // fees are taken in place, so the route carries over whatever is left of the amount in
+            unwrap_int!(amount_in
+                .amount
+                .checked_add(result_balance)
+                .and_then(|amount| amount.checked_sub(initial_balance)))
+        } else {
+            // ensure that the new balance is higher than the old balance
+            invariant!(result_balance >= initial_balance, BalanceLower);
+            result_balance - initial_balance
+        };
+
+        // ensure the step met its minimum
+        let step_minimum = continuation.current_step_minimum();
+        invariant!(
+            next_amount_in >= step_minimum,
+            StepMinimumOutNotMet,
+            &format!(
+                "Step {} output {} is below the step minimum {}",
+                continuation.current_step(),
+                next_amount_in,
+                step_minimum
+            )
+        );
 
         // write results
-        continuation.input = output_account.key();
+        continuation.input = output_info.key();
         continuation.amount_in = TokenAmount::new(output_account.mint, next_amount_in);
         continuation.steps_left -= 1;
 
+        RouteResult {
+            amount_in,
+            amount_out: continuation.amount_in,
+        }
+        .set_return_data()?;
+
         emit!(SwapActionEvent {
             action_type: Self::TYPE,
             owner: continuation.owner,
@@ -103,3 +186,170 @@ pub trait Processor<'info>: ActionInputOutput<'info> {
         Ok(())
     }
 }
+
+/// Checks the invariants of a completed route, returning the amount of tokens out.
+fn route_amount_out(continuation: &ContinuationV2, output: &TokenAccount) -> Result<u64> {
+    require!(continuation.steps_left == 0, EndIncomplete);
+    require!(continuation.legs().is_empty(), LegsNotMerged);
+
+    let result_balance = output.amount;
+    require!(
+        result_balance >= continuation.output_initial_balance,
+        BalanceLower
+    );
+    require!(
+        output.mint == continuation.minimum_amount_out.mint,
+        OutputMintMismatch,
+    );
+
+    let mut amount_out = result_balance - continuation.output_initial_balance;
+    // if input token = output token, add the initial amount in to the difference
+    if continuation.initial_amount_in.mint == output.mint {
+        amount_out += continuation.initial_amount_in.amount;
+    }
+
+    require!(
+        amount_out >= continuation.minimum_amount_out.amount,
+        MinimumOutNotMet,
+    );
+    Ok(amount_out)
+}
+
+/// Checks the invariants of a completed route and emits its result.
+pub fn complete_route(continuation: &ContinuationV2, output: &TokenAccount) -> Result<()> {
+    require!(continuation.mode() == RouteMode::ExactIn, RouteModeMismatch);
+    let amount_out = TokenAmount::new(
+        continuation.minimum_amount_out.mint,
+        route_amount_out(continuation, output)?,
+    );
+    RouteResult {
+        amount_in: continuation.initial_amount_in,
+        amount_out,
+    }
+    .set_return_data()?;
+    emit!(SwapCompleteEvent {
+        owner: continuation.owner,
+        amount_in: continuation.initial_amount_in,
+        amount_out,
+    });
+    Ok(())
+}
+
+/// Checks the invariants of a completed [RouteMode::ExactOut] route and emits its result.
+///
+/// Returns the output above the exact amount out, which must be refunded
+/// for the output account to receive exactly the amount out.
+pub fn complete_exact_out_route(
+    continuation: &ContinuationV2,
+    input: &AccountInfo,
+    output: &TokenAccount,
+) -> Result<u64> {
+    require!(
+        continuation.mode() == RouteMode::ExactOut,
+        RouteModeMismatch
+    );
+    state::assert_has_one(continuation.initial_input, input.key())?;
+    let input = TokenAccount::load(input)?;
+    let amount_out = continuation.minimum_amount_out;
+    let surplus = route_amount_out(continuation, output)? - amount_out.amount;
+
+    // the tokens actually spent, rather than the amount the route was begun with
+    let amount_in = TokenAmount::new(
+        continuation.initial_amount_in.mint,
+        continuation
+            .input_initial_balance
+            .saturating_sub(input.amount),
+    );
+    require!(
+        amount_in.amount <= continuation.maximum_amount_in.amount,
+        MaximumAmountInExceeded
+    );
+
+    RouteResult {
+        amount_in,
+        amount_out,
+    }
+    .set_return_data()?;
+    emit!(SwapExactOutCompleteEvent {
+        owner: continuation.owner,
+        amount_in,
+        maximum_amount_in: continuation.maximum_amount_in,
+        amount_out,
+        refunded: TokenAmount::new(amount_out.mint, surplus),
+    });
+    Ok(surplus)
+}
+
+/// Completes the route of a continuation account, then closes it to the payer.
+pub fn end_route<'info>(
+    continuation: &AccountInfo<'info>,
+    output: &AccountInfo<'info>,
+    authority: Pubkey,
+    payer: &AccountInfo<'info>,
+) -> Result<()> {
+    state::load(continuation, |continuation| {
+        state::assert_has_one(continuation.authority(), authority)?;
+        state::assert_has_one(continuation.payer, payer.key())?;
+        state::assert_has_one(continuation.output, output.key())?;
+        continuation.assert_not_expired()?;
+        complete_route(continuation, &TokenAccount::load(output)?)
+    })?;
+    state::close(continuation, payer)
+}
+
+/// Loads a token account of either token program, checking that it is owned by `owner`.
+pub fn load_owned_token_account(
+    account: &AccountInfo,
+    owner: Pubkey,
+    name: &str,
+) -> Result<TokenAccount> {
+    let token_account = TokenAccount::load(account)?;
+    if token_account.owner != owner {
+        return Err(error!(anchor_lang::error::ErrorCode::ConstraintHasOne)
+            .with_account_name(name)
+            .with_pubkeys((token_account.owner, owner)));
+    }
+    Ok(token_account)
+}
+
+/// Transfers `amount` tokens of `mint` out of `from`.
+///
+/// Token-2022 only transfers tokens of a mint with a transfer fee if given the mint, which
+/// may be passed as the first of the `remaining_accounts`. The transfer is then checked
+/// against the mint, and `to` receives `amount` less the transfer fee.
+pub fn transfer<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    mint: Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    match remaining_accounts.first() {
+        Some(mint_info) if mint_info.key() == mint => token_interface::transfer_checked(
+            CpiContext::new(
+                token_program.clone(),
+                token_interface::TransferChecked {
+                    from: from.clone(),
+                    mint: mint_info.clone(),
+                    to: to.clone(),
+                    authority: authority.clone(),
+                },
+            ),
+            amount,
+            token_interface::Mint::load(mint_info)?.decimals,
+        ),
+        _ => token_interface::transfer(
+            CpiContext::new(
+                token_program.clone(),
+                token_interface::Transfer {
+                    from: from.clone(),
+                    to: to.clone(),
+                    authority: authority.clone(),
+                },
+            ),
+            amount,
+        ),
+    }
+}
@@ -0,0 +1,68 @@
+//! Return data written by the router.
+//!
+//! After every action and after [crate::continuation_router::end], the router sets its
+//! return data to a Borsh-serialized [RouteResult]:
+//!
+//! | Offset | Size | Field                |
+//! |--------|------|----------------------|
+//! | 0      | 32   | `amount_in.mint`     |
+//! | 32     | 8    | `amount_in.amount`   |
+//! | 40     | 32   | `amount_out.mint`    |
+//! | 72     | 8    | `amount_out.amount`  |
+//!
+//! For an action, the amounts are those of the step. For `end`, they are those of the whole route.
+//! Programs calling the router via CPI may read the result with [RouteResult::from_return_data].
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{get_return_data, set_return_data};
+
+use crate::TokenAmount;
+
+/// Realized amounts of a route or of a step of a route.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RouteResult {
+    /// Amount of tokens that went in.
+    pub amount_in: TokenAmount,
+    /// Amount of tokens that came out.
+    pub amount_out: TokenAmount,
+}
+
+impl RouteResult {
+    pub const LEN: usize = TokenAmount::LEN * 2;
+
+    /// Sets the [RouteResult] as the return data of the current instruction.
+    pub fn set_return_data(&self) -> Result<()> {
+        set_return_data(&self.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Reads the [RouteResult] of the last router instruction invoked via CPI.
+    ///
+    /// Returns `None` if the return data was not set by the router.
+    pub fn from_return_data() -> Option<RouteResult> {
+        let (program_id, data) = get_return_data()?;
+        if program_id != crate::ID {
+            return None;
+        }
+        RouteResult::try_from_slice(&data).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_result_layout() {
+        let result = RouteResult {
+            amount_in: TokenAmount::new(Pubkey::new_unique(), 1),
+            amount_out: TokenAmount::new(Pubkey::new_unique(), 2),
+        };
+        let data = result.try_to_vec().unwrap();
+        assert_eq!(data.len(), RouteResult::LEN);
+        assert_eq!(data[0..32], result.amount_in.mint.to_bytes());
+        assert_eq!(data[32..40], 1_u64.to_le_bytes());
+        assert_eq!(data[40..72], result.amount_out.mint.to_bytes());
+        assert_eq!(data[72..80], 2_u64.to_le_bytes());
+    }
+}
@@ -0,0 +1,91 @@
+//! Processing of the steps of a single-instruction route.
+
+use std::collections::BTreeMap;
+
+use crate::processor::{ActionArgs, ActionContext, Processor};
+use crate::*;
+
+/// Processes a [RouteStep] of a [crate::continuation_router::route].
+pub fn process_step<'info>(
+    ctx: &Context<'_, '_, '_, 'info, Route<'info>>,
+    step: &RouteStep,
+    continuation: &mut ContinuationV2,
+) -> Result<()> {
+    let (swap_program, accounts) =
+        step_accounts(ctx.remaining_accounts, step, &ctx.accounts.config)?;
+
+    match step.action_type {
+        ActionType::SSSwap => process_action::<SSSwap>(ctx, swap_program, accounts, continuation),
+        ActionType::SSWithdrawOne => {
+            process_action::<SSWithdrawOne>(ctx, swap_program, accounts, continuation)
+        }
+        ActionType::SSWithdraw => {
+            process_action::<SSWithdraw>(ctx, swap_program, accounts, continuation)
+        }
+        ActionType::SSDepositA => {
+            process_action::<SSDepositA>(ctx, swap_program, accounts, continuation)
+        }
+        ActionType::SSDepositB => {
+            process_action::<SSDepositB>(ctx, swap_program, accounts, continuation)
+        }
+        ActionType::SSDepositBalanced => {
+            process_action::<SSDepositBalanced>(ctx, swap_program, accounts, continuation)
+        }
+        ActionType::ADWithdraw => {
+            process_action::<ADWithdraw>(ctx, swap_program, accounts, continuation)
+        }
+        ActionType::ADDeposit => {
+            process_action::<ADDeposit>(ctx, swap_program, accounts, continuation)
+        }
+        // actions with arguments cannot be routed
+        ActionType::TakeFee | ActionType::ExternalAction => {
+            Err(error!(ErrorCode::InvalidActionArgs))
+        }
+    }
+}
+
+/// Splits the accounts of a [RouteStep] into its swap program and the accounts of its action.
+pub fn step_accounts<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    step: &RouteStep,
+    config: &RouterConfig,
+) -> Result<(&'a AccountInfo<'info>, &'a [AccountInfo<'info>])> {
+    let accounts = unwrap_opt!(
+        remaining_accounts.get(step.accounts_start as usize..step.accounts_end as usize),
+        InvalidRouteStep
+    );
+    let (swap_program, accounts) = unwrap_opt!(accounts.split_first(), InvalidRouteStep);
+    invariant!(config.is_approved(swap_program.key), SwapProgramNotApproved);
+    Ok((swap_program, accounts))
+}
+
+/// Deserializes the accounts of an action and processes it.
+fn process_action<'info, T>(
+    ctx: &Context<'_, '_, '_, 'info, Route<'info>>,
+    swap_program: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    continuation: &mut ContinuationV2,
+) -> Result<()>
+where
+    T: Accounts<'info>,
+    for<'a, 'b, 'c> ActionContext<'a, 'b, 'c, 'info, T>: Processor<'info>,
+{
+    let mut remaining_accounts = accounts;
+    let action = T::try_accounts(
+        ctx.program_id,
+        &mut remaining_accounts,
+        &[],
+        &mut BTreeMap::new(),
+    )?;
+    let action_ctx = &ActionContext {
+        program_id: ctx.program_id,
+        action: &action,
+        remaining_accounts,
+        token_program: ctx.accounts.token_program.clone(),
+        swap_program: swap_program.clone(),
+        owner: ctx.accounts.owner.to_account_info(),
+        route_owner: continuation.owner,
+        args: ActionArgs::None,
+    };
+    action_ctx.process(continuation)
+}
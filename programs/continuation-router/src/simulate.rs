@@ -0,0 +1,446 @@
+//! Simulation of the steps of a route against the current state of their pools.
+//!
+//! [crate::continuation_router::simulate_route] sets its return data to the
+//! Borsh-serialized `Vec<RouteResult>` of the expected amounts of each step.
+
+use anchor_spl::token::Mint;
+use stable_swap_anchor::SwapInfo;
+use stable_swap_math::curve::StableSwap as StableSwapCurve;
+use stable_swap_math::math::FeeCalculator;
+use stable_swap_math::pool_converter::PoolTokenConverter;
+use std::collections::BTreeMap;
+
+use crate::action::stable_swap::compute_balanced_amount;
+use crate::*;
+
+/// Maximum number of steps which may be simulated, such that the results fit in the return data.
+pub const MAX_SIMULATED_STEPS: usize = 12;
+
+/// Number of accounts of an add-decimals user stake, which precede the underlying mint.
+const AD_USER_STAKE_ACCOUNTS: usize = 7;
+
+/// Computes the expected output of an action without moving any tokens.
+pub trait SimulateAction<'info>: Sized {
+    /// Mints of the input and output token accounts of the action.
+    fn mints(&self) -> Result<(Pubkey, Pubkey)>;
+
+    /// Computes the amount of tokens out of the action for `amount_in` tokens.
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64>;
+}
+
+/// Simulates a [RouteStep] of a [crate::continuation_router::simulate_route].
+pub fn simulate_step<'info>(
+    ctx: &Context<'_, '_, '_, 'info, SimulateRoute<'info>>,
+    step: &RouteStep,
+    amount_in: u64,
+) -> Result<RouteResult> {
+    let (swap_program, accounts) =
+        route::step_accounts(ctx.remaining_accounts, step, &ctx.accounts.config)?;
+    match step.action_type {
+        ActionType::SSSwap => simulate_action::<SSSwap>(ctx, swap_program, accounts, amount_in),
+        ActionType::SSWithdrawOne => {
+            simulate_action::<SSWithdrawOne>(ctx, swap_program, accounts, amount_in)
+        }
+        ActionType::SSWithdraw => {
+            simulate_action::<SSWithdraw>(ctx, swap_program, accounts, amount_in)
+        }
+        ActionType::SSDepositA => {
+            simulate_action::<SSDepositA>(ctx, swap_program, accounts, amount_in)
+        }
+        ActionType::SSDepositB => {
+            simulate_action::<SSDepositB>(ctx, swap_program, accounts, amount_in)
+        }
+        ActionType::SSDepositBalanced => {
+            simulate_action::<SSDepositBalanced>(ctx, swap_program, accounts, amount_in)
+        }
+        ActionType::ADWithdraw => {
+            simulate_action::<ADWithdraw>(ctx, swap_program, accounts, amount_in)
+        }
+        ActionType::ADDeposit => {
+            simulate_action::<ADDeposit>(ctx, swap_program, accounts, amount_in)
+        }
+        // actions with arguments cannot be routed
+        ActionType::TakeFee | ActionType::ExternalAction => {
+            Err(error!(ErrorCode::InvalidActionArgs))
+        }
+    }
+}
+
+/// Deserializes the accounts of an action and simulates it.
+fn simulate_action<'info, T>(
+    ctx: &Context<'_, '_, '_, 'info, SimulateRoute<'info>>,
+    swap_program: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    amount_in: u64,
+) -> Result<RouteResult>
+where
+    T: Accounts<'info> + SimulateAction<'info>,
+{
+    invariant!(amount_in != 0, ZeroSwap);
+    let mut remaining_accounts = accounts;
+    let action = T::try_accounts(
+        ctx.program_id,
+        &mut remaining_accounts,
+        &[],
+        &mut BTreeMap::new(),
+    )?;
+    let (input_mint, output_mint) = action.mints()?;
+    let amount_out = action.simulate(swap_program, remaining_accounts, amount_in)?;
+    Ok(RouteResult {
+        amount_in: TokenAmount::new(input_mint, amount_in),
+        amount_out: TokenAmount::new(output_mint, amount_out),
+    })
+}
+
+/// State of a StableSwap pool, as seen from one of its tokens.
+struct PoolState {
+    info: SwapInfo,
+    curve: StableSwapCurve,
+    /// Whether the token is token A of the pool.
+    is_token_a: bool,
+    /// Reserves of the token.
+    reserve: u64,
+    /// Reserves of the other token.
+    other_reserve: u64,
+}
+
+impl PoolState {
+    /// Loads the state of a swap from the reserve of a token and the reserve of the other token.
+    fn load(
+        swap_program: &AccountInfo,
+        swap: &StableSwap,
+        reserve: &AccountInfo,
+        other_reserve: &AccountInfo,
+    ) -> Result<PoolState> {
+        assert_keys_eq!(*swap.swap.owner, swap_program.key(), InvalidRouteStep);
+        let info = SwapInfo::try_deserialize(&mut &swap.swap.try_borrow_data()?[..])?;
+        let is_token_a = if *reserve.key == info.token_a.reserves {
+            assert_keys_eq!(other_reserve.key(), info.token_b.reserves, InvalidRouteStep);
+            true
+        } else {
+            assert_keys_eq!(reserve.key(), info.token_b.reserves, InvalidRouteStep);
+            assert_keys_eq!(other_reserve.key(), info.token_a.reserves, InvalidRouteStep);
+            false
+        };
+        let reserve = Account::<TokenAccount>::try_from(reserve)?.amount;
+        let other_reserve = Account::<TokenAccount>::try_from(other_reserve)?.amount;
+        let curve = StableSwapCurve::new_from_swap_info(&info, Clock::get()?.unix_timestamp);
+        Ok(PoolState {
+            info,
+            curve,
+            is_token_a,
+            reserve,
+            other_reserve,
+        })
+    }
+
+    /// Reserves of token A and token B.
+    fn reserves(&self) -> (u64, u64) {
+        if self.is_token_a {
+            (self.reserve, self.other_reserve)
+        } else {
+            (self.other_reserve, self.reserve)
+        }
+    }
+
+    /// Supply of the pool token.
+    fn pool_token_supply(&self, pool_mint: &AccountInfo) -> Result<u64> {
+        assert_keys_eq!(pool_mint.key(), self.info.pool_mint, InvalidRouteStep);
+        Ok(Account::<Mint>::try_from(pool_mint)?.supply)
+    }
+
+    /// Computes the pool tokens minted for depositing `amount` of the token
+    /// and `other_amount` of the other token.
+    fn deposit(&self, pool_mint: &AccountInfo, amount: u64, other_amount: u64) -> Result<u64> {
+        let (amount_a, amount_b) = if self.is_token_a {
+            (amount, other_amount)
+        } else {
+            (other_amount, amount)
+        };
+        let (reserve_a, reserve_b) = self.reserves();
+        Ok(unwrap_opt!(
+            self.curve.compute_mint_amount_for_deposit(
+                amount_a,
+                amount_b,
+                reserve_a,
+                reserve_b,
+                self.pool_token_supply(pool_mint)?,
+                &self.info.fees,
+            ),
+            SimulationFailed
+        ))
+    }
+}
+
+impl<'info> SimulateAction<'info> for SSSwap<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((self.input.user.mint, self.output.user_token.user.mint))
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        _remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let pool = PoolState::load(
+            swap_program,
+            &self.swap,
+            &self.input.reserve,
+            &self.output.user_token.reserve,
+        )?;
+        let result = unwrap_opt!(
+            pool.curve
+                .swap_to(amount_in, pool.reserve, pool.other_reserve, &pool.info.fees),
+            SimulationFailed
+        );
+        Ok(result.amount_swapped)
+    }
+}
+
+impl<'info> SimulateAction<'info> for SSWithdrawOne<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((self.input_lp.mint, self.output.user_token.user.mint))
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        _remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let pool = PoolState::load(
+            swap_program,
+            &self.swap,
+            &self.output.user_token.reserve,
+            &self.quote_reserves,
+        )?;
+        let fees = &pool.info.fees;
+        let amount_out = pool
+            .curve
+            .compute_withdraw_one(
+                amount_in,
+                pool.pool_token_supply(&self.pool_mint)?,
+                pool.reserve,
+                pool.other_reserve,
+                fees,
+            )
+            .and_then(|(amount, _)| amount.checked_sub(fees.withdraw_fee(amount)?));
+        Ok(unwrap_opt!(amount_out, SimulationFailed))
+    }
+}
+
+impl<'info> SimulateAction<'info> for SSWithdraw<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((self.input_lp.mint, self.output.user_token.user.mint))
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        _remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let pool = PoolState::load(
+            swap_program,
+            &self.swap,
+            &self.output.user_token.reserve,
+            &self.other_output.user_token.reserve,
+        )?;
+        let (token_a, token_b) = pool.reserves();
+        let converter = PoolTokenConverter {
+            supply: pool.pool_token_supply(&self.pool_mint)?,
+            token_a,
+            token_b,
+            fees: &pool.info.fees,
+        };
+        let rate = if pool.is_token_a {
+            converter.token_a_rate(amount_in)
+        } else {
+            converter.token_b_rate(amount_in)
+        };
+        let (amount_out, _, _) = unwrap_opt!(rate, SimulationFailed);
+        Ok(amount_out)
+    }
+}
+
+impl<'info> SimulateAction<'info> for SSDepositA<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((self.input_a.user.mint, self.output_lp.mint))
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        _remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let pool = PoolState::load(
+            swap_program,
+            &self.swap,
+            &self.input_a.reserve,
+            &self.input_b.reserve,
+        )?;
+        invariant!(pool.is_token_a, InvalidRouteStep);
+        pool.deposit(&self.pool_mint, amount_in, 0)
+    }
+}
+
+impl<'info> SimulateAction<'info> for SSDepositB<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((self.input_b.user.mint, self.output_lp.mint))
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        _remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let pool = PoolState::load(
+            swap_program,
+            &self.swap,
+            &self.input_b.reserve,
+            &self.input_a.reserve,
+        )?;
+        invariant!(!pool.is_token_a, InvalidRouteStep);
+        pool.deposit(&self.pool_mint, amount_in, 0)
+    }
+}
+
+impl<'info> SimulateAction<'info> for SSDepositBalanced<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((self.input.user.mint, self.output_lp.mint))
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        _remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let pool = PoolState::load(
+            swap_program,
+            &self.swap,
+            &self.input.reserve,
+            &self.other_input.reserve,
+        )?;
+        let other_amount = unwrap_int!(compute_balanced_amount(
+            amount_in,
+            pool.reserve,
+            pool.other_reserve
+        ));
+        pool.deposit(&self.pool_mint, amount_in, other_amount)
+    }
+}
+
+/// State of an add-decimals wrapper.
+///
+/// This mirrors `add_decimals::WrappedToken`, as add-decimals depends on the router.
+#[derive(AnchorDeserialize)]
+struct WrapperState {
+    _decimals: u8,
+    multiplier: u64,
+    underlying_mint: Pubkey,
+    _underlying_tokens: Pubkey,
+    wrapped_mint: Pubkey,
+}
+
+impl WrapperState {
+    /// Loads the wrapper, which is the first of the remaining accounts passed to add-decimals.
+    fn load(swap_program: &AccountInfo, remaining_accounts: &[AccountInfo]) -> Result<Self> {
+        let wrapper = unwrap_opt!(remaining_accounts.first(), InvalidRouteStep);
+        // add-decimals owns no accounts other than wrappers
+        assert_keys_eq!(*wrapper.owner, swap_program.key(), InvalidRouteStep);
+        let data = wrapper.try_borrow_data()?;
+        let mut state = unwrap_opt!(data.get(8..), InvalidRouteStep);
+        Ok(WrapperState::deserialize(&mut state)?)
+    }
+
+    /// Computes the transfer fee of moving `amount` underlying tokens.
+    ///
+    /// Underlying mints of Token-2022 are passed to add-decimals after the accounts of
+    /// the user stake. Without them, the underlying mint has no transfer fee.
+    fn underlying_transfer_fee(
+        &self,
+        remaining_accounts: &[AccountInfo],
+        amount: u64,
+    ) -> Result<u64> {
+        match remaining_accounts.get(AD_USER_STAKE_ACCOUNTS) {
+            Some(mint) if mint.key() == self.underlying_mint => {
+                token_interface::transfer_fee(mint, Clock::get()?.epoch, amount)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+/// Loads the mints of the input and output token accounts of an add-decimals action.
+fn token_account_mints(input: &AccountInfo, output: &AccountInfo) -> Result<(Pubkey, Pubkey)> {
+    Ok((
+        token_interface::TokenAccount::load(input)?.mint,
+        token_interface::TokenAccount::load(output)?.mint,
+    ))
+}
+
+impl<'info> SimulateAction<'info> for ADWithdraw<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        token_account_mints(&self.input, &self.output)
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let wrapper = WrapperState::load(swap_program, remaining_accounts)?;
+        let (input_mint, output_mint) = self.mints()?;
+        assert_keys_eq!(input_mint, wrapper.wrapped_mint, InputMintMismatch);
+        assert_keys_eq!(output_mint, wrapper.underlying_mint, OutputMintMismatch);
+        let amount_out = unwrap_int!(amount_in.checked_div(wrapper.multiplier));
+        let fee = wrapper.underlying_transfer_fee(remaining_accounts, amount_out)?;
+        Ok(unwrap_int!(amount_out.checked_sub(fee)))
+    }
+}
+
+impl<'info> SimulateAction<'info> for ADDeposit<'info> {
+    fn mints(&self) -> Result<(Pubkey, Pubkey)> {
+        token_account_mints(&self.input, &self.output)
+    }
+
+    fn simulate(
+        &self,
+        swap_program: &AccountInfo<'info>,
+        remaining_accounts: &[AccountInfo<'info>],
+        amount_in: u64,
+    ) -> Result<u64> {
+        let wrapper = WrapperState::load(swap_program, remaining_accounts)?;
+        let (input_mint, output_mint) = self.mints()?;
+        assert_keys_eq!(input_mint, wrapper.underlying_mint, InputMintMismatch);
+        assert_keys_eq!(output_mint, wrapper.wrapped_mint, OutputMintMismatch);
+        // add-decimals only wraps the tokens it receives
+        let fee = wrapper.underlying_transfer_fee(remaining_accounts, amount_in)?;
+        let amount_received = unwrap_int!(amount_in.checked_sub(fee));
+        Ok(unwrap_int!(wrapper.multiplier.checked_mul(amount_received)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program::MAX_RETURN_DATA;
+
+    #[test]
+    fn test_max_simulated_steps() {
+        let results = vec![RouteResult::default(); MAX_SIMULATED_STEPS];
+        assert!(results.try_to_vec().unwrap().len() <= MAX_RETURN_DATA);
+        let results = vec![RouteResult::default(); MAX_SIMULATED_STEPS + 1];
+        assert!(results.try_to_vec().unwrap().len() > MAX_RETURN_DATA);
+    }
+}
@@ -0,0 +1,215 @@
+//! Loading of continuation accounts.
+//!
+//! Routes started with [crate::continuation_router::begin] store a Borsh-serialized
+//! [Continuation], while routes started with [crate::continuation_router::begin_v2] store a
+//! zero-copy [ContinuationV2]. The two layouts have different discriminators, so the
+//! instructions of a route accept either while routes migrate to the zero-copy layout:
+//! a [ContinuationV2] is used in place, while a [Continuation] is converted to a
+//! [ContinuationV2] when loaded and written back once the instruction succeeds.
+
+use anchor_lang::{prelude::*, Discriminator};
+use vipers::prelude::*;
+
+use crate::{Continuation, ContinuationV2};
+
+/// Returns true if the account holds a [ContinuationV2].
+fn is_zero_copy(info: &AccountInfo) -> Result<bool> {
+    let data = info.try_borrow_data()?;
+    Ok(data.get(..8) == Some(&ContinuationV2::discriminator()[..]))
+}
+
+/// Calls `f` with the continuation stored in the account.
+pub fn load<'info, R>(
+    info: &AccountInfo<'info>,
+    f: impl FnOnce(&ContinuationV2) -> Result<R>,
+) -> Result<R> {
+    if is_zero_copy(info)? {
+        let loader = AccountLoader::<ContinuationV2>::try_from(info)?;
+        let continuation = loader.load()?;
+        return f(&continuation);
+    }
+    let account = Account::<Continuation>::try_from(info)?;
+    f(&ContinuationV2::try_from(&*account)?)
+}
+
+/// Calls `f` with the continuation stored in the account, persisting its changes.
+pub fn load_mut<'info, R>(
+    info: &AccountInfo<'info>,
+    f: impl FnOnce(&mut ContinuationV2) -> Result<R>,
+) -> Result<R> {
+    if is_zero_copy(info)? {
+        let loader = AccountLoader::<ContinuationV2>::try_from(info)?;
+        let mut continuation = loader.load_mut()?;
+        return f(&mut continuation);
+    }
+    let mut account = Account::<Continuation>::try_from(info)?;
+    let mut continuation = ContinuationV2::try_from(&*account)?;
+    let result = f(&mut continuation)?;
+    let nonce = account.__nonce;
+    account.set_inner(Continuation {
+        __nonce: nonce,
+        ..Continuation::from(&continuation)
+    });
+    account.exit(&crate::ID)?;
+    Ok(result)
+}
+
+/// Checks that an account is the one referenced by a field of the continuation,
+/// failing like the `has_one` constraint.
+pub fn assert_has_one(field: Pubkey, key: Pubkey) -> Result<()> {
+    if field != key {
+        return Err(error!(anchor_lang::error::ErrorCode::ConstraintHasOne)
+            .with_account_name("continuation")
+            .with_pubkeys((field, key)));
+    }
+    Ok(())
+}
+
+/// Closes a continuation account of either layout, sending its lamports to `destination`.
+pub fn close<'info>(info: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let lamports = unwrap_int!(destination.lamports().checked_add(info.lamports()));
+    **destination.try_borrow_mut_lamports()? = lamports;
+    **info.try_borrow_mut_lamports()? = 0;
+    info.try_borrow_mut_data()?[..8]
+        .copy_from_slice(&anchor_lang::__private::CLOSED_ACCOUNT_DISCRIMINATOR);
+    Ok(())
+}
+
+/// Deserializes the data of a continuation account of either layout.
+///
+/// Clients may use this to read routes regardless of the instruction that began them.
+pub fn try_deserialize(data: &[u8]) -> Result<ContinuationV2> {
+    if data.starts_with(&ContinuationV2::discriminator()) {
+        // `begin_v2` accepts accounts larger than the zero-copy layout
+        let mut data = data
+            .get(..8 + ContinuationV2::LEN)
+            .ok_or(anchor_lang::error::ErrorCode::AccountDidNotDeserialize)?;
+        return ContinuationV2::try_deserialize(&mut data);
+    }
+    ContinuationV2::try_from(&Continuation::try_deserialize(&mut &data[..])?)
+}
+
+impl TryFrom<&Continuation> for ContinuationV2 {
+    type Error = anchor_lang::error::Error;
+
+    fn try_from(continuation: &Continuation) -> Result<Self> {
+        let mut state = ContinuationV2 {
+            owner: continuation.owner,
+            payer: continuation.payer,
+            initial_amount_in: continuation.initial_amount_in,
+            input: continuation.input,
+            amount_in: continuation.amount_in,
+            output: continuation.output,
+            output_initial_balance: continuation.output_initial_balance,
+            minimum_amount_out: continuation.minimum_amount_out,
+            maximum_amount_in: continuation.maximum_amount_in,
+            created_at: continuation.created_at,
+            steps_left: continuation.steps_left,
+            num_steps: continuation.num_steps,
+            mode: continuation.mode.into(),
+            ..ContinuationV2::default()
+        };
+        state.set_legs(&continuation.legs)?;
+        state.set_step_minimums(&continuation.step_minimums)?;
+        state.set_valid_until(continuation.valid_until);
+        Ok(state)
+    }
+}
+
+/// The delegate of a route is not kept, since only [ContinuationV2] routes may be delegated.
+impl From<&ContinuationV2> for Continuation {
+    fn from(state: &ContinuationV2) -> Self {
+        Continuation {
+            owner: state.owner,
+            payer: state.payer,
+            initial_amount_in: state.initial_amount_in,
+            input: state.input,
+            amount_in: state.amount_in,
+            steps_left: state.steps_left,
+            num_steps: state.num_steps,
+            output: state.output,
+            output_initial_balance: state.output_initial_balance,
+            minimum_amount_out: state.minimum_amount_out,
+            mode: state.mode(),
+            maximum_amount_in: state.maximum_amount_in,
+            legs: state.legs().to_vec(),
+            step_minimums: state.step_minimums().to_vec(),
+            valid_until: state.valid_until(),
+            created_at: state.created_at,
+            __nonce: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Leg, RouteMode, TokenAmount, MAX_STEP_MINIMUMS};
+
+    fn token_amount(amount: u64) -> TokenAmount {
+        TokenAmount {
+            mint: Pubkey::new_unique(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_convert_round_trip() {
+        let continuation = Continuation {
+            owner: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+            initial_amount_in: token_amount(1),
+            input: Pubkey::new_unique(),
+            amount_in: token_amount(2),
+            steps_left: 3,
+            num_steps: 4,
+            output: Pubkey::new_unique(),
+            output_initial_balance: 5,
+            minimum_amount_out: token_amount(6),
+            mode: RouteMode::ExactOut,
+            maximum_amount_in: token_amount(7),
+            legs: vec![Leg {
+                input: Pubkey::new_unique(),
+                amount_in: token_amount(8),
+            }],
+            step_minimums: vec![9; MAX_STEP_MINIMUMS],
+            valid_until: Some(-10),
+            created_at: 11,
+            __nonce: 0,
+        };
+        let state = ContinuationV2::try_from(&continuation).unwrap();
+        assert_eq!(
+            Continuation::from(&state).try_to_vec().unwrap(),
+            continuation.try_to_vec().unwrap()
+        );
+        assert_eq!(
+            Continuation::from(&ContinuationV2::default())
+                .try_to_vec()
+                .unwrap(),
+            Continuation::default().try_to_vec().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_try_deserialize() {
+        let state = ContinuationV2 {
+            owner: Pubkey::new_unique(),
+            steps_left: 1,
+            ..ContinuationV2::default()
+        };
+
+        let mut legacy = vec![];
+        Continuation::from(&state)
+            .try_serialize(&mut legacy)
+            .unwrap();
+        legacy.resize(8 + Continuation::LEN, 0);
+        assert_eq!(try_deserialize(&legacy).unwrap(), state);
+
+        // zero-copy accounts may be allocated with more space than the layout
+        let mut zero_copy = ContinuationV2::discriminator().to_vec();
+        zero_copy.extend_from_slice(anchor_lang::__private::bytemuck::bytes_of(&state));
+        zero_copy.resize(8 + ContinuationV2::LEN + 64, 0);
+        assert_eq!(try_deserialize(&zero_copy).unwrap(), state);
+        assert!(try_deserialize(&zero_copy[..8 + ContinuationV2::LEN - 1]).is_err());
+    }
+}
@@ -23,12 +23,12 @@ pub fn router_action(args: TokenStream, input: TokenStream) -> TokenStream {
                 {
                     quote! {
                         impl<'info> crate::processor::ActionInputOutput<'info> for ActionContext<'_, '_, '_, 'info, #action_name<'info>> {
-                            fn input_account(&self) -> &Account<'info, TokenAccount> {
-                                &self.action.input
+                            fn input_account(&self) -> AccountInfo<'info> {
+                                self.action.input.to_account_info()
                             }
 
-                            fn output_account(&self) -> &Account<'info, TokenAccount> {
-                                &self.action.output
+                            fn output_account(&self) -> AccountInfo<'info> {
+                                self.action.output.to_account_info()
                             }
                         }
 
@@ -43,7 +43,7 @@ pub fn router_action(args: TokenStream, input: TokenStream) -> TokenStream {
                                         self.swap_program.clone(),
                                         self.remaining_accounts.to_vec()
                                     ),
-                                    Self::TYPE.into(),
+                                    self.args.action_code().unwrap_or_else(|| Self::TYPE.into()),
                                     amount_in,
                                     minimum_amount_out,
                                 )
@@ -53,10 +53,10 @@ pub fn router_action(args: TokenStream, input: TokenStream) -> TokenStream {
                 }
                 _ => quote! {
                     impl<'info> crate::processor::ActionInputOutput<'info> for ActionContext<'_, '_, '_, 'info, #action_name<'info>> {
-                        fn input_account(&self) -> &Account<'info, TokenAccount> {
+                        fn input_account(&self) -> AccountInfo<'info> {
                             self.action.input_account()
                         }
-                        fn output_account(&self) -> &Account<'info, TokenAccount> {
+                        fn output_account(&self) -> AccountInfo<'info> {
                             self.action.output_account()
                         }
                     }
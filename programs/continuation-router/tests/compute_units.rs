@@ -0,0 +1,743 @@
+//! Compute units consumed by the router instructions.
+//!
+//! Each benchmark is a route whose last instruction is measured. `cargo test` checks that
+//! the benchmarks succeed in-process, while measuring compute units requires the BPF
+//! programs, with the mock swap program built at the StableSwap address:
+//!
+//! ```sh
+//! anchor build
+//! cargo build-bpf --manifest-path programs/mock-swap/Cargo.toml --features stable-swap-id
+//! BPF_OUT_DIR=$PWD/target/deploy cargo test -p continuation-router --features test-bpf --test compute_units
+//! ```
+//!
+//! The test prints the compute units of each benchmark, and fails if an instruction consumes
+//! more than recorded in `tests/compute_units.txt`. Set `UPDATE_COMPUTE_UNITS=1` to record
+//! the measured units instead.
+//!
+//! Routes are begun with `begin_v2`, which creates a zero-copy continuation. Benchmarks
+//! suffixed with `_legacy` run the same instruction against a continuation in the Borsh
+//! layout created by `begin`, so the difference is the saving of the zero-copy layout.
+//! Updating the baseline also rewrites the table of these savings in the README.
+
+mod harness;
+
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use continuation_router::{accounts, instruction};
+use harness::{Pool, TestBank};
+use mock_swap::{Mode, PoolParams};
+
+/// Action code of the mock swap program as an adapter.
+const ADAPTER_ACTION_CODE: u16 = 1_000;
+
+const AMOUNT: u64 = 1_000_000;
+
+/// A pool and the token accounts of the owner of a route.
+struct Fixture {
+    bank: TestBank,
+    owner: Pubkey,
+    continuation: Pubkey,
+    pool: Pool,
+    /// Accounts of token A and token B.
+    tokens: [Pubkey; 2],
+    /// Account of LP tokens.
+    lp: Pubkey,
+    /// Account of token A of another wallet, which receives fees.
+    referrer: Pubkey,
+}
+
+impl Fixture {
+    fn new(owner: Pubkey) -> Self {
+        let mut bank = TestBank::new();
+        bank.set_account(
+            owner,
+            harness::TestAccount {
+                lamports: 1_000_000_000,
+                ..Default::default()
+            },
+        );
+        let admin = bank.create_wallet(1_000_000_000);
+        let config = bank.init_router(admin, vec![mock_swap::ID]);
+        bank.process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::UpdateConfig { config, admin },
+            instruction::RegisterAdapter {
+                program: mock_swap::ID,
+                action_code: ADAPTER_ACTION_CODE,
+            },
+        ))
+        .unwrap();
+
+        let continuation = bank.create_continuation();
+        let mints = [bank.create_mint(), bank.create_mint()];
+        let params = PoolParams {
+            rate_bps: 10_000,
+            fee_bps: 4,
+            admin_fee_bps: 5_000,
+            mode: Mode::Normal,
+        };
+        let pool = bank.create_pool(mints, [AMOUNT * 1_000, AMOUNT * 1_000], params);
+        let tokens = [
+            bank.create_token_account(mints[0], owner, AMOUNT),
+            bank.create_token_account(mints[1], owner, AMOUNT),
+        ];
+        let lp = bank.create_token_account(pool.pool_mint, owner, AMOUNT);
+        let referrer_wallet = bank.create_wallet(1_000_000_000);
+        let referrer = bank.create_token_account(mints[0], referrer_wallet, 0);
+        Fixture {
+            bank,
+            owner,
+            continuation,
+            pool,
+            tokens,
+            lp,
+            referrer,
+        }
+    }
+
+    fn begin(&self) -> Instruction {
+        let random = Pubkey::new_unique();
+        let (continuation, _) = Pubkey::find_program_address(
+            &[b"anchor", self.owner.as_ref(), random.as_ref()],
+            &continuation_router::ID,
+        );
+        harness::instruction(
+            continuation_router::ID,
+            accounts::Begin {
+                continuation,
+                random,
+                input: self.tokens[0],
+                output: self.tokens[1],
+                owner: self.owner,
+                payer: self.owner,
+                rent: sysvar::rent::ID,
+                system_program: anchor_lang::system_program::ID,
+            },
+            instruction::Begin {
+                amount_in: AMOUNT,
+                minimum_amount_out: 0,
+                num_steps: 1,
+                valid_until: None,
+            },
+        )
+    }
+
+    fn begin_v2(&self, input: Pubkey, output: Pubkey, num_steps: u16) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::BeginV2 {
+                continuation: self.continuation,
+                input,
+                output,
+                owner: self.owner,
+            },
+            instruction::BeginV2 {
+                amount_in: AMOUNT,
+                minimum_amount_out: 0,
+                num_steps,
+                valid_until: None,
+            },
+        )
+    }
+
+    /// Begins a route with a continuation in the legacy layout.
+    ///
+    /// The route is begun by `begin_v2` and then rewritten in the layout of `begin`,
+    /// since the harness cannot create the continuation of `begin`.
+    fn begin_legacy(&mut self, input: Pubkey, output: Pubkey, num_steps: u16) {
+        self.bank
+            .process_instruction(self.begin_v2(input, output, num_steps))
+            .unwrap();
+        self.bank.convert_to_legacy_continuation(&self.continuation);
+    }
+
+    fn end(&self, output: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::End {
+                continuation: self.continuation,
+                output,
+                owner: self.owner,
+                payer: self.owner,
+            },
+            instruction::End {},
+        )
+    }
+
+    fn continuation_accounts(&self, swap_program: Pubkey) -> accounts::ContinuationAccounts {
+        accounts::ContinuationAccounts {
+            continuation: self.continuation,
+            token_program: anchor_spl::token::ID,
+            swap_program,
+            config: Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID).0,
+            owner: self.owner,
+        }
+    }
+
+    fn stable_swap(&self) -> accounts::StableSwap {
+        accounts::StableSwap {
+            swap: self.pool.swap,
+            swap_authority: self.pool.config,
+            clock: sysvar::clock::ID,
+        }
+    }
+
+    /// Token accounts of token `index` of the pool.
+    fn swap_token(&self, index: usize) -> accounts::SwapToken {
+        accounts::SwapToken {
+            user: self.tokens[index],
+            reserve: self.pool.reserves[index],
+        }
+    }
+
+    fn swap_output(&self, index: usize) -> accounts::SwapOutput {
+        accounts::SwapOutput {
+            user_token: self.swap_token(index),
+            fees: self.pool.admin_fees[index],
+        }
+    }
+
+    fn ss_deposit(&self) -> accounts::SSDeposit {
+        accounts::SSDeposit {
+            swap: self.stable_swap(),
+            input_a: self.swap_token(0),
+            input_b: self.swap_token(1),
+            pool_mint: self.pool.pool_mint,
+            output_lp: self.lp,
+        }
+    }
+
+    /// Appends the accounts of a swap of token A for token B by the mock swap program.
+    fn with_swap_accounts(&self, mut instruction: Instruction) -> Instruction {
+        instruction.accounts.extend(self.pool.swap_accounts(
+            self.owner,
+            0,
+            self.tokens[0],
+            self.tokens[1],
+        ));
+        instruction
+    }
+
+    fn ss_swap(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSSwapAccounts {
+                continuation: self.continuation_accounts(stable_swap_anchor::ID),
+                action: accounts::SSSwap {
+                    swap: self.stable_swap(),
+                    input: self.swap_token(0),
+                    output: self.swap_output(1),
+                },
+            },
+            instruction::SsSwap {},
+        )
+    }
+
+    fn ss_withdraw_one(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSWithdrawOneAccounts {
+                continuation: self.continuation_accounts(stable_swap_anchor::ID),
+                action: accounts::SSWithdrawOne {
+                    swap: self.stable_swap(),
+                    pool_mint: self.pool.pool_mint,
+                    input_lp: self.lp,
+                    quote_reserves: self.pool.reserves[0],
+                    output: self.swap_output(1),
+                },
+            },
+            instruction::SsWithdrawOne {},
+        )
+    }
+
+    fn ss_deposit_a(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSDepositAAccounts {
+                continuation: self.continuation_accounts(stable_swap_anchor::ID),
+                action: accounts::SSDepositA {
+                    inner: self.ss_deposit(),
+                },
+            },
+            instruction::SsDepositA {},
+        )
+    }
+
+    fn ss_deposit_b(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSDepositBAccounts {
+                continuation: self.continuation_accounts(stable_swap_anchor::ID),
+                action: accounts::SSDepositB {
+                    inner: self.ss_deposit(),
+                },
+            },
+            instruction::SsDepositB {},
+        )
+    }
+
+    fn ss_withdraw(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSWithdrawAccounts {
+                continuation: self.continuation_accounts(stable_swap_anchor::ID),
+                action: accounts::SSWithdraw {
+                    swap: self.stable_swap(),
+                    pool_mint: self.pool.pool_mint,
+                    input_lp: self.lp,
+                    output: self.swap_output(0),
+                    other_output: self.swap_output(1),
+                },
+            },
+            instruction::SsWithdraw {},
+        )
+    }
+
+    fn ss_deposit_balanced(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSDepositBalancedAccounts {
+                continuation: self.continuation_accounts(stable_swap_anchor::ID),
+                action: accounts::SSDepositBalanced {
+                    swap: self.stable_swap(),
+                    input: self.swap_token(0),
+                    other_input: self.swap_token(1),
+                    pool_mint: self.pool.pool_mint,
+                    output_lp: self.lp,
+                },
+            },
+            instruction::SsDepositBalanced {},
+        )
+    }
+
+    fn ad_withdraw(&self) -> Instruction {
+        self.with_swap_accounts(harness::instruction(
+            continuation_router::ID,
+            accounts::ADWithdrawAccounts {
+                continuation: self.continuation_accounts(mock_swap::ID),
+                action: accounts::ADWithdraw {
+                    input: self.tokens[0],
+                    output: self.tokens[1],
+                },
+            },
+            instruction::AdWithdraw {},
+        ))
+    }
+
+    fn ad_deposit(&self) -> Instruction {
+        self.with_swap_accounts(harness::instruction(
+            continuation_router::ID,
+            accounts::ADDepositAccounts {
+                continuation: self.continuation_accounts(mock_swap::ID),
+                action: accounts::ADDeposit {
+                    input: self.tokens[0],
+                    output: self.tokens[1],
+                },
+            },
+            instruction::AdDeposit {},
+        ))
+    }
+
+    fn take_fee(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::TakeFeeAccounts {
+                continuation: self.continuation_accounts(anchor_spl::token::ID),
+                action: accounts::TakeFee {
+                    input: self.tokens[0],
+                    referrer: self.referrer,
+                },
+            },
+            instruction::TakeFee {
+                fee_bps: 30,
+                max_fee: u64::MAX,
+            },
+        )
+    }
+
+    fn external_action(&self) -> Instruction {
+        self.with_swap_accounts(harness::instruction(
+            continuation_router::ID,
+            accounts::ExternalActionAccounts {
+                continuation: self.continuation_accounts(mock_swap::ID),
+                action: accounts::ExternalAction {
+                    input: self.tokens[0],
+                    output: self.tokens[1],
+                },
+            },
+            instruction::ExternalAction {
+                action_code: ADAPTER_ACTION_CODE,
+            },
+        ))
+    }
+}
+
+/// A benchmark returns the instructions of a route, each executed in its own transaction.
+/// The last instruction is measured.
+type Benchmark = fn(&mut Fixture) -> Vec<Instruction>;
+
+const BENCHMARKS: &[(&str, Benchmark)] = &[
+    ("begin", |f| vec![f.begin()]),
+    ("begin_v2", |f| {
+        vec![f.begin_v2(f.tokens[0], f.tokens[1], 1)]
+    }),
+    ("end", |f| {
+        vec![
+            f.begin_v2(f.tokens[0], f.tokens[1], 1),
+            f.ss_swap(),
+            f.end(f.tokens[1]),
+        ]
+    }),
+    ("ss_swap", |f| {
+        vec![f.begin_v2(f.tokens[0], f.tokens[1], 1), f.ss_swap()]
+    }),
+    ("ss_withdraw_one", |f| {
+        vec![f.begin_v2(f.lp, f.tokens[1], 1), f.ss_withdraw_one()]
+    }),
+    ("ss_deposit_a", |f| {
+        vec![f.begin_v2(f.tokens[0], f.lp, 1), f.ss_deposit_a()]
+    }),
+    ("ss_deposit_b", |f| {
+        vec![f.begin_v2(f.tokens[1], f.lp, 1), f.ss_deposit_b()]
+    }),
+    ("ss_withdraw", |f| {
+        vec![f.begin_v2(f.lp, f.tokens[0], 1), f.ss_withdraw()]
+    }),
+    ("ss_deposit_balanced", |f| {
+        vec![f.begin_v2(f.tokens[0], f.lp, 1), f.ss_deposit_balanced()]
+    }),
+    ("ad_withdraw", |f| {
+        vec![f.begin_v2(f.tokens[0], f.tokens[1], 1), f.ad_withdraw()]
+    }),
+    ("ad_deposit", |f| {
+        vec![f.begin_v2(f.tokens[0], f.tokens[1], 1), f.ad_deposit()]
+    }),
+    ("take_fee", |f| {
+        vec![f.begin_v2(f.tokens[0], f.tokens[1], 2), f.take_fee()]
+    }),
+    ("external_action", |f| {
+        vec![f.begin_v2(f.tokens[0], f.tokens[1], 1), f.external_action()]
+    }),
+    ("end_legacy", |f| {
+        f.begin_legacy(f.tokens[0], f.tokens[1], 1);
+        vec![f.ss_swap(), f.end(f.tokens[1])]
+    }),
+    ("ss_swap_legacy", |f| {
+        f.begin_legacy(f.tokens[0], f.tokens[1], 1);
+        vec![f.ss_swap()]
+    }),
+    ("ad_withdraw_legacy", |f| {
+        f.begin_legacy(f.tokens[0], f.tokens[1], 1);
+        vec![f.ad_withdraw()]
+    }),
+    ("take_fee_legacy", |f| {
+        f.begin_legacy(f.tokens[0], f.tokens[1], 2);
+        vec![f.take_fee()]
+    }),
+];
+
+/// Parses the baseline into compute units by benchmark name.
+fn parse_baseline(baseline: &str) -> BTreeMap<String, u64> {
+    baseline
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, units) = line
+                .split_once(char::is_whitespace)
+                .unwrap_or_else(|| panic!("invalid baseline line: {}", line));
+            (name.to_string(), units.trim().parse().unwrap())
+        })
+        .collect()
+}
+
+/// Formats compute units by benchmark name as a baseline.
+fn format_baseline(units: &BTreeMap<String, u64>) -> String {
+    let header = include_str!("compute_units.txt")
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| format!("{}\n", line));
+    let entries = units
+        .iter()
+        .map(|(name, units)| format!("{:<24}{}\n", name, units));
+    header.chain(["\n".to_string()]).chain(entries).collect()
+}
+
+/// Pairs of benchmarks of the same instruction against the Borsh and the zero-copy layouts.
+fn layout_pairs() -> Vec<(&'static str, &'static str)> {
+    let mut pairs = vec![("begin", "begin_v2")];
+    pairs.extend(BENCHMARKS.iter().filter_map(|(name, _)| {
+        let zero_copy = name.strip_suffix("_legacy")?;
+        Some((*name, zero_copy))
+    }));
+    pairs
+}
+
+/// Formats the compute units saved by the zero-copy layout as a Markdown table.
+fn format_savings(units: &BTreeMap<String, u64>) -> String {
+    let rows: Vec<String> = layout_pairs()
+        .into_iter()
+        .filter_map(|(legacy, zero_copy)| {
+            let legacy_units = *units.get(legacy)?;
+            let zero_copy_units = *units.get(zero_copy)?;
+            Some(format!(
+                "| `{}` / `{}` | {} | {} | {} |\n",
+                legacy,
+                zero_copy,
+                legacy_units,
+                zero_copy_units,
+                legacy_units as i64 - zero_copy_units as i64
+            ))
+        })
+        .collect();
+    if rows.is_empty() {
+        return "No compute units have been recorded in `tests/compute_units.txt` yet.\n"
+            .to_string();
+    }
+    let header = "| Benchmarks | `Continuation` | `ContinuationV2` | Saved |\n\
+                  | --- | ---: | ---: | ---: |\n";
+    std::iter::once(header.to_string()).chain(rows).collect()
+}
+
+/// Markers of the table of savings in the README.
+const SAVINGS_START: &str = "<!-- compute-savings:start -->";
+const SAVINGS_END: &str = "<!-- compute-savings:end -->";
+
+/// Replaces the table of savings between the markers of the README.
+fn update_savings(readme: &str, savings: &str) -> String {
+    let (before, rest) = readme
+        .split_once(SAVINGS_START)
+        .expect("missing start of the compute savings in the README");
+    let (_, after) = rest
+        .split_once(SAVINGS_END)
+        .expect("missing end of the compute savings in the README");
+    format!(
+        "{}{}\n{}{}{}",
+        before, SAVINGS_START, savings, SAVINGS_END, after
+    )
+}
+
+/// Compares measured compute units to the baseline, returning the benchmarks which exceed it
+/// or are missing from it.
+fn check_baseline(
+    baseline: &BTreeMap<String, u64>,
+    measured: &BTreeMap<String, u64>,
+) -> Vec<String> {
+    measured
+        .iter()
+        .filter_map(|(name, units)| match baseline.get(name) {
+            Some(limit) if units > limit => Some(format!(
+                "{}: consumed {}, above the baseline of {}",
+                name, units, limit
+            )),
+            Some(_) => None,
+            None => Some(format!(
+                "{}: consumed {}, missing from the baseline",
+                name, units
+            )),
+        })
+        .collect()
+}
+
+#[test]
+fn test_benchmarks() {
+    for (name, benchmark) in BENCHMARKS {
+        // `begin` creates its continuation, which the harness does not support
+        if *name == "begin" {
+            continue;
+        }
+        let mut f = Fixture::new(Pubkey::new_unique());
+        for instruction in benchmark(&mut f) {
+            f.bank
+                .process_instruction(instruction)
+                .unwrap_or_else(|err| panic!("benchmark {} failed: {}", name, err));
+        }
+    }
+}
+
+#[test]
+fn test_baseline() {
+    let baseline = parse_baseline(include_str!("compute_units.txt"));
+    for name in baseline.keys() {
+        assert!(
+            BENCHMARKS.iter().any(|(benchmark, _)| benchmark == name),
+            "unknown benchmark {} in the baseline",
+            name
+        );
+    }
+    assert_eq!(parse_baseline(&format_baseline(&baseline)), baseline);
+
+    let measured: BTreeMap<String, u64> = [("ss_swap", 100), ("end", 50), ("begin_v2", 10)]
+        .into_iter()
+        .map(|(name, units)| (name.to_string(), units))
+        .collect();
+    let baseline: BTreeMap<String, u64> = [("ss_swap", 100), ("end", 49)]
+        .into_iter()
+        .map(|(name, units)| (name.to_string(), units))
+        .collect();
+    assert_eq!(
+        check_baseline(&baseline, &measured),
+        vec![
+            "begin_v2: consumed 10, missing from the baseline".to_string(),
+            "end: consumed 50, above the baseline of 49".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_savings() {
+    // the README publishes the savings of the baseline
+    let readme = include_str!("../README.md");
+    let baseline = parse_baseline(include_str!("compute_units.txt"));
+    assert_eq!(
+        update_savings(readme, &format_savings(&baseline)),
+        readme,
+        "the compute savings in the README do not match the baseline"
+    );
+
+    let pairs = layout_pairs();
+    assert!(pairs.contains(&("end_legacy", "end")));
+    for (legacy, zero_copy) in pairs {
+        assert!(BENCHMARKS.iter().any(|(name, _)| *name == legacy));
+        assert!(BENCHMARKS.iter().any(|(name, _)| *name == zero_copy));
+    }
+
+    let units: BTreeMap<String, u64> = [("end_legacy", 7_000), ("end", 5_500), ("ss_swap", 1)]
+        .into_iter()
+        .map(|(name, units)| (name.to_string(), units))
+        .collect();
+    assert_eq!(
+        format_savings(&units),
+        "| Benchmarks | `Continuation` | `ContinuationV2` | Saved |\n\
+         | --- | ---: | ---: | ---: |\n\
+         | `end_legacy` / `end` | 7000 | 5500 | 1500 |\n"
+    );
+}
+
+#[cfg(feature = "test-bpf")]
+mod bpf {
+    use super::*;
+    use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext};
+    use solana_sdk::account::Account;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::{Transaction, TransactionError};
+
+    /// Path of the baseline, relative to the crate.
+    const BASELINE_PATH: &str = "tests/compute_units.txt";
+
+    /// Path of the README, which publishes the savings of the zero-copy layout.
+    const README_PATH: &str = "README.md";
+
+    /// Starts a bank with the BPF programs and the accounts of a [Fixture].
+    async fn start(f: &Fixture) -> ProgramTestContext {
+        let mut program_test = ProgramTest::default();
+        program_test.prefer_bpf(true);
+        program_test.add_program("continuation_router", continuation_router::ID, None);
+        program_test.add_program("mock_swap", mock_swap::ID, None);
+        for (key, account) in f.bank.snapshot() {
+            // programs are loaded by the bank, which also provides the sysvars
+            if account.executable || account.owner == sysvar::ID {
+                continue;
+            }
+            program_test.add_account(
+                key,
+                Account {
+                    lamports: account.lamports,
+                    data: account.data,
+                    owner: account.owner,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+        program_test.start_with_context().await
+    }
+
+    fn transaction(
+        context: &ProgramTestContext,
+        owner: &Keypair,
+        instructions: &[Instruction],
+    ) -> Transaction {
+        Transaction::new_signed_with_payer(
+            instructions,
+            Some(&context.payer.pubkey()),
+            &[&context.payer, owner],
+            context.last_blockhash,
+        )
+    }
+
+    /// Executes an instruction, returning the compute units it consumed.
+    ///
+    /// Banks only report the units consumed by failed simulations, so the instruction is
+    /// first simulated followed by a transfer which always fails.
+    async fn measure(
+        context: &mut ProgramTestContext,
+        owner: &Keypair,
+        instruction: Instruction,
+    ) -> u64 {
+        let failing_transfer =
+            system_instruction::transfer(&context.payer.pubkey(), &owner.pubkey(), u64::MAX);
+        let simulated = transaction(context, owner, &[instruction.clone(), failing_transfer]);
+        let units = match context
+            .banks_client
+            .process_transaction_with_preflight(simulated)
+            .await
+        {
+            Err(BanksClientError::SimulationError {
+                err: TransactionError::InstructionError(1, _),
+                units_consumed,
+                ..
+            }) => units_consumed,
+            result => panic!("unexpected simulation result: {:?}", result),
+        };
+        let tx = transaction(context, owner, &[instruction]);
+        context.banks_client.process_transaction(tx).await.unwrap();
+        units
+    }
+
+    #[tokio::test]
+    async fn test_compute_units() {
+        let mut measured = BTreeMap::new();
+        for (name, benchmark) in BENCHMARKS {
+            let owner = Keypair::new();
+            let mut f = Fixture::new(owner.pubkey());
+            let mut instructions = benchmark(&mut f);
+            let measured_instruction = instructions.pop().unwrap();
+
+            let mut context = start(&f).await;
+            for instruction in instructions {
+                let tx = transaction(&context, &owner, &[instruction]);
+                context.banks_client.process_transaction(tx).await.unwrap();
+            }
+            let units = measure(&mut context, &owner, measured_instruction).await;
+            println!("{:<24}{}", name, units);
+            measured.insert(name.to_string(), units);
+        }
+
+        println!("{}", format_savings(&measured));
+
+        if std::env::var_os("UPDATE_COMPUTE_UNITS").is_some() {
+            std::fs::write(BASELINE_PATH, format_baseline(&measured)).unwrap();
+            let readme = std::fs::read_to_string(README_PATH).unwrap();
+            std::fs::write(
+                README_PATH,
+                update_savings(&readme, &format_savings(&measured)),
+            )
+            .unwrap();
+            return;
+        }
+        let failures = check_baseline(
+            &parse_baseline(include_str!("compute_units.txt")),
+            &measured,
+        );
+        assert!(
+            failures.is_empty(),
+            "compute units do not match the baseline:\n{}",
+            failures.join("\n")
+        );
+    }
+}
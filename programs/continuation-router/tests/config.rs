@@ -0,0 +1,168 @@
+//! Creation and administration of the [continuation_router::RouterConfig].
+
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::InstructionData;
+use continuation_router::{accounts, instruction, ErrorCode, RouterConfig};
+use harness::{TestAccount, TestBank};
+
+/// The router error as returned by an instruction.
+fn router_error(code: ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+/// The Anchor framework error as returned by an instruction.
+fn anchor_error(code: anchor_lang::error::ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+fn config_key() -> Pubkey {
+    Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID).0
+}
+
+fn program_data_key() -> Pubkey {
+    Pubkey::find_program_address(
+        &[continuation_router::ID.as_ref()],
+        &bpf_loader_upgradeable::ID,
+    )
+    .0
+}
+
+/// Creates a program data account of the upgradeable loader.
+fn set_program_data(bank: &mut TestBank, key: Pubkey, upgrade_authority: Option<Pubkey>) {
+    // bincode encoding of `UpgradeableLoaderState::ProgramData`
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend(0u64.to_le_bytes());
+    match upgrade_authority {
+        Some(authority) => {
+            data.push(1);
+            data.extend(authority.as_ref());
+        }
+        None => data.push(0),
+    }
+    bank.set_account(
+        key,
+        TestAccount {
+            lamports: 1_000_000_000,
+            data,
+            owner: bpf_loader_upgradeable::ID,
+            executable: false,
+        },
+    );
+}
+
+fn initialize_config(admin: Pubkey, program_data: Pubkey) -> Instruction {
+    harness::instruction(
+        continuation_router::ID,
+        accounts::InitializeConfig {
+            config: config_key(),
+            admin,
+            program_data,
+            payer: admin,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::InitializeConfig {},
+    )
+}
+
+fn update_config(admin: Pubkey, data: impl InstructionData) -> Instruction {
+    harness::instruction(
+        continuation_router::ID,
+        accounts::UpdateConfig {
+            config: config_key(),
+            admin,
+        },
+        data,
+    )
+}
+
+fn config(bank: &TestBank) -> RouterConfig {
+    let account = bank.account(&config_key()).unwrap();
+    RouterConfig::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[test]
+fn test_initialize_config() {
+    let mut bank = TestBank::new();
+    let authority = bank.create_wallet(1_000_000_000);
+    let attacker = bank.create_wallet(1_000_000_000);
+    set_program_data(&mut bank, program_data_key(), Some(authority));
+
+    // only the upgrade authority may create the config
+    assert_eq!(
+        bank.process_instruction(initialize_config(attacker, program_data_key())),
+        Err(router_error(ErrorCode::NotUpgradeAuthority))
+    );
+    // the program data must be that of the router
+    let other_program_data = Pubkey::new_unique();
+    set_program_data(&mut bank, other_program_data, Some(attacker));
+    assert_eq!(
+        bank.process_instruction(initialize_config(attacker, other_program_data)),
+        Err(anchor_error(anchor_lang::error::ErrorCode::ConstraintSeeds))
+    );
+
+    bank.process_instruction(initialize_config(authority, program_data_key()))
+        .unwrap();
+    let config = config(&bank);
+    assert_eq!(config.admin, authority);
+    assert!(config.approved_programs.is_empty());
+}
+
+#[test]
+fn test_initialize_config_immutable() {
+    let mut bank = TestBank::new();
+    let authority = bank.create_wallet(1_000_000_000);
+    set_program_data(&mut bank, program_data_key(), None);
+    assert_eq!(
+        bank.process_instruction(initialize_config(authority, program_data_key())),
+        Err(router_error(ErrorCode::NotUpgradeAuthority))
+    );
+}
+
+#[test]
+fn test_set_admin() {
+    let mut bank = TestBank::new();
+    let authority = bank.create_wallet(1_000_000_000);
+    let new_admin = bank.create_wallet(1_000_000_000);
+    set_program_data(&mut bank, program_data_key(), Some(authority));
+    bank.process_instruction(initialize_config(authority, program_data_key()))
+        .unwrap();
+
+    // only the admin may hand over the config
+    assert_eq!(
+        bank.process_instruction(update_config(
+            new_admin,
+            instruction::SetAdmin { new_admin }
+        )),
+        Err(anchor_error(
+            anchor_lang::error::ErrorCode::ConstraintHasOne
+        ))
+    );
+    bank.process_instruction(update_config(
+        authority,
+        instruction::SetAdmin { new_admin },
+    ))
+    .unwrap();
+    assert_eq!(config(&bank).admin, new_admin);
+
+    // the previous admin can no longer update the config
+    let program = Pubkey::new_unique();
+    assert_eq!(
+        bank.process_instruction(update_config(
+            authority,
+            instruction::AddApprovedProgram { program }
+        )),
+        Err(anchor_error(
+            anchor_lang::error::ErrorCode::ConstraintHasOne
+        ))
+    );
+    bank.process_instruction(update_config(
+        new_admin,
+        instruction::AddApprovedProgram { program },
+    ))
+    .unwrap();
+    assert_eq!(config(&bank).approved_programs, vec![program]);
+}
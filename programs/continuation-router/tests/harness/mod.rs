@@ -0,0 +1,820 @@
+//! In-process execution of router instructions.
+//!
+//! Programs are executed natively by calling their entrypoint functions, which is fast
+//! enough for property-based tests and does not require the BPF programs to be built.
+//! `solana-program-test` is used instead where a full bank is needed, such as for
+//! measuring compute units.
+//! The syscalls they rely on (sysvars, cross-program invocations and return data)
+//! are provided by [SyscallStubs] backed by thread-local state, which keeps
+//! tests running on different threads isolated from each other.
+
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{ProgramResult, SUCCESS};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+use anchor_lang::solana_program::program_utils::limited_deserialize;
+use anchor_lang::solana_program::system_instruction::SystemInstruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_lang::{AccountSerialize, InstructionData};
+use anchor_spl::associated_token::get_associated_token_address;
+use continuation_router::{Continuation, ContinuationV2, RouterConfig};
+use mock_swap::{PoolConfig, PoolParams};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{ExtensionType, StateWithExtensions, StateWithExtensionsMut};
+use stable_swap_client::state::{SwapInfo, SwapTokenInfo};
+
+/// Entrypoint of a program.
+pub type ProcessInstruction = fn(&Pubkey, &[AccountInfo], &[u8]) -> ProgramResult;
+
+/// State of the runtime shared by all programs invoked on the current thread.
+#[derive(Default)]
+struct Runtime {
+    programs: HashMap<Pubkey, ProcessInstruction>,
+    clock: Clock,
+    call_stack: Vec<Pubkey>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+}
+
+thread_local! {
+    static RUNTIME: RefCell<Runtime> = RefCell::new(Runtime::default());
+}
+
+static INSTALL_STUBS: Once = Once::new();
+
+/// The program currently being executed.
+fn current_program() -> Pubkey {
+    RUNTIME.with(|rt| rt.borrow().call_stack.last().copied().unwrap_or_default())
+}
+
+/// Calls the entrypoint of `program_id`.
+fn invoke(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let process = RUNTIME
+        .with(|rt| rt.borrow().programs.get(program_id).copied())
+        .ok_or(ProgramError::IncorrectProgramId)?;
+    RUNTIME.with(|rt| rt.borrow_mut().call_stack.push(*program_id));
+    let result = process(program_id, accounts, data);
+    RUNTIME.with(|rt| rt.borrow_mut().call_stack.pop());
+    result
+}
+
+/// The instructions of the system program used to create accounts.
+///
+/// The data of allocated accounts is leaked, since the slices of [AccountInfo]s cannot grow.
+fn process_system_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| {
+        if !from.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        **from.try_borrow_mut_lamports()? = from
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        **to.try_borrow_mut_lamports()? += lamports;
+        Ok(())
+    };
+    let allocate = |account: &AccountInfo, space: u64| {
+        if !account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !account.data_is_empty() || *account.owner != system_program::ID {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        *account.try_borrow_mut_data()? = Box::leak(vec![0; space as usize].into_boxed_slice());
+        Ok(())
+    };
+    let assign = |account: &AccountInfo, owner: &Pubkey| {
+        if !account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        account.assign(owner);
+        Ok(())
+    };
+    match limited_deserialize(data, 1_024).map_err(|_| ProgramError::InvalidInstructionData)? {
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            if accounts[1].lamports() > 0 {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            transfer(&accounts[0], &accounts[1], lamports)?;
+            allocate(&accounts[1], space)?;
+            assign(&accounts[1], &owner)
+        }
+        SystemInstruction::Transfer { lamports } => transfer(&accounts[0], &accounts[1], lamports),
+        SystemInstruction::Allocate { space } => allocate(&accounts[0], space),
+        SystemInstruction::Assign { owner } => assign(&accounts[0], &owner),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = current_program();
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let signed_by_caller = signers_seeds
+                .iter()
+                .any(|seeds| Pubkey::create_program_address(seeds, &caller) == Ok(meta.pubkey));
+            // callers may not escalate the privileges of the accounts they pass
+            if meta.is_signer && !info.is_signer && !signed_by_caller {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let mut info = info.clone();
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            accounts.push(info);
+        }
+        invoke(&instruction.program_id, &accounts, &instruction.data)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = RUNTIME.with(|rt| rt.borrow().clock.clone());
+        unsafe { std::ptr::write(var_addr as *mut Clock, clock) };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { std::ptr::write(var_addr as *mut Rent, Rent::default()) };
+        SUCCESS
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RUNTIME.with(|rt| rt.borrow().return_data.clone())
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let program_id = current_program();
+        RUNTIME.with(|rt| rt.borrow_mut().return_data = Some((program_id, data.to_vec())));
+    }
+}
+
+/// An account stored in a [TestBank].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+/// Accounts and programs which instructions are executed against.
+///
+/// Only one bank should be used per thread at a time, since the registered programs,
+/// the clock and the return data are stored per thread.
+pub struct TestBank {
+    accounts: HashMap<Pubkey, TestAccount>,
+}
+
+impl TestBank {
+    /// Creates a bank with the system and token programs, the router and the mock swap program.
+    ///
+    /// Like on every cluster, the native mint exists from the start.
+    pub fn new() -> Self {
+        INSTALL_STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Stubs));
+        });
+        RUNTIME.with(|rt| *rt.borrow_mut() = Runtime::default());
+
+        let mut bank = TestBank {
+            accounts: HashMap::new(),
+        };
+        bank.add_program(system_program::ID, process_system_instruction);
+        bank.add_program(spl_token::ID, spl_token::processor::Processor::process);
+        bank.add_program(
+            spl_token_2022::ID,
+            spl_token_2022::processor::Processor::process,
+        );
+        bank.add_program(continuation_router::ID, continuation_router::entry);
+        bank.add_program(mock_swap::ID, mock_swap::entry);
+        bank.write_clock();
+        bank.write_rent();
+        bank.set_mint(
+            spl_token::native_mint::ID,
+            COption::None,
+            0,
+            spl_token::native_mint::DECIMALS,
+        );
+        bank
+    }
+
+    /// Registers a program, which may then be invoked directly or via CPI.
+    pub fn add_program(&mut self, program_id: Pubkey, process: ProcessInstruction) {
+        RUNTIME.with(|rt| rt.borrow_mut().programs.insert(program_id, process));
+        self.set_account(
+            program_id,
+            TestAccount {
+                lamports: 1,
+                executable: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Sets the unix timestamp of the clock sysvar.
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        RUNTIME.with(|rt| rt.borrow_mut().clock.unix_timestamp = unix_timestamp);
+        self.write_clock();
+    }
+
+    /// Writes the clock to its sysvar account, which is read by some instructions.
+    fn write_clock(&mut self) {
+        let mut account = TestAccount {
+            lamports: 1,
+            data: vec![0; Clock::size_of()],
+            owner: sysvar::ID,
+            executable: false,
+        };
+        let clock = RUNTIME.with(|rt| rt.borrow().clock.clone());
+        let mut info = AccountInfo::new(
+            &sysvar::clock::ID,
+            false,
+            true,
+            &mut account.lamports,
+            &mut account.data,
+            &account.owner,
+            false,
+            0,
+        );
+        clock.to_account_info(&mut info).unwrap();
+        self.set_account(sysvar::clock::ID, account);
+    }
+
+    /// Writes the default rent to its sysvar account, which is read by initializers.
+    fn write_rent(&mut self) {
+        let mut account = TestAccount {
+            lamports: 1,
+            data: vec![0; Rent::size_of()],
+            owner: sysvar::ID,
+            executable: false,
+        };
+        let mut info = AccountInfo::new(
+            &sysvar::rent::ID,
+            false,
+            true,
+            &mut account.lamports,
+            &mut account.data,
+            &account.owner,
+            false,
+            0,
+        );
+        Rent::default().to_account_info(&mut info).unwrap();
+        self.set_account(sysvar::rent::ID, account);
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&TestAccount> {
+        self.accounts.get(key)
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, account: TestAccount) {
+        self.accounts.insert(key, account);
+    }
+
+    /// A copy of all accounts, used to check that failed transactions have no effect.
+    pub fn snapshot(&self) -> HashMap<Pubkey, TestAccount> {
+        self.accounts.clone()
+    }
+
+    /// The return data of the last instruction which set it.
+    pub fn return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RUNTIME.with(|rt| rt.borrow().return_data.clone())
+    }
+
+    /// Executes an instruction as its own transaction.
+    pub fn process_instruction(&mut self, instruction: Instruction) -> ProgramResult {
+        self.process_transaction(&[instruction])
+    }
+
+    /// Executes instructions atomically: if any of them fails, no account is modified.
+    pub fn process_transaction(&mut self, instructions: &[Instruction]) -> ProgramResult {
+        let snapshot = self.snapshot();
+        RUNTIME.with(|rt| rt.borrow_mut().return_data = None);
+        for instruction in instructions {
+            if let Err(err) = self.execute(instruction) {
+                self.accounts = snapshot;
+                return Err(err);
+            }
+        }
+        // like the runtime, accounts without lamports are garbage collected
+        // at the end of the transaction
+        self.accounts.retain(|_, account| account.lamports != 0);
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> ProgramResult {
+        let mut keys: Vec<Pubkey> = vec![];
+        for meta in &instruction.accounts {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let mut accounts: Vec<TestAccount> = keys
+            .iter()
+            .map(|key| self.accounts.get(key).cloned().unwrap_or_default())
+            .collect();
+        let original = accounts.clone();
+
+        // like the runtime, an account has the union of the privileges of all its metas
+        let is_signer = |key: &Pubkey| {
+            instruction
+                .accounts
+                .iter()
+                .any(|meta| meta.pubkey == *key && meta.is_signer)
+        };
+        let is_writable = |key: &Pubkey| {
+            instruction
+                .accounts
+                .iter()
+                .any(|meta| meta.pubkey == *key && meta.is_writable)
+        };
+        let (result, data) = {
+            let infos: Vec<AccountInfo> = keys
+                .iter()
+                .zip(accounts.iter_mut())
+                .map(|(key, account)| {
+                    AccountInfo::new(
+                        key,
+                        is_signer(key),
+                        is_writable(key),
+                        &mut account.lamports,
+                        &mut account.data,
+                        &account.owner,
+                        account.executable,
+                        0,
+                    )
+                })
+                .collect();
+            // duplicate metas share the same account
+            let account_infos: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| infos[keys.iter().position(|key| *key == meta.pubkey).unwrap()].clone())
+                .collect();
+            let result = invoke(&instruction.program_id, &account_infos, &instruction.data);
+            // the system program replaces the data of the accounts it allocates
+            let data: Vec<Vec<u8>> = infos
+                .iter()
+                .map(|info| info.data.borrow().to_vec())
+                .collect();
+            (result, data)
+        };
+        result?;
+        for (account, data) in accounts.iter_mut().zip(data) {
+            account.data = data;
+        }
+
+        for ((key, account), original) in keys.into_iter().zip(accounts).zip(original) {
+            assert!(
+                is_writable(&key) || account == original,
+                "read-only account {} was modified",
+                key
+            );
+            self.accounts.insert(key, account);
+        }
+        Ok(())
+    }
+
+    /// Creates an account of a system-owned wallet.
+    pub fn create_wallet(&mut self, lamports: u64) -> Pubkey {
+        let wallet = Pubkey::new_unique();
+        self.set_account(
+            wallet,
+            TestAccount {
+                lamports,
+                ..Default::default()
+            },
+        );
+        wallet
+    }
+
+    /// Creates a mint without a mint authority.
+    pub fn create_mint(&mut self) -> Pubkey {
+        self.add_mint(COption::None, 0)
+    }
+
+    fn add_mint(&mut self, mint_authority: COption<Pubkey>, supply: u64) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        self.set_mint(mint, mint_authority, supply, 6);
+        mint
+    }
+
+    fn set_mint(
+        &mut self,
+        mint: Pubkey,
+        mint_authority: COption<Pubkey>,
+        supply: u64,
+        decimals: u8,
+    ) {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            mint_authority,
+            supply,
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(
+            mint,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(Mint::LEN),
+                data,
+                owner: spl_token::ID,
+                executable: false,
+            },
+        );
+    }
+
+    /// Creates a Token-2022 mint whose authority is also its freeze authority,
+    /// with a transfer fee of `transfer_fee_bps` if non-zero.
+    pub fn create_token_2022_mint(
+        &mut self,
+        mint_authority: COption<Pubkey>,
+        decimals: u8,
+        transfer_fee_bps: u16,
+    ) -> Pubkey {
+        let extensions: &[ExtensionType] = if transfer_fee_bps == 0 {
+            &[]
+        } else {
+            &[ExtensionType::TransferFeeConfig]
+        };
+        let len = ExtensionType::get_account_len::<spl_token_2022::state::Mint>(extensions);
+        let mut data = vec![0; len];
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                .unwrap();
+        if transfer_fee_bps != 0 {
+            let transfer_fee = TransferFee {
+                epoch: 0.into(),
+                maximum_fee: u64::MAX.into(),
+                transfer_fee_basis_points: transfer_fee_bps.into(),
+            };
+            let config = state.init_extension::<TransferFeeConfig>().unwrap();
+            config.older_transfer_fee = transfer_fee;
+            config.newer_transfer_fee = transfer_fee;
+        }
+        state.base = spl_token_2022::state::Mint {
+            mint_authority,
+            decimals,
+            is_initialized: true,
+            freeze_authority: mint_authority,
+            ..Default::default()
+        };
+        state.pack_base();
+        if transfer_fee_bps != 0 {
+            state.init_account_type().unwrap();
+        }
+
+        let mint = Pubkey::new_unique();
+        self.set_account(
+            mint,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(len),
+                data,
+                owner: spl_token_2022::ID,
+                executable: false,
+            },
+        );
+        mint
+    }
+
+    /// Creates a token account holding `amount` tokens.
+    pub fn create_token_account(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let account = Pubkey::new_unique();
+        self.set_token_account(account, mint, owner, amount);
+        account
+    }
+
+    /// Creates the associated token account of `owner` holding `amount` tokens.
+    pub fn create_associated_token_account(
+        &mut self,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let account = get_associated_token_address(&owner, &mint);
+        self.set_token_account(account, mint, owner, amount);
+        account
+    }
+
+    /// Token accounts belong to the program of their mint. Accounts of
+    /// Token-2022 mints with a transfer fee carry the withheld fee extension.
+    /// Accounts of the native mint hold their amount in lamports above the rent.
+    fn set_token_account(&mut self, account: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) {
+        let mint_account = &self.accounts[&mint];
+        let token_program = mint_account.owner;
+        let data = if token_program == spl_token_2022::ID {
+            let mint_state =
+                StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_account.data)
+                    .unwrap();
+            let extensions = ExtensionType::get_required_init_account_extensions(
+                &mint_state.get_extension_types().unwrap(),
+            );
+            let len = ExtensionType::get_account_len::<spl_token_2022::state::Account>(&extensions);
+            let mut data = vec![0; len];
+            if !extensions.is_empty() {
+                let mut state =
+                    StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(
+                        &mut data,
+                    )
+                    .unwrap();
+                state.init_extension::<TransferFeeAmount>().unwrap();
+                state.init_account_type().unwrap();
+            }
+            data
+        } else {
+            vec![0; TokenAccount::LEN]
+        };
+        let rent = Rent::default().minimum_balance(data.len());
+        let is_native = mint == spl_token::native_mint::ID;
+        self.set_account(
+            account,
+            TestAccount {
+                lamports: if is_native { rent + amount } else { rent },
+                data,
+                owner: token_program,
+                executable: false,
+            },
+        );
+        self.update_token_account(account, |token_account| {
+            *token_account = TokenAccount {
+                mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                is_native: if is_native {
+                    COption::Some(rent)
+                } else {
+                    COption::None
+                },
+                ..Default::default()
+            }
+        });
+    }
+
+    pub fn token_account(&self, key: &Pubkey) -> TokenAccount {
+        TokenAccount::unpack_unchecked(&self.accounts[key].data[..TokenAccount::LEN]).unwrap()
+    }
+
+    /// Modifies a token account in place, e.g. to simulate changes by other transactions.
+    pub fn update_token_account(&mut self, key: Pubkey, f: impl FnOnce(&mut TokenAccount)) {
+        let mut token_account = self.token_account(&key);
+        f(&mut token_account);
+        let account = self.accounts.get_mut(&key).unwrap();
+        token_account.pack_into_slice(&mut account.data[..TokenAccount::LEN]);
+    }
+
+    /// Creates the [RouterConfig] with the given approved programs.
+    pub fn init_router(&mut self, admin: Pubkey, approved_programs: Vec<Pubkey>) -> Pubkey {
+        let (config, bump) =
+            Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID);
+        let mut data = vec![];
+        RouterConfig {
+            bump,
+            admin,
+            approved_programs,
+            adapters: vec![],
+        }
+        .try_serialize(&mut data)
+        .unwrap();
+        data.resize(8 + RouterConfig::LEN, 0);
+        self.set_account(
+            config,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: continuation_router::ID,
+                executable: false,
+            },
+        );
+        config
+    }
+
+    /// Creates an uninitialized continuation account, as done before `begin_v2`.
+    pub fn create_continuation(&mut self) -> Pubkey {
+        let continuation = Pubkey::new_unique();
+        let size = 8 + ContinuationV2::LEN;
+        self.set_account(
+            continuation,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(size),
+                data: vec![0; size],
+                owner: continuation_router::ID,
+                executable: false,
+            },
+        );
+        continuation
+    }
+
+    /// Reads a continuation account of either layout.
+    pub fn continuation(&self, key: &Pubkey) -> ContinuationV2 {
+        continuation_router::state::try_deserialize(&self.accounts[key].data).unwrap()
+    }
+
+    /// Modifies a continuation account of the zero-copy layout in place.
+    pub fn update_continuation(&mut self, key: &Pubkey, f: impl FnOnce(&mut ContinuationV2)) {
+        let mut continuation = self.continuation(key);
+        f(&mut continuation);
+        let bytes = anchor_lang::__private::bytemuck::bytes_of(&continuation);
+        let account = self.accounts.get_mut(key).unwrap();
+        account.data[8..8 + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Rewrites a continuation account in the legacy Borsh layout, as if its route
+    /// had been started by `begin`.
+    pub fn convert_to_legacy_continuation(&mut self, key: &Pubkey) {
+        let mut data = vec![];
+        Continuation::from(&self.continuation(key))
+            .try_serialize(&mut data)
+            .unwrap();
+        data.resize(8 + Continuation::LEN, 0);
+        let account = self.accounts.get_mut(key).unwrap();
+        account.lamports = Rent::default().minimum_balance(data.len());
+        account.data = data;
+    }
+
+    /// Creates a pool of the mock swap program between `mints`, holding `reserves`.
+    ///
+    /// The state is written directly since `initialize` creates an account, which the
+    /// bank does not support. LP tokens are minted for the value of the reserves.
+    pub fn create_pool(
+        &mut self,
+        mints: [Pubkey; 2],
+        reserves: [u64; 2],
+        params: PoolParams,
+    ) -> Pool {
+        let admin = self.create_wallet(1_000_000_000);
+        let swap = Pubkey::new_unique();
+        let (config, bump) = Pubkey::find_program_address(&[swap.as_ref()], &mock_swap::ID);
+        let pool_config = PoolConfig {
+            swap,
+            admin,
+            bump,
+            rate_bps: params.rate_bps,
+            mode: params.mode,
+        };
+        let supply = reserves[0] + pool_config.convert(reserves[1], false).unwrap();
+        let pool = Pool {
+            swap,
+            config,
+            admin,
+            mints,
+            reserves: [
+                self.create_token_account(mints[0], config, reserves[0]),
+                self.create_token_account(mints[1], config, reserves[1]),
+            ],
+            admin_fees: [
+                self.create_token_account(mints[0], admin, 0),
+                self.create_token_account(mints[1], admin, 0),
+            ],
+            pool_mint: self.add_mint(COption::Some(config), supply),
+        };
+
+        let mut data = vec![];
+        pool_config.try_serialize(&mut data).unwrap();
+        data.resize(8 + PoolConfig::LEN, 0);
+        self.set_account(
+            config,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: mock_swap::ID,
+                executable: false,
+            },
+        );
+
+        let token = |index: usize| SwapTokenInfo {
+            reserves: pool.reserves[index],
+            mint: mints[index],
+            admin_fees: pool.admin_fees[index],
+            index: index as u8,
+        };
+        let mut data = vec![0; SwapInfo::LEN];
+        SwapInfo {
+            is_initialized: true,
+            is_paused: false,
+            nonce: bump,
+            initial_amp_factor: 1,
+            target_amp_factor: 1,
+            start_ramp_ts: 0,
+            stop_ramp_ts: 0,
+            future_admin_deadline: 0,
+            future_admin_key: Pubkey::default(),
+            admin_key: admin,
+            token_a: token(0),
+            token_b: token(1),
+            pool_mint: pool.pool_mint,
+            fees: params.fees(),
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(
+            swap,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: mock_swap::ID,
+                executable: false,
+            },
+        );
+        pool
+    }
+
+    /// Updates the params of a pool with `set_params`.
+    pub fn set_pool_params(&mut self, pool: &Pool, params: PoolParams) {
+        self.process_instruction(instruction(
+            mock_swap::ID,
+            mock_swap::accounts::SetParams {
+                swap: pool.swap,
+                config: pool.config,
+                admin: pool.admin,
+            },
+            mock_swap::instruction::SetParams { params },
+        ))
+        .unwrap();
+    }
+}
+
+/// Builds an instruction from Anchor accounts and instruction data.
+pub fn instruction(
+    program_id: Pubkey,
+    accounts: impl ToAccountMetas,
+    data: impl InstructionData,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Accounts of a pool of the mock swap program.
+#[derive(Clone, Debug)]
+pub struct Pool {
+    pub swap: Pubkey,
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub mints: [Pubkey; 2],
+    pub reserves: [Pubkey; 2],
+    pub admin_fees: [Pubkey; 2],
+    pub pool_mint: Pubkey,
+}
+
+impl Pool {
+    /// Accounts of a StableSwap swap from token `from` of the pool to the other token.
+    pub fn swap_accounts(
+        &self,
+        user_authority: Pubkey,
+        from: usize,
+        source: Pubkey,
+        destination: Pubkey,
+    ) -> Vec<AccountMeta> {
+        let to = 1 - from;
+        stable_swap_client::instruction::swap(
+            &spl_token::ID,
+            &self.swap,
+            &self.config,
+            &user_authority,
+            &source,
+            &self.reserves[from],
+            &self.reserves[to],
+            &destination,
+            &self.admin_fees[to],
+            0,
+            0,
+        )
+        .unwrap()
+        .accounts
+    }
+}
@@ -0,0 +1,1575 @@
+//! Invariants of [continuation_router::processor::Processor::process] and
+//! [continuation_router::continuation_router::end] under random routes, including
+//! routes driven by the delegate of the input account.
+
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::{system_instruction, system_program};
+use continuation_router::{
+    accounts, instruction, ActionType, ErrorCode, Leg, RouteResult, RouteStep, TokenAmount,
+    PAYER_ABORT_DELAY_SECONDS,
+};
+use harness::{Pool, TestBank};
+use mock_swap::{Mode, PoolParams};
+use proptest::prelude::*;
+
+/// The router error as returned by an instruction.
+fn router_error(code: ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+/// The Anchor framework error as returned by an instruction.
+fn anchor_error(code: anchor_lang::error::ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+/// The mock swap error as returned by an instruction.
+fn mock_swap_error(code: mock_swap::ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+fn token_amount(mint: Pubkey, amount: u64) -> TokenAmount {
+    TokenAmount { mint, amount }
+}
+
+/// Params of a pool without fees.
+fn pool_params(rate_bps: u64, mode: Mode) -> PoolParams {
+    PoolParams {
+        rate_bps,
+        fee_bps: 0,
+        admin_fee_bps: 0,
+        mode,
+    }
+}
+
+/// Reserves of each token of the pools, which cover any output of a route.
+const RESERVES: u64 = 1 << 62;
+
+/// A route through the token accounts of the owner,
+/// with one step through a pool between each pair of adjacent accounts.
+struct Fixture {
+    bank: TestBank,
+    owner: Pubkey,
+    attacker: Pubkey,
+    continuation: Pubkey,
+    mints: Vec<Pubkey>,
+    accounts: Vec<Pubkey>,
+    pools: Vec<Pool>,
+}
+
+/// Accounts of an action instruction, which tests may substitute.
+struct Step {
+    deposit: bool,
+    continuation: Pubkey,
+    swap_program: Pubkey,
+    owner: Pubkey,
+    owner_signs: bool,
+    input: Pubkey,
+    output: Pubkey,
+    pool: Pool,
+}
+
+impl Step {
+    fn instruction(&self) -> Instruction {
+        let continuation = accounts::ContinuationAccounts {
+            continuation: self.continuation,
+            token_program: anchor_spl::token::ID,
+            swap_program: self.swap_program,
+            config: Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID).0,
+            owner: self.owner,
+        };
+        let mut instruction = if self.deposit {
+            harness::instruction(
+                continuation_router::ID,
+                accounts::ADDepositAccounts {
+                    continuation,
+                    action: accounts::ADDeposit {
+                        input: self.input,
+                        output: self.output,
+                    },
+                },
+                instruction::AdDeposit {},
+            )
+        } else {
+            harness::instruction(
+                continuation_router::ID,
+                accounts::ADWithdrawAccounts {
+                    continuation,
+                    action: accounts::ADWithdraw {
+                        input: self.input,
+                        output: self.output,
+                    },
+                },
+                instruction::AdWithdraw {},
+            )
+        };
+        // the accounts passed to the swap program
+        instruction.accounts.extend(self.pool.swap_accounts(
+            self.owner,
+            0,
+            self.input,
+            self.output,
+        ));
+        for meta in instruction.accounts.iter_mut() {
+            if meta.pubkey == self.owner {
+                meta.is_signer = self.owner_signs;
+            }
+        }
+        instruction
+    }
+
+    fn action_type(&self) -> ActionType {
+        if self.deposit {
+            ActionType::ADDeposit
+        } else {
+            ActionType::ADWithdraw
+        }
+    }
+
+    /// The accounts of the step in a [continuation_router::continuation_router::route]:
+    /// the swap program, then the accounts of the action and those passed to the swap program.
+    fn route_accounts(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![AccountMeta::new_readonly(self.swap_program, false)];
+        // deposits and withdrawals take the same accounts
+        accounts.extend(
+            accounts::ADDeposit {
+                input: self.input,
+                output: self.output,
+            }
+            .to_account_metas(None),
+        );
+        accounts.extend(
+            self.pool
+                .swap_accounts(self.owner, 0, self.input, self.output),
+        );
+        accounts
+    }
+}
+
+impl Fixture {
+    /// Creates the accounts of a route with `balances.len() - 1` steps.
+    ///
+    /// `balances` are the initial balances of the token accounts of the route.
+    fn new(balances: &[u64]) -> Self {
+        Self::with_mints(balances, |bank, _| bank.create_mint())
+    }
+
+    /// Like [Fixture::new], with the mint of each token account given by `mint`.
+    fn with_mints(balances: &[u64], mint: impl Fn(&mut TestBank, usize) -> Pubkey) -> Self {
+        let mut bank = TestBank::new();
+        let owner = bank.create_wallet(1_000_000_000);
+        let attacker = bank.create_wallet(1_000_000_000);
+        bank.init_router(Pubkey::new_unique(), vec![mock_swap::ID]);
+        let continuation = bank.create_continuation();
+        let mints: Vec<Pubkey> = (0..balances.len())
+            .map(|index| mint(&mut bank, index))
+            .collect();
+        let accounts = mints
+            .iter()
+            .zip(balances)
+            .map(|(mint, balance)| bank.create_token_account(*mint, owner, *balance))
+            .collect();
+        let pools = mints
+            .windows(2)
+            .map(|pair| {
+                bank.create_pool(
+                    [pair[0], pair[1]],
+                    [RESERVES, RESERVES],
+                    pool_params(10_000, Mode::Normal),
+                )
+            })
+            .collect();
+        Fixture {
+            bank,
+            owner,
+            attacker,
+            continuation,
+            mints,
+            accounts,
+            pools,
+        }
+    }
+
+    fn num_steps(&self) -> usize {
+        self.accounts.len() - 1
+    }
+
+    fn begin(&self, amount_in: u64, minimum_amount_out: u64) -> Instruction {
+        self.begin_route(amount_in, minimum_amount_out, self.num_steps() as u16, None)
+    }
+
+    /// Begins a route of `num_steps` steps, which may include splits and merges.
+    fn begin_route(
+        &self,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        num_steps: u16,
+        valid_until: Option<i64>,
+    ) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::BeginV2 {
+                continuation: self.continuation,
+                input: self.accounts[0],
+                output: self.accounts[self.num_steps()],
+                owner: self.owner,
+            },
+            instruction::BeginV2 {
+                amount_in,
+                minimum_amount_out,
+                num_steps,
+                valid_until,
+            },
+        )
+    }
+
+    fn split(&self, amounts: Vec<u64>) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::Split {
+                continuation: self.continuation,
+                owner: self.owner,
+            },
+            instruction::Split { amounts },
+        )
+    }
+
+    fn merge(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::Merge {
+                continuation: self.continuation,
+                owner: self.owner,
+            },
+            instruction::Merge {},
+        )
+    }
+
+    fn set_step_minimums(&self, step_minimums: Vec<u64>) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SetStepMinimums {
+                continuation: self.continuation,
+                owner: self.owner,
+            },
+            instruction::SetStepMinimums { step_minimums },
+        )
+    }
+
+    /// The [RouteStep]s of a single-instruction route through `steps`,
+    /// with the accounts of each step following those of the previous one.
+    fn route_steps(&self, steps: &[Step]) -> (Vec<RouteStep>, Vec<AccountMeta>) {
+        let mut route_steps = vec![];
+        let mut remaining_accounts: Vec<AccountMeta> = vec![];
+        for step in steps {
+            let accounts = step.route_accounts();
+            route_steps.push(RouteStep {
+                action_type: step.action_type(),
+                accounts_start: remaining_accounts.len() as u8,
+                accounts_end: (remaining_accounts.len() + accounts.len()) as u8,
+            });
+            remaining_accounts.extend(accounts);
+        }
+        (route_steps, remaining_accounts)
+    }
+
+    /// Routes from the first to the last account in a single instruction.
+    fn route(
+        &self,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        steps: Vec<RouteStep>,
+        remaining_accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        let (config, _) =
+            Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID);
+        let mut instruction = harness::instruction(
+            continuation_router::ID,
+            accounts::Route {
+                input: self.accounts[0],
+                output: self.accounts[self.num_steps()],
+                owner: self.owner,
+                token_program: anchor_spl::token::ID,
+                config,
+            },
+            instruction::Route {
+                amount_in,
+                minimum_amount_out,
+                steps,
+            },
+        );
+        instruction.accounts.extend(remaining_accounts);
+        instruction
+    }
+
+    fn step(&self, index: usize) -> Step {
+        Step {
+            deposit: index % 2 == 1,
+            continuation: self.continuation,
+            swap_program: mock_swap::ID,
+            owner: self.owner,
+            owner_signs: true,
+            input: self.accounts[index],
+            output: self.accounts[index + 1],
+            pool: self.pools[index].clone(),
+        }
+    }
+
+    /// Sets the rate and mode of the pool of a step.
+    fn set_pool(&mut self, index: usize, rate_bps: u64, mode: Mode) {
+        self.bank
+            .set_pool_params(&self.pools[index], pool_params(rate_bps, mode));
+    }
+
+    fn end(&self, output: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::End {
+                continuation: self.continuation,
+                output,
+                owner: self.owner,
+                payer: self.owner,
+            },
+            instruction::End {},
+        )
+    }
+
+    fn wrap_sol(&self, index: usize, amount: u64) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::WrapSOL {
+                owner: self.owner,
+                wsol: self.accounts[index],
+                system_program: system_program::ID,
+                token_program: anchor_spl::token::ID,
+            },
+            instruction::WrapSol { amount },
+        )
+    }
+
+    fn end_unwrap_sol(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::EndUnwrapSOL {
+                continuation: self.continuation,
+                output: self.accounts[self.num_steps()],
+                owner: self.owner,
+                payer: self.owner,
+                token_program: anchor_spl::token::ID,
+            },
+            instruction::EndUnwrapSol {},
+        )
+    }
+
+    fn begin_exact_out(
+        &self,
+        amount_in: u64,
+        maximum_amount_in: u64,
+        amount_out: u64,
+    ) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::BeginV2 {
+                continuation: self.continuation,
+                input: self.accounts[0],
+                output: self.accounts[self.num_steps()],
+                owner: self.owner,
+            },
+            instruction::BeginExactOut {
+                amount_in,
+                maximum_amount_in,
+                amount_out,
+                num_steps: self.num_steps() as u16,
+                valid_until: None,
+            },
+        )
+    }
+
+    fn end_exact_out(&self, input: Pubkey, refund: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::EndExactOut {
+                continuation: self.continuation,
+                input,
+                output: self.accounts[self.num_steps()],
+                refund,
+                owner: self.owner,
+                payer: self.owner,
+                token_program: anchor_spl::token::ID,
+            },
+            instruction::EndExactOut {},
+        )
+    }
+
+    fn begin_delegated(&self, delegate: Pubkey, amount_in: u64, output: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::BeginDelegated {
+                continuation: self.continuation,
+                input: self.accounts[0],
+                output,
+                delegate,
+            },
+            instruction::BeginDelegated {
+                amount_in,
+                minimum_amount_out: 0,
+                num_steps: self.num_steps() as u16,
+                valid_until: None,
+            },
+        )
+    }
+
+    /// Step `index` of a route driven by `delegate`.
+    fn delegated_step(&self, index: usize, delegate: Pubkey) -> Step {
+        Step {
+            owner: delegate,
+            ..self.step(index)
+        }
+    }
+
+    /// Ends a route driven by `delegate`, which also paid for the continuation.
+    fn end_delegated(&self, delegate: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::End {
+                continuation: self.continuation,
+                output: self.accounts[self.num_steps()],
+                owner: delegate,
+                payer: delegate,
+            },
+            instruction::End {},
+        )
+    }
+
+    fn abort(&self, authority: Pubkey, payer: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::Abort {
+                continuation: self.continuation,
+                authority,
+                payer,
+            },
+            instruction::Abort {},
+        )
+    }
+
+    /// Aborts the route as `authority`, checking that the rent of the continuation
+    /// is returned to `payer` and that the continuation is closed.
+    fn assert_aborted(&mut self, authority: Pubkey, payer: Pubkey) {
+        let lamports = |f: &Self, key: &Pubkey| f.bank.account(key).unwrap().lamports;
+        let payer_lamports = lamports(self, &payer);
+        let continuation_lamports = lamports(self, &self.continuation);
+
+        // funding the continuation in the same transaction keeps its data,
+        // which must no longer be usable as a continuation
+        let funder = self.bank.create_wallet(1);
+        let fund = system_instruction::transfer(&funder, &self.continuation, 1);
+        self.bank
+            .process_transaction(&[self.abort(authority, payer), fund])
+            .unwrap();
+        assert_eq!(
+            lamports(self, &payer),
+            payer_lamports + continuation_lamports
+        );
+        let continuation = self.bank.account(&self.continuation).unwrap();
+        assert_eq!(continuation.lamports, 1);
+        assert_eq!(
+            continuation.data[..8],
+            anchor_lang::__private::CLOSED_ACCOUNT_DISCRIMINATOR
+        );
+        self.assert_fails(
+            self.step(0).instruction(),
+            anchor_error(anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch),
+        );
+    }
+
+    /// Approves `delegate` to spend `amount` of the token account at `index`.
+    fn approve(&mut self, index: usize, delegate: Pubkey, amount: u64) {
+        self.bank
+            .update_token_account(self.accounts[index], |account| {
+                account.delegate = COption::Some(delegate);
+                account.delegated_amount = amount;
+            });
+    }
+
+    fn balance(&self, index: usize) -> u64 {
+        self.bank.token_account(&self.accounts[index]).amount
+    }
+
+    /// Begins a route of `amount_in` and swaps at par for the first `steps` steps.
+    fn run(&mut self, amount_in: u64, steps: usize) {
+        self.bank
+            .process_instruction(self.begin(amount_in, 0))
+            .unwrap();
+        for index in 0..steps {
+            self.bank
+                .process_instruction(self.step(index).instruction())
+                .unwrap();
+        }
+    }
+
+    /// Executes an instruction which is expected to fail without modifying any account.
+    fn assert_fails(&mut self, instruction: Instruction, err: ProgramError) {
+        let snapshot = self.bank.snapshot();
+        assert_eq!(self.bank.process_instruction(instruction), Err(err));
+        assert_eq!(self.bank.snapshot(), snapshot);
+    }
+}
+
+/// Ways of tampering with the accounts of an action.
+#[derive(Clone, Copy, Debug)]
+enum Substitution {
+    /// Another token account of the owner with the same mint as the input.
+    Input,
+    /// An output account owned by the attacker.
+    ForeignOutput,
+    /// An output account of the owner with a different mint than the route output.
+    OutputMint,
+    /// The attacker signs instead of the owner.
+    Owner,
+    /// The owner does not sign.
+    UnsignedOwner,
+    /// A swap program which is not approved by the config.
+    SwapProgram,
+    /// The input account was transferred to the attacker after the route began.
+    InputOwnerChanged,
+}
+
+/// Ways of tampering with a delegated route.
+#[derive(Clone, Copy, Debug)]
+enum DelegateSubstitution {
+    /// The owner signs instead of the delegate.
+    Owner,
+    /// The delegation of the input account was revoked.
+    Revoked,
+    /// The input account is delegated to the attacker.
+    OtherDelegate,
+    /// The delegated amount of the input account is below the amount in.
+    DelegatedAmount,
+    /// An output account owned by the delegate.
+    DelegateOutput,
+}
+
+fn delegate_substitution() -> impl Strategy<Value = DelegateSubstitution> {
+    prop_oneof![
+        Just(DelegateSubstitution::Owner),
+        Just(DelegateSubstitution::Revoked),
+        Just(DelegateSubstitution::OtherDelegate),
+        Just(DelegateSubstitution::DelegatedAmount),
+        Just(DelegateSubstitution::DelegateOutput),
+    ]
+}
+
+fn substitution() -> impl Strategy<Value = Substitution> {
+    prop_oneof![
+        Just(Substitution::Input),
+        Just(Substitution::ForeignOutput),
+        Just(Substitution::OutputMint),
+        Just(Substitution::Owner),
+        Just(Substitution::UnsignedOwner),
+        Just(Substitution::SwapProgram),
+        Just(Substitution::InputOwnerChanged),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn test_route(
+        amount_in in 1u64..1_000_000_000_000,
+        rates_bps in prop::collection::vec(1u64..=20_000, 1..=6),
+        balances in prop::collection::vec(0u64..1_000_000, 7),
+        minimum_ratio_bps in 0u64..=12_000,
+        legacy in any::<bool>(),
+    ) {
+        let num_steps = rates_bps.len();
+        let mut balances = balances[..=num_steps].to_vec();
+        balances[0] += amount_in;
+        let mut f = Fixture::new(&balances);
+
+        // amounts going into each step and out of the route
+        let mut amounts = vec![amount_in];
+        for rate_bps in &rates_bps {
+            let amount = *amounts.last().unwrap() as u128;
+            amounts.push((amount * *rate_bps as u128 / 10_000) as u64);
+        }
+        let expected_out = amounts[num_steps];
+        let minimum_amount_out = (expected_out as u128 * minimum_ratio_bps as u128 / 10_000) as u64;
+
+        f.bank.process_instruction(f.begin(amount_in, minimum_amount_out)).unwrap();
+        if legacy {
+            f.bank.convert_to_legacy_continuation(&f.continuation);
+        }
+        let continuation = f.bank.continuation(&f.continuation);
+        prop_assert_eq!(continuation.steps_left as usize, num_steps);
+        prop_assert_eq!(continuation.input, f.accounts[0]);
+        prop_assert_eq!(continuation.output, f.accounts[num_steps]);
+        prop_assert_eq!(continuation.output_initial_balance, balances[num_steps]);
+
+        for (index, rate_bps) in rates_bps.iter().enumerate() {
+            f.set_pool(index, *rate_bps, Mode::Normal);
+            let result = f.bank.process_instruction(f.step(index).instruction());
+            if amounts[index] == 0 {
+                prop_assert_eq!(result, Err(router_error(ErrorCode::ZeroSwap)));
+                return Ok(());
+            }
+            if index == num_steps - 1 && expected_out < minimum_amount_out {
+                prop_assert_eq!(result, Err(mock_swap_error(mock_swap::ErrorCode::ExceededSlippage)));
+                return Ok(());
+            }
+            result.unwrap();
+
+            // the continuation moves to the output of the step
+            let continuation = f.bank.continuation(&f.continuation);
+            let amount_out = token_amount(f.mints[index + 1], amounts[index + 1]);
+            prop_assert_eq!(continuation.steps_left as usize, num_steps - index - 1);
+            prop_assert_eq!(continuation.input, f.accounts[index + 1]);
+            prop_assert_eq!(continuation.amount_in, amount_out);
+            prop_assert_eq!(
+                RouteResult::from_return_data(),
+                Some(RouteResult {
+                    amount_in: token_amount(f.mints[index], amounts[index]),
+                    amount_out,
+                })
+            );
+            prop_assert_eq!(f.balance(index + 1), balances[index + 1] + amounts[index + 1]);
+        }
+
+        // no steps may be added to a completed route
+        f.assert_fails(f.step(0).instruction(), router_error(ErrorCode::NoMoreSteps));
+
+        let owner_lamports = f.bank.account(&f.owner).unwrap().lamports;
+        let continuation_lamports = f.bank.account(&f.continuation).unwrap().lamports;
+        f.bank.process_instruction(f.end(f.accounts[num_steps])).unwrap();
+        prop_assert_eq!(
+            RouteResult::from_return_data(),
+            Some(RouteResult {
+                amount_in: token_amount(f.mints[0], amount_in),
+                amount_out: token_amount(f.mints[num_steps], expected_out),
+            })
+        );
+        // the continuation is closed to the payer
+        prop_assert!(f.bank.account(&f.continuation).is_none());
+        prop_assert_eq!(
+            f.bank.account(&f.owner).unwrap().lamports,
+            owner_lamports + continuation_lamports
+        );
+        prop_assert_eq!(f.balance(0), balances[0] - amount_in);
+    }
+
+    #[test]
+    fn test_end_incomplete(num_steps in 1usize..=6, steps in 0usize..6) {
+        let steps = steps % num_steps;
+        let mut f = Fixture::new(&vec![1_000; num_steps + 1]);
+        f.run(1_000, steps);
+        let continuation = f.bank.continuation(&f.continuation);
+        prop_assert_eq!(continuation.steps_left as usize, num_steps - steps);
+        f.assert_fails(f.end(f.accounts[num_steps]), router_error(ErrorCode::EndIncomplete));
+    }
+
+    #[test]
+    fn test_end_output_substituted(num_steps in 1usize..=6) {
+        let mut f = Fixture::new(&vec![1_000; num_steps + 1]);
+        f.run(1_000, num_steps);
+        let mint = f.mints[num_steps];
+        let other_output = f.bank.create_token_account(mint, f.owner, 0);
+        f.assert_fails(
+            f.end(other_output),
+            anchor_error(anchor_lang::error::ErrorCode::ConstraintHasOne),
+        );
+    }
+
+    #[test]
+    fn test_step_balance_lower(
+        num_steps in 1usize..=6,
+        step in 0usize..6,
+        balance in 1u64..1_000_000,
+        amount in 1u64..1_000_000,
+    ) {
+        let step = step % num_steps;
+        let mut balances = vec![balance; num_steps + 1];
+        balances[0] += 1_000;
+        let mut f = Fixture::new(&balances);
+        f.run(1_000, step);
+        f.set_pool(step, 10_000, Mode::LowerOutput { amount });
+        f.assert_fails(f.step(step).instruction(), router_error(ErrorCode::BalanceLower));
+    }
+
+    #[test]
+    fn test_step_failure(num_steps in 1usize..=6, step in 0usize..6) {
+        let step = step % num_steps;
+        let mut f = Fixture::new(&vec![1_000; num_steps + 1]);
+        f.run(1_000, step);
+        f.set_pool(step, 10_000, Mode::Fail);
+        f.assert_fails(f.step(step).instruction(), mock_swap_error(mock_swap::ErrorCode::PoolFailure));
+    }
+
+    #[test]
+    fn test_end_output_drained(
+        num_steps in 1usize..=6,
+        rate_bps in 1u64..=20_000,
+        minimum_amount_out in 0u64..2_000,
+        drained in 1u64..=3_000,
+    ) {
+        let mut balances = vec![1_000; num_steps + 1];
+        balances[0] += 1_000;
+        let mut f = Fixture::new(&balances);
+        let output = f.accounts[num_steps];
+        let mint = f.mints[num_steps];
+
+        // a pool which ignores slippage, followed by a transfer out of the output account
+        f.bank.process_instruction(f.begin(1_000, minimum_amount_out)).unwrap();
+        for index in 0..num_steps {
+            let rate_bps = if index == num_steps - 1 { rate_bps } else { 10_000 };
+            f.set_pool(index, rate_bps, Mode::IgnoreMinimum);
+            f.bank.process_instruction(f.step(index).instruction()).unwrap();
+        }
+        let amount_out = f.balance(num_steps) - 1_000;
+        let drained = drained.min(f.balance(num_steps));
+        let destination = f.bank.create_token_account(mint, f.attacker, 0);
+        let transfer = spl_token::instruction::transfer(
+            &spl_token::ID,
+            &output,
+            &destination,
+            &f.owner,
+            &[],
+            drained,
+        )
+        .unwrap();
+
+        let result = f.bank.process_transaction(&[transfer, f.end(output)]);
+        if drained > amount_out {
+            prop_assert_eq!(result, Err(router_error(ErrorCode::BalanceLower)));
+        } else if amount_out - drained < minimum_amount_out {
+            prop_assert_eq!(result, Err(router_error(ErrorCode::MinimumOutNotMet)));
+        } else {
+            prop_assert_eq!(result, Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_account_substitution(
+        num_steps in 1usize..=6,
+        step in 0usize..6,
+        substitution in substitution(),
+        legacy in any::<bool>(),
+    ) {
+        let step = match substitution {
+            // the output mint is only checked by the last step
+            Substitution::OutputMint => num_steps - 1,
+            _ => step % num_steps,
+        };
+        let mut f = Fixture::new(&vec![1_000; num_steps + 1]);
+        f.run(1_000, step);
+        if legacy {
+            f.bank.convert_to_legacy_continuation(&f.continuation);
+        }
+
+        let mut accounts = f.step(step);
+        let input_mint = f.mints[step];
+        let output_mint = f.mints[step + 1];
+        let err = match substitution {
+            Substitution::Input => {
+                accounts.input = f.bank.create_token_account(input_mint, f.owner, 1_000);
+                router_error(ErrorCode::PathInputOutputMismatch)
+            }
+            Substitution::ForeignOutput => {
+                accounts.output = f.bank.create_token_account(output_mint, f.attacker, 0);
+                router_error(ErrorCode::OutputOwnerMismatch)
+            }
+            Substitution::OutputMint => {
+                let mint = f.bank.create_mint();
+                accounts.output = f.bank.create_token_account(mint, f.owner, 0);
+                router_error(ErrorCode::OutputMintMismatch)
+            }
+            Substitution::Owner => {
+                accounts.owner = f.attacker;
+                anchor_error(anchor_lang::error::ErrorCode::ConstraintHasOne)
+            }
+            Substitution::UnsignedOwner => {
+                accounts.owner_signs = false;
+                anchor_error(anchor_lang::error::ErrorCode::AccountNotSigner)
+            }
+            Substitution::SwapProgram => {
+                let program = Pubkey::new_unique();
+                f.bank.add_program(program, mock_swap::entry);
+                accounts.swap_program = program;
+                router_error(ErrorCode::SwapProgramNotApproved)
+            }
+            Substitution::InputOwnerChanged => {
+                let attacker = f.attacker;
+                f.bank.update_token_account(accounts.input, |account| account.owner = attacker);
+                router_error(ErrorCode::InputOwnerMismatch)
+            }
+        };
+        f.assert_fails(accounts.instruction(), err);
+
+        // the route can still be completed with the expected accounts
+        if !matches!(substitution, Substitution::InputOwnerChanged) {
+            for index in step..num_steps {
+                f.bank.process_instruction(f.step(index).instruction()).unwrap();
+            }
+            f.bank.process_instruction(f.end(f.accounts[num_steps])).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_exact_out_route(
+        num_steps in 1usize..=6,
+        amount_in in 1u64..1_000_000,
+        rate_bps in 1u64..=20_000,
+        amount_out in 0u64..2_000_000,
+        slack in 0u64..1_000,
+        drained in 0u64..1_000,
+    ) {
+        let mut f = Fixture::new(&vec![2_000_000; num_steps + 1]);
+        let output = f.accounts[num_steps];
+        let refund = f.bank.create_token_account(f.mints[num_steps], f.owner, 0);
+        let maximum_amount_in = amount_in + slack;
+        let produced = (amount_in as u128 * rate_bps as u128 / 10_000) as u64;
+
+        f.bank
+            .process_instruction(f.begin_exact_out(amount_in, maximum_amount_in, amount_out))
+            .unwrap();
+        let continuation = f.bank.continuation(&f.continuation);
+        prop_assert_eq!(
+            continuation.maximum_amount_in,
+            TokenAmount { mint: f.mints[0], amount: maximum_amount_in }
+        );
+        prop_assert_eq!(continuation.delegate(), None);
+        for index in 0..num_steps {
+            let rate_bps = if index == num_steps - 1 { rate_bps } else { 10_000 };
+            f.set_pool(index, rate_bps, Mode::Normal);
+            let result = f.bank.process_instruction(f.step(index).instruction());
+            if index == num_steps - 1 && produced == 0 {
+                prop_assert_eq!(result, Err(router_error(ErrorCode::ZeroSwap)));
+                return Ok(());
+            }
+            if index == num_steps - 1 && produced < amount_out {
+                prop_assert_eq!(result, Err(mock_swap_error(mock_swap::ErrorCode::ExceededSlippage)));
+                return Ok(());
+            }
+            result.unwrap();
+        }
+
+        // exact-output routes cannot skip the checks of `end_exact_out`
+        f.assert_fails(f.end(output), router_error(ErrorCode::RouteModeMismatch));
+
+        // tokens taken from the input account count towards the maximum amount in
+        let destination = f.bank.create_token_account(f.mints[0], f.attacker, 0);
+        let transfer = spl_token::instruction::transfer(
+            &spl_token::ID,
+            &f.accounts[0],
+            &destination,
+            &f.owner,
+            &[],
+            drained,
+        )
+        .unwrap();
+        let result = f
+            .bank
+            .process_transaction(&[transfer, f.end_exact_out(f.accounts[0], refund)]);
+        if amount_in + drained > maximum_amount_in {
+            prop_assert_eq!(result, Err(router_error(ErrorCode::MaximumAmountInExceeded)));
+            return Ok(());
+        }
+        result.unwrap();
+        prop_assert_eq!(
+            RouteResult::from_return_data(),
+            Some(RouteResult {
+                amount_in: token_amount(f.mints[0], amount_in + drained),
+                amount_out: token_amount(f.mints[num_steps], amount_out),
+            })
+        );
+        // the output receives exactly the amount out, and the surplus is refunded
+        prop_assert_eq!(f.balance(num_steps), 2_000_000 + amount_out);
+        prop_assert_eq!(f.bank.token_account(&refund).amount, produced - amount_out);
+        prop_assert!(f.bank.account(&f.continuation).is_none());
+    }
+
+    #[test]
+    fn test_delegated_route(
+        num_steps in 1usize..=6,
+        amount_in in 1u64..1_000_000,
+        delegated_amount in 0u64..2_000_000,
+    ) {
+        let mut f = Fixture::new(&vec![2_000_000; num_steps + 1]);
+        let delegate = f.bank.create_wallet(1_000_000_000);
+        f.approve(0, delegate, delegated_amount);
+        for index in 1..num_steps {
+            f.approve(index, delegate, amount_in);
+        }
+
+        let output = f.accounts[num_steps];
+        let begin = f.begin_delegated(delegate, amount_in, output);
+        if delegated_amount < amount_in {
+            f.assert_fails(begin, router_error(ErrorCode::InsufficientDelegatedAmount));
+            return Ok(());
+        }
+        f.bank.process_instruction(begin).unwrap();
+        let continuation = f.bank.continuation(&f.continuation);
+        prop_assert_eq!(continuation.owner, f.owner);
+        prop_assert_eq!(continuation.payer, delegate);
+        prop_assert_eq!(continuation.delegate(), Some(delegate));
+
+        for index in 0..num_steps {
+            f.bank
+                .process_instruction(f.delegated_step(index, delegate).instruction())
+                .unwrap();
+        }
+        // the delegate spent its allowance into the accounts of the owner
+        prop_assert_eq!(
+            f.bank.token_account(&f.accounts[0]).delegated_amount,
+            delegated_amount - amount_in
+        );
+        prop_assert_eq!(f.balance(0), 2_000_000 - amount_in);
+        prop_assert_eq!(f.balance(num_steps), 2_000_000 + amount_in);
+
+        // only the delegate may end the route, which returns the rent to it
+        f.assert_fails(
+            f.end(output),
+            anchor_error(anchor_lang::error::ErrorCode::ConstraintHasOne),
+        );
+        let delegate_lamports = f.bank.account(&delegate).unwrap().lamports;
+        let continuation_lamports = f.bank.account(&f.continuation).unwrap().lamports;
+        f.bank.process_instruction(f.end_delegated(delegate)).unwrap();
+        prop_assert!(f.bank.account(&f.continuation).is_none());
+        prop_assert_eq!(
+            f.bank.account(&delegate).unwrap().lamports,
+            delegate_lamports + continuation_lamports
+        );
+    }
+
+    #[test]
+    fn test_delegated_substitution(
+        num_steps in 1usize..=6,
+        step in 0usize..6,
+        substitution in delegate_substitution(),
+    ) {
+        let step = step % num_steps;
+        let mut f = Fixture::new(&vec![1_000; num_steps + 1]);
+        let delegate = f.bank.create_wallet(1_000_000_000);
+        for index in 0..num_steps {
+            f.approve(index, delegate, 1_000);
+        }
+        f.bank
+            .process_instruction(f.begin_delegated(delegate, 1_000, f.accounts[num_steps]))
+            .unwrap();
+        for index in 0..step {
+            f.bank
+                .process_instruction(f.delegated_step(index, delegate).instruction())
+                .unwrap();
+        }
+
+        let mut accounts = f.delegated_step(step, delegate);
+        let err = match substitution {
+            DelegateSubstitution::Owner => {
+                accounts.owner = f.owner;
+                anchor_error(anchor_lang::error::ErrorCode::ConstraintHasOne)
+            }
+            DelegateSubstitution::Revoked => {
+                f.bank.update_token_account(accounts.input, |account| {
+                    account.delegate = COption::None;
+                    account.delegated_amount = 0;
+                });
+                router_error(ErrorCode::InputNotDelegated)
+            }
+            DelegateSubstitution::OtherDelegate => {
+                let attacker = f.attacker;
+                f.approve(step, attacker, 1_000);
+                router_error(ErrorCode::InputNotDelegated)
+            }
+            DelegateSubstitution::DelegatedAmount => {
+                f.approve(step, delegate, 999);
+                router_error(ErrorCode::InsufficientDelegatedAmount)
+            }
+            DelegateSubstitution::DelegateOutput => {
+                let mint = f.mints[step + 1];
+                accounts.output = f.bank.create_token_account(mint, delegate, 0);
+                router_error(ErrorCode::OutputOwnerMismatch)
+            }
+        };
+        f.assert_fails(accounts.instruction(), err);
+
+        // the route can still be completed once the owner approves the delegate again
+        f.approve(step, delegate, 1_000);
+        for index in step..num_steps {
+            f.bank
+                .process_instruction(f.delegated_step(index, delegate).instruction())
+                .unwrap();
+        }
+        f.bank.process_instruction(f.end_delegated(delegate)).unwrap();
+    }
+
+    #[test]
+    fn test_valid_until(
+        num_steps in 1usize..=6,
+        valid_until in 1_000i64..3_000,
+        step_delay in 0i64..2_000,
+        end_delay in 0i64..2_000,
+    ) {
+        let mut f = Fixture::new(&vec![1_000; num_steps + 1]);
+        f.bank.set_unix_timestamp(1_000);
+        f.bank
+            .process_instruction(f.begin_route(1_000, 0, num_steps as u16, Some(valid_until)))
+            .unwrap();
+        prop_assert_eq!(f.bank.continuation(&f.continuation).valid_until(), Some(valid_until));
+
+        // actions may be processed up to and including `valid_until`
+        let step_time = 1_000 + step_delay;
+        f.bank.set_unix_timestamp(step_time);
+        if step_time > valid_until {
+            f.assert_fails(f.step(0).instruction(), router_error(ErrorCode::RouteExpired));
+            return Ok(());
+        }
+        for index in 0..num_steps {
+            f.bank.process_instruction(f.step(index).instruction()).unwrap();
+        }
+
+        // so may the route be ended
+        let end_time = step_time + end_delay;
+        f.bank.set_unix_timestamp(end_time);
+        if end_time > valid_until {
+            f.assert_fails(f.end(f.accounts[num_steps]), router_error(ErrorCode::RouteExpired));
+            return Ok(());
+        }
+        f.bank.process_instruction(f.end(f.accounts[num_steps])).unwrap();
+        prop_assert!(f.bank.account(&f.continuation).is_none());
+    }
+}
+
+#[test]
+fn test_begin_delegated_accounts() {
+    let mut f = Fixture::new(&[1_000, 0]);
+    let delegate = f.bank.create_wallet(1_000_000_000);
+    let output = f.accounts[1];
+
+    // the input must be delegated to the signer
+    f.assert_fails(
+        f.begin_delegated(delegate, 1_000, output),
+        router_error(ErrorCode::InputNotDelegated),
+    );
+    let attacker = f.attacker;
+    f.approve(0, attacker, 1_000);
+    f.assert_fails(
+        f.begin_delegated(delegate, 1_000, output),
+        router_error(ErrorCode::InputNotDelegated),
+    );
+
+    // the output must be owned by the owner of the input
+    f.approve(0, delegate, 1_000);
+    let delegate_output = f.bank.create_token_account(f.mints[1], delegate, 0);
+    f.assert_fails(
+        f.begin_delegated(delegate, 1_000, delegate_output),
+        router_error(ErrorCode::OutputOwnerMismatch),
+    );
+
+    // the delegate may abort its route at any time
+    f.bank
+        .process_instruction(f.begin_delegated(delegate, 1_000, output))
+        .unwrap();
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::Abort {
+                continuation: f.continuation,
+                authority: delegate,
+                payer: delegate,
+            },
+            instruction::Abort {},
+        ))
+        .unwrap();
+    assert!(f.bank.account(&f.continuation).is_none());
+}
+
+#[test]
+fn test_adapter_builtin_action() {
+    const ACTION_CODE: u16 = 1_000;
+    let mut f = Fixture::new(&[1_000, 0]);
+    // the mock swap program is only registered as an adapter
+    let admin = f.bank.create_wallet(1_000_000_000);
+    let config = f.bank.init_router(admin, vec![]);
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::UpdateConfig { config, admin },
+            instruction::RegisterAdapter {
+                program: mock_swap::ID,
+                action_code: ACTION_CODE,
+            },
+        ))
+        .unwrap();
+    f.bank.process_instruction(f.begin(1_000, 0)).unwrap();
+
+    // adapters may not be passed as the swap program of built-in actions
+    let step = f.step(0);
+    f.assert_fails(
+        step.instruction(),
+        router_error(ErrorCode::SwapProgramNotApproved),
+    );
+
+    let mut external_action = harness::instruction(
+        continuation_router::ID,
+        accounts::ExternalActionAccounts {
+            continuation: accounts::ContinuationAccounts {
+                continuation: f.continuation,
+                token_program: anchor_spl::token::ID,
+                swap_program: mock_swap::ID,
+                config,
+                owner: f.owner,
+            },
+            action: accounts::ExternalAction {
+                input: step.input,
+                output: step.output,
+            },
+        },
+        instruction::ExternalAction {
+            action_code: ACTION_CODE,
+        },
+    );
+    external_action
+        .accounts
+        .extend(step.pool.swap_accounts(f.owner, 0, step.input, step.output));
+    f.bank.process_instruction(external_action).unwrap();
+    f.bank.process_instruction(f.end(f.accounts[1])).unwrap();
+    assert_eq!(f.balance(1), 1_000);
+}
+
+#[test]
+fn test_in_place_swap_balance_lower() {
+    let mut f = Fixture::new(&[2_000, 0, 0]);
+    let pool = f.bank.create_pool(
+        [f.mints[0], f.mints[0]],
+        [RESERVES, RESERVES],
+        pool_params(5_000, Mode::Normal),
+    );
+    f.bank.process_instruction(f.begin(1_000, 0)).unwrap();
+
+    // only fees may be taken in place; a swap into its own input must not lower its balance
+    let mut step = f.step(0);
+    step.output = f.accounts[0];
+    step.pool = pool;
+    f.assert_fails(step.instruction(), router_error(ErrorCode::BalanceLower));
+}
+
+#[test]
+fn test_end_exact_out_accounts() {
+    let mut f = Fixture::new(&[2_000, 0]);
+    let output = f.accounts[1];
+    let refund = f.bank.create_token_account(f.mints[1], f.owner, 0);
+
+    // exact-input routes are ended by `end`
+    f.run(1_000, 1);
+    f.assert_fails(
+        f.end_exact_out(f.accounts[0], refund),
+        router_error(ErrorCode::RouteModeMismatch),
+    );
+    f.bank.process_instruction(f.end(output)).unwrap();
+
+    let continuation = f.bank.create_continuation();
+    f.continuation = continuation;
+    f.bank
+        .process_instruction(f.begin_exact_out(500, 500, 400))
+        .unwrap();
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+
+    // the input must be the input account the route began with
+    let other_input = f.bank.create_token_account(f.mints[0], f.owner, 1_000);
+    f.assert_fails(
+        f.end_exact_out(other_input, refund),
+        anchor_error(anchor_lang::error::ErrorCode::ConstraintHasOne),
+    );
+    // the surplus must be refunded to another account of the owner
+    f.assert_fails(
+        f.end_exact_out(f.accounts[0], output),
+        router_error(ErrorCode::InvalidRefundAccount),
+    );
+    let foreign_refund = f.bank.create_token_account(f.mints[1], f.attacker, 0);
+    f.assert_fails(
+        f.end_exact_out(f.accounts[0], foreign_refund),
+        router_error(ErrorCode::OutputOwnerMismatch),
+    );
+    let other_mint = f.bank.create_mint();
+    let wrong_mint_refund = f.bank.create_token_account(other_mint, f.owner, 0);
+    f.assert_fails(
+        f.end_exact_out(f.accounts[0], wrong_mint_refund),
+        router_error(ErrorCode::OutputMintMismatch),
+    );
+
+    f.bank
+        .process_instruction(f.end_exact_out(f.accounts[0], refund))
+        .unwrap();
+    assert_eq!(f.balance(1), 1_000 + 400);
+    assert_eq!(f.bank.token_account(&refund).amount, 100);
+}
+
+#[test]
+fn test_split_merge() {
+    let mut f = Fixture::new(&[1_000, 0, 0]);
+    // a split, a swap on each of the two legs, a merge and a final swap
+    f.bank
+        .process_instruction(f.begin_route(1_000, 0, 5, None))
+        .unwrap();
+
+    // the legs must add up to the amount of the route
+    f.assert_fails(
+        f.split(vec![600, 300]),
+        router_error(ErrorCode::SplitAmountMismatch),
+    );
+    f.assert_fails(
+        f.split(vec![600, 500]),
+        router_error(ErrorCode::SplitAmountMismatch),
+    );
+    f.bank.process_instruction(f.split(vec![600, 400])).unwrap();
+    let continuation = f.bank.continuation(&f.continuation);
+    assert_eq!(continuation.amount_in, token_amount(f.mints[0], 600));
+    assert_eq!(
+        continuation.legs(),
+        &[Leg {
+            input: f.accounts[0],
+            amount_in: token_amount(f.mints[0], 400),
+        }]
+    );
+
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+    assert_eq!(f.balance(1), 600);
+    // the legs may only be merged once all of them reached the same account
+    f.assert_fails(f.merge(), router_error(ErrorCode::PathInputOutputMismatch));
+    // nor may the route end while a leg is open
+    f.assert_fails(f.end(f.accounts[2]), router_error(ErrorCode::EndIncomplete));
+
+    // the step on the input of the suspended leg continues that leg
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+    assert_eq!(f.balance(0), 0);
+    assert_eq!(f.balance(1), 1_000);
+    f.bank.process_instruction(f.merge()).unwrap();
+    let continuation = f.bank.continuation(&f.continuation);
+    assert_eq!(continuation.input, f.accounts[1]);
+    assert_eq!(continuation.amount_in, token_amount(f.mints[1], 1_000));
+    assert!(continuation.legs().is_empty());
+
+    f.bank.process_instruction(f.step(1).instruction()).unwrap();
+    f.bank.process_instruction(f.end(f.accounts[2])).unwrap();
+    assert_eq!(
+        RouteResult::from_return_data(),
+        Some(RouteResult {
+            amount_in: token_amount(f.mints[0], 1_000),
+            amount_out: token_amount(f.mints[2], 1_000),
+        })
+    );
+    assert_eq!(f.balance(2), 1_000);
+}
+
+#[test]
+fn test_end_legs_not_merged() {
+    let mut f = Fixture::new(&[1_000, 0]);
+    f.bank
+        .process_instruction(f.begin_route(1_000, 0, 3, None))
+        .unwrap();
+    f.bank.process_instruction(f.split(vec![500, 500])).unwrap();
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+
+    // every step was processed, but the legs were never merged
+    assert_eq!(f.bank.continuation(&f.continuation).steps_left, 0);
+    assert_eq!(f.balance(1), 1_000);
+    f.assert_fails(f.end(f.accounts[1]), router_error(ErrorCode::LegsNotMerged));
+}
+
+#[test]
+fn test_step_minimums() {
+    let mut f = Fixture::new(&[1_000, 0, 0]);
+    f.bank.process_instruction(f.begin(1_000, 0)).unwrap();
+    f.assert_fails(
+        f.set_step_minimums(vec![950, 0, 0]),
+        router_error(ErrorCode::TooManyStepMinimums),
+    );
+    // only the intermediate hop has a minimum
+    f.bank
+        .process_instruction(f.set_step_minimums(vec![950]))
+        .unwrap();
+    assert_eq!(f.bank.continuation(&f.continuation).step_minimums(), &[950]);
+
+    f.set_pool(0, 9_000, Mode::Normal);
+    f.assert_fails(
+        f.step(0).instruction(),
+        router_error(ErrorCode::StepMinimumOutNotMet),
+    );
+    f.set_pool(0, 9_500, Mode::Normal);
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+    assert_eq!(f.balance(1), 950);
+
+    // the minimums are fixed once the first step was processed
+    f.assert_fails(
+        f.set_step_minimums(vec![0]),
+        router_error(ErrorCode::StepsAlreadyProcessed),
+    );
+    f.set_pool(1, 5_000, Mode::Normal);
+    f.bank.process_instruction(f.step(1).instruction()).unwrap();
+    f.bank.process_instruction(f.end(f.accounts[2])).unwrap();
+    assert_eq!(f.balance(2), 475);
+}
+
+#[test]
+fn test_abort_owner() {
+    let mut f = Fixture::new(&[1_000, 0, 0]);
+    f.run(1_000, 1);
+    // the owner may abort right away, even before the route expires
+    let owner = f.owner;
+    f.assert_aborted(owner, owner);
+}
+
+#[test]
+fn test_abort_payer() {
+    let mut f = Fixture::new(&[1_000, 0]);
+    let payer = f.bank.create_wallet(1_000_000_000);
+    f.bank.set_unix_timestamp(1_000);
+    f.bank.process_instruction(f.begin(1_000, 0)).unwrap();
+    f.bank
+        .update_continuation(&f.continuation, |continuation| continuation.payer = payer);
+
+    // only the owner, the delegate and the payer may abort
+    let attacker = f.attacker;
+    f.assert_fails(
+        f.abort(attacker, payer),
+        router_error(ErrorCode::Unauthorized),
+    );
+    f.assert_fails(
+        f.abort(attacker, attacker),
+        anchor_error(anchor_lang::error::ErrorCode::ConstraintHasOne),
+    );
+
+    // the payer must give the owner time to complete the route
+    f.bank
+        .set_unix_timestamp(1_000 + PAYER_ABORT_DELAY_SECONDS - 1);
+    f.assert_fails(
+        f.abort(payer, payer),
+        router_error(ErrorCode::AbortTooEarly),
+    );
+    f.bank.set_unix_timestamp(1_000 + PAYER_ABORT_DELAY_SECONDS);
+    f.assert_aborted(payer, payer);
+}
+
+#[test]
+fn test_abort_payer_expired() {
+    let mut f = Fixture::new(&[1_000, 0]);
+    let payer = f.bank.create_wallet(1_000_000_000);
+    f.bank.set_unix_timestamp(1_000);
+    f.bank
+        .process_instruction(f.begin_route(1_000, 0, 1, Some(1_010)))
+        .unwrap();
+    f.bank
+        .update_continuation(&f.continuation, |continuation| continuation.payer = payer);
+
+    // an expired route may be aborted by the payer before the delay
+    f.bank.set_unix_timestamp(1_010);
+    f.assert_fails(
+        f.abort(payer, payer),
+        router_error(ErrorCode::AbortTooEarly),
+    );
+    f.bank.set_unix_timestamp(1_011);
+    f.assert_aborted(payer, payer);
+}
+
+#[test]
+fn test_return_data() {
+    let mut f = Fixture::new(&[1_000, 0, 0]);
+    f.set_pool(0, 5_000, Mode::Normal);
+    f.set_pool(1, 15_000, Mode::Normal);
+
+    // the raw return data of the router, in the layout documented by `return_data`
+    let assert_return_data = |f: &Fixture, amount_in: TokenAmount, amount_out: TokenAmount| {
+        let (program_id, data) = f.bank.return_data().unwrap();
+        assert_eq!(program_id, continuation_router::ID);
+        assert_eq!(data.len(), RouteResult::LEN);
+        assert_eq!(data[0..32], amount_in.mint.to_bytes());
+        assert_eq!(data[32..40], amount_in.amount.to_le_bytes());
+        assert_eq!(data[40..72], amount_out.mint.to_bytes());
+        assert_eq!(data[72..80], amount_out.amount.to_le_bytes());
+    };
+
+    // each action returns the amounts of its step
+    f.bank.process_instruction(f.begin(1_000, 0)).unwrap();
+    f.bank.process_instruction(f.step(0).instruction()).unwrap();
+    assert_return_data(
+        &f,
+        token_amount(f.mints[0], 1_000),
+        token_amount(f.mints[1], 500),
+    );
+    f.bank.process_instruction(f.step(1).instruction()).unwrap();
+    assert_return_data(
+        &f,
+        token_amount(f.mints[1], 500),
+        token_amount(f.mints[2], 750),
+    );
+
+    // `end` returns the amounts of the whole route
+    f.bank.process_instruction(f.end(f.accounts[2])).unwrap();
+    assert_return_data(
+        &f,
+        token_amount(f.mints[0], 1_000),
+        token_amount(f.mints[2], 750),
+    );
+}
+
+#[test]
+fn test_route_instruction() {
+    let mut f = Fixture::new(&[1_000, 0, 0, 0]);
+    f.set_pool(0, 5_000, Mode::Normal);
+    f.set_pool(1, 20_000, Mode::Normal);
+    f.set_pool(2, 15_000, Mode::Normal);
+    let steps: Vec<Step> = (0..3).map(|index| f.step(index)).collect();
+    let (route_steps, remaining_accounts) = f.route_steps(&steps);
+
+    // the minimum amount out applies to the last step
+    f.assert_fails(
+        f.route(
+            1_000,
+            1_501,
+            route_steps.clone(),
+            remaining_accounts.clone(),
+        ),
+        mock_swap_error(mock_swap::ErrorCode::ExceededSlippage),
+    );
+    f.bank
+        .process_instruction(f.route(1_000, 1_500, route_steps, remaining_accounts))
+        .unwrap();
+    assert_eq!(f.balance(0), 0);
+    assert_eq!(f.balance(1), 0);
+    assert_eq!(f.balance(2), 0);
+    assert_eq!(f.balance(3), 1_500);
+    assert_eq!(
+        RouteResult::from_return_data(),
+        Some(RouteResult {
+            amount_in: token_amount(f.mints[0], 1_000),
+            amount_out: token_amount(f.mints[3], 1_500),
+        })
+    );
+}
+
+#[test]
+fn test_route_instruction_steps() {
+    let mut f = Fixture::new(&[1_000, 0, 0]);
+    let steps = [f.step(0), f.step(1)];
+    let (route_steps, remaining_accounts) = f.route_steps(&steps);
+    let num_accounts = remaining_accounts.len() as u8;
+    let first_end = route_steps[0].accounts_end;
+    let tamper = |f: &mut Fixture, tamper: &dyn Fn(&mut Vec<RouteStep>), err: ErrorCode| {
+        let mut route_steps = route_steps.clone();
+        tamper(&mut route_steps);
+        f.assert_fails(
+            f.route(1_000, 0, route_steps, remaining_accounts.clone()),
+            router_error(err),
+        );
+    };
+
+    // the range of a step must hold the swap program and lie within the remaining accounts
+    tamper(
+        &mut f,
+        &|steps| steps[1].accounts_end = num_accounts + 1,
+        ErrorCode::InvalidRouteStep,
+    );
+    tamper(
+        &mut f,
+        &|steps| steps[1].accounts_start = num_accounts + 1,
+        ErrorCode::InvalidRouteStep,
+    );
+    tamper(
+        &mut f,
+        &|steps| steps[1].accounts_start = steps[1].accounts_end,
+        ErrorCode::InvalidRouteStep,
+    );
+    // overlapping ranges read the accounts of another step
+    tamper(
+        &mut f,
+        &|steps| steps[1].accounts_start = first_end - 2,
+        ErrorCode::SwapProgramNotApproved,
+    );
+    tamper(
+        &mut f,
+        &|steps| {
+            steps[1].accounts_start = steps[0].accounts_start;
+            steps[1].accounts_end = steps[0].accounts_end;
+        },
+        ErrorCode::PathInputOutputMismatch,
+    );
+    // actions which take arguments cannot be routed
+    tamper(
+        &mut f,
+        &|steps| steps[0].action_type = ActionType::TakeFee,
+        ErrorCode::InvalidActionArgs,
+    );
+    tamper(
+        &mut f,
+        &|steps| steps[1].action_type = ActionType::ExternalAction,
+        ErrorCode::InvalidActionArgs,
+    );
+
+    // the swap program of every step must be approved
+    let program = Pubkey::new_unique();
+    f.bank.add_program(program, mock_swap::entry);
+    let mut unapproved = f.step(1);
+    unapproved.swap_program = program;
+    let (unapproved_steps, unapproved_accounts) = f.route_steps(&[f.step(0), unapproved]);
+    f.assert_fails(
+        f.route(1_000, 0, unapproved_steps, unapproved_accounts),
+        router_error(ErrorCode::SwapProgramNotApproved),
+    );
+
+    f.bank
+        .process_instruction(f.route(1_000, 0, route_steps.clone(), remaining_accounts.clone()))
+        .unwrap();
+    assert_eq!(f.balance(2), 1_000);
+}
+
+#[test]
+fn test_wrap_route_unwrap_sol() {
+    let mut f = Fixture::with_mints(&[0, 0, 0], |bank, index| {
+        if index == 1 {
+            bank.create_mint()
+        } else {
+            spl_token::native_mint::ID
+        }
+    });
+    let lamports = |f: &Fixture, key: &Pubkey| f.bank.account(key).unwrap().lamports;
+    let owner_lamports = lamports(&f, &f.owner);
+    let input_lamports = lamports(&f, &f.accounts[0]);
+
+    f.bank
+        .process_transaction(&[f.wrap_sol(0, 1_000_000), f.begin(1_000_000, 1_000_000)])
+        .unwrap();
+    assert_eq!(lamports(&f, &f.owner), owner_lamports - 1_000_000);
+    assert_eq!(lamports(&f, &f.accounts[0]), input_lamports + 1_000_000);
+    assert_eq!(f.balance(0), 1_000_000);
+
+    for index in 0..f.num_steps() {
+        f.bank
+            .process_instruction(f.step(index).instruction())
+            .unwrap();
+    }
+    assert_eq!(lamports(&f, &f.accounts[0]), input_lamports);
+    assert_eq!(f.balance(0), 0);
+    assert_eq!(f.balance(2), 1_000_000);
+
+    // the output is closed to the owner, along with the continuation
+    let owner_lamports = lamports(&f, &f.owner);
+    let output_lamports = lamports(&f, &f.accounts[2]);
+    let continuation_lamports = lamports(&f, &f.continuation);
+    assert_eq!(output_lamports, input_lamports + 1_000_000);
+    f.bank.process_instruction(f.end_unwrap_sol()).unwrap();
+    assert_eq!(
+        lamports(&f, &f.owner),
+        owner_lamports + output_lamports + continuation_lamports
+    );
+    assert!(f.bank.account(&f.accounts[2]).is_none());
+    assert!(f.bank.account(&f.continuation).is_none());
+}
+
+#[test]
+fn test_end_unwrap_sol_not_native() {
+    let mut f = Fixture::new(&[1_000, 0]);
+    f.run(1_000, 1);
+    f.assert_fails(f.end_unwrap_sol(), router_error(ErrorCode::NotNativeMint));
+}
@@ -0,0 +1,484 @@
+//! Routes through the StableSwap actions of the router, executed against
+//! pools of the mock swap program.
+
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::sysvar;
+use continuation_router::{accounts, instruction, ErrorCode, RouteResult, TokenAmount};
+use harness::{Pool, TestBank};
+use mock_swap::{Mode, PoolParams};
+use proptest::prelude::*;
+use stable_swap_math::math::FeeCalculator;
+
+fn router_error(code: ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+fn mock_swap_error(code: mock_swap::ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+const RESERVES: u64 = 1_000_000_000_000;
+
+/// A pool between two tokens and the token accounts of its user.
+struct Fixture {
+    bank: TestBank,
+    owner: Pubkey,
+    continuation: Pubkey,
+    pool: Pool,
+    /// Accounts of the owner of token A, token B and LP tokens.
+    accounts: [Pubkey; 3],
+}
+
+impl Fixture {
+    fn new(params: PoolParams) -> Self {
+        let mut bank = TestBank::new();
+        let owner = bank.create_wallet(1_000_000_000);
+        bank.init_router(Pubkey::new_unique(), vec![mock_swap::ID]);
+        let continuation = bank.create_continuation();
+        let mints = [bank.create_mint(), bank.create_mint()];
+        let pool = bank.create_pool(mints, [RESERVES, RESERVES], params);
+        let accounts = [
+            bank.create_token_account(mints[0], owner, RESERVES),
+            bank.create_token_account(mints[1], owner, 0),
+            bank.create_token_account(pool.pool_mint, owner, 0),
+        ];
+        Fixture {
+            bank,
+            owner,
+            continuation,
+            pool,
+            accounts,
+        }
+    }
+
+    fn begin(&self, amount_in: u64, output: Pubkey, num_steps: u16) -> Instruction {
+        self.begin_from(self.accounts[0], amount_in, output, num_steps)
+    }
+
+    fn begin_from(
+        &self,
+        input: Pubkey,
+        amount_in: u64,
+        output: Pubkey,
+        num_steps: u16,
+    ) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::BeginV2 {
+                continuation: self.continuation,
+                input,
+                output,
+                owner: self.owner,
+            },
+            instruction::BeginV2 {
+                amount_in,
+                minimum_amount_out: 0,
+                num_steps,
+                valid_until: None,
+            },
+        )
+    }
+
+    fn end(&self, output: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::End {
+                continuation: self.continuation,
+                output,
+                owner: self.owner,
+                payer: self.owner,
+            },
+            instruction::End {},
+        )
+    }
+
+    fn continuation_accounts(&self) -> accounts::ContinuationAccounts {
+        accounts::ContinuationAccounts {
+            continuation: self.continuation,
+            token_program: anchor_spl::token::ID,
+            swap_program: stable_swap_anchor::ID,
+            config: Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID).0,
+            owner: self.owner,
+        }
+    }
+
+    fn stable_swap(&self) -> accounts::StableSwap {
+        accounts::StableSwap {
+            swap: self.pool.swap,
+            swap_authority: self.pool.config,
+            clock: sysvar::clock::ID,
+        }
+    }
+
+    /// Token accounts of token `index` of the pool.
+    fn swap_token(&self, index: usize) -> accounts::SwapToken {
+        accounts::SwapToken {
+            user: self.accounts[index],
+            reserve: self.pool.reserves[index],
+        }
+    }
+
+    fn swap_output(&self, index: usize) -> accounts::SwapOutput {
+        accounts::SwapOutput {
+            user_token: self.swap_token(index),
+            fees: self.pool.admin_fees[index],
+        }
+    }
+
+    /// Swaps token A for token B.
+    fn ss_swap(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSSwapAccounts {
+                continuation: self.continuation_accounts(),
+                action: accounts::SSSwap {
+                    swap: self.stable_swap(),
+                    input: self.swap_token(0),
+                    output: self.swap_output(1),
+                },
+            },
+            instruction::SsSwap {},
+        )
+    }
+
+    /// Deposits token A for LP tokens.
+    fn ss_deposit_a(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSDepositAAccounts {
+                continuation: self.continuation_accounts(),
+                action: accounts::SSDepositA {
+                    inner: accounts::SSDeposit {
+                        swap: self.stable_swap(),
+                        input_a: self.swap_token(0),
+                        input_b: self.swap_token(1),
+                        pool_mint: self.pool.pool_mint,
+                        output_lp: self.accounts[2],
+                    },
+                },
+            },
+            instruction::SsDepositA {},
+        )
+    }
+
+    /// Withdraws LP tokens for token B.
+    fn ss_withdraw_one(&self) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSWithdrawOneAccounts {
+                continuation: self.continuation_accounts(),
+                action: accounts::SSWithdrawOne {
+                    swap: self.stable_swap(),
+                    pool_mint: self.pool.pool_mint,
+                    input_lp: self.accounts[2],
+                    quote_reserves: self.pool.reserves[0],
+                    output: self.swap_output(1),
+                },
+            },
+            instruction::SsWithdrawOne {},
+        )
+    }
+
+    /// Withdraws LP tokens for token B, sending the share of token A to `other_output`.
+    fn ss_withdraw(&self, signer: Pubkey, other_output: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSWithdrawAccounts {
+                continuation: accounts::ContinuationAccounts {
+                    owner: signer,
+                    ..self.continuation_accounts()
+                },
+                action: accounts::SSWithdraw {
+                    swap: self.stable_swap(),
+                    pool_mint: self.pool.pool_mint,
+                    input_lp: self.accounts[2],
+                    output: self.swap_output(1),
+                    other_output: accounts::SwapOutput {
+                        user_token: accounts::SwapToken {
+                            user: other_output,
+                            reserve: self.pool.reserves[0],
+                        },
+                        fees: self.pool.admin_fees[0],
+                    },
+                },
+            },
+            instruction::SsWithdraw {},
+        )
+    }
+
+    /// Deposits token A along with a balanced amount of token B from `other_input`.
+    fn ss_deposit_balanced(&self, other_input: Pubkey) -> Instruction {
+        harness::instruction(
+            continuation_router::ID,
+            accounts::SSDepositBalancedAccounts {
+                continuation: self.continuation_accounts(),
+                action: accounts::SSDepositBalanced {
+                    swap: self.stable_swap(),
+                    input: self.swap_token(0),
+                    other_input: accounts::SwapToken {
+                        user: other_input,
+                        reserve: self.pool.reserves[1],
+                    },
+                    pool_mint: self.pool.pool_mint,
+                    output_lp: self.accounts[2],
+                },
+            },
+            instruction::SsDepositBalanced {},
+        )
+    }
+
+    fn balance(&self, account: &Pubkey) -> u64 {
+        self.bank.token_account(account).amount
+    }
+}
+
+fn pool_params(mode: Mode) -> PoolParams {
+    PoolParams {
+        rate_bps: 10_000,
+        fee_bps: 0,
+        admin_fee_bps: 0,
+        mode,
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_ss_swap(
+        amount_in in 1u64..=RESERVES / 4,
+        rate_bps in 1u64..=20_000,
+        fee_bps in 0u64..=10_000,
+        admin_fee_bps in 0u64..=10_000,
+    ) {
+        let params = PoolParams {
+            rate_bps,
+            fee_bps,
+            admin_fee_bps,
+            mode: Mode::Normal,
+        };
+        let mut f = Fixture::new(params);
+        let output = f.accounts[1];
+        let amount = (amount_in as u128 * rate_bps as u128 / 10_000) as u64;
+        let fee = params.fees().trade_fee(amount).unwrap();
+        let admin_fee = params.fees().admin_trade_fee(fee).unwrap();
+        let amount_out = amount - fee;
+
+        f.bank.process_instruction(f.begin(amount_in, output, 1)).unwrap();
+        // a step may output nothing, as only the minimum amount out of the route is enforced
+        f.bank.process_instruction(f.ss_swap()).unwrap();
+        prop_assert_eq!(f.balance(&output), amount_out);
+        prop_assert_eq!(f.balance(&f.pool.admin_fees[1]), admin_fee);
+        prop_assert_eq!(f.balance(&f.pool.reserves[0]), RESERVES + amount_in);
+        prop_assert_eq!(f.balance(&f.pool.reserves[1]), RESERVES - amount_out - admin_fee);
+
+        f.bank.process_instruction(f.end(output)).unwrap();
+        prop_assert_eq!(
+            RouteResult::from_return_data(),
+            Some(RouteResult {
+                amount_in: TokenAmount { mint: f.pool.mints[0], amount: amount_in },
+                amount_out: TokenAmount { mint: f.pool.mints[1], amount: amount_out },
+            })
+        );
+    }
+
+    #[test]
+    fn test_ss_deposit_withdraw_one(
+        amount_in in 1u64..=RESERVES / 4,
+        rate_bps in 1u64..=20_000,
+        fee_bps in 0u64..10_000,
+    ) {
+        let params = PoolParams {
+            rate_bps,
+            fee_bps,
+            admin_fee_bps: 0,
+            mode: Mode::Normal,
+        };
+        let mut f = Fixture::new(params);
+        let output = f.accounts[1];
+        // LP tokens are worth one token A each
+        let amount = (amount_in as u128 * rate_bps as u128 / 10_000) as u64;
+        let amount_out = amount - params.fees().withdraw_fee(amount).unwrap();
+
+        f.bank.process_instruction(f.begin(amount_in, output, 2)).unwrap();
+        f.bank.process_instruction(f.ss_deposit_a()).unwrap();
+        prop_assert_eq!(f.balance(&f.accounts[2]), amount_in);
+        f.bank.process_instruction(f.ss_withdraw_one()).unwrap();
+        prop_assert_eq!(f.balance(&f.accounts[2]), 0);
+        prop_assert_eq!(f.balance(&output), amount_out);
+        f.bank.process_instruction(f.end(output)).unwrap();
+    }
+}
+
+#[test]
+fn test_ss_swap_lower_output() {
+    let mut f = Fixture::new(pool_params(Mode::LowerOutput { amount: 1 }));
+    let output = f.accounts[1];
+    f.bank
+        .update_token_account(output, |account| account.amount = 1_000);
+    f.bank
+        .process_instruction(f.begin(1_000, output, 1))
+        .unwrap();
+    assert_eq!(
+        f.bank.process_instruction(f.ss_swap()),
+        Err(router_error(ErrorCode::BalanceLower))
+    );
+}
+
+#[test]
+fn test_ss_swap_failure() {
+    let mut f = Fixture::new(pool_params(Mode::Fail));
+    let output = f.accounts[1];
+    f.bank
+        .process_instruction(f.begin(1_000, output, 1))
+        .unwrap();
+    assert_eq!(
+        f.bank.process_instruction(f.ss_swap()),
+        Err(mock_swap_error(mock_swap::ErrorCode::PoolFailure))
+    );
+}
+
+#[test]
+fn test_ss_swap_set_params() {
+    let mut f = Fixture::new(pool_params(Mode::Fail));
+    let output = f.accounts[1];
+    let pool = f.pool.clone();
+    f.bank.set_pool_params(&pool, pool_params(Mode::Normal));
+    f.bank
+        .process_instruction(f.begin(1_000, output, 1))
+        .unwrap();
+    f.bank.process_instruction(f.ss_swap()).unwrap();
+    assert_eq!(f.balance(&output), 1_000);
+}
+
+#[test]
+fn test_ss_deposit_balanced_other_input_owner() {
+    let mut f = Fixture::new(pool_params(Mode::Normal));
+    let owner = f.owner;
+    let lp = f.accounts[2];
+    f.bank
+        .update_token_account(f.accounts[1], |account| account.amount = 1_000);
+    let attacker = f.bank.create_wallet(1_000_000_000);
+    let foreign_input = f
+        .bank
+        .create_token_account(f.pool.mints[1], attacker, 1_000);
+    f.bank.update_token_account(foreign_input, |account| {
+        account.delegate = COption::Some(owner);
+        account.delegated_amount = 1_000;
+    });
+    f.bank.process_instruction(f.begin(1_000, lp, 1)).unwrap();
+
+    // the other input must belong to the owner, even if the owner may spend it
+    assert_eq!(
+        f.bank
+            .process_instruction(f.ss_deposit_balanced(foreign_input)),
+        Err(router_error(ErrorCode::InputOwnerMismatch))
+    );
+    f.bank
+        .process_instruction(f.ss_deposit_balanced(f.accounts[1]))
+        .unwrap();
+    assert_eq!(f.balance(&f.accounts[1]), 0);
+    assert_eq!(f.balance(&foreign_input), 1_000);
+    f.bank.process_instruction(f.end(lp)).unwrap();
+}
+
+#[test]
+fn test_ss_withdraw() {
+    let mut f = Fixture::new(pool_params(Mode::Normal));
+    let (lp, output) = (f.accounts[2], f.accounts[1]);
+    f.bank
+        .update_token_account(lp, |account| account.amount = 2_000);
+    let attacker = f.bank.create_wallet(1_000_000_000);
+    let foreign_output = f.bank.create_token_account(f.pool.mints[0], attacker, 0);
+    f.bank
+        .process_instruction(f.begin_from(lp, 2_000, output, 1))
+        .unwrap();
+
+    // the token which does not continue the route must go to the owner
+    assert_eq!(
+        f.bank
+            .process_instruction(f.ss_withdraw(f.owner, foreign_output)),
+        Err(router_error(ErrorCode::OutputOwnerMismatch))
+    );
+    f.bank
+        .process_instruction(f.ss_withdraw(f.owner, f.accounts[0]))
+        .unwrap();
+    // LP tokens are worth half of each reserve
+    assert_eq!(f.balance(&lp), 0);
+    assert_eq!(f.balance(&output), 1_000);
+    assert_eq!(f.balance(&f.accounts[0]), RESERVES + 1_000);
+    assert_eq!(f.balance(&foreign_output), 0);
+
+    f.bank.process_instruction(f.end(output)).unwrap();
+    assert_eq!(
+        RouteResult::from_return_data(),
+        Some(RouteResult {
+            amount_in: TokenAmount {
+                mint: f.pool.pool_mint,
+                amount: 2_000
+            },
+            amount_out: TokenAmount {
+                mint: f.pool.mints[1],
+                amount: 1_000
+            },
+        })
+    );
+}
+
+#[test]
+fn test_ss_withdraw_delegated() {
+    let mut f = Fixture::new(pool_params(Mode::Normal));
+    let (lp, output) = (f.accounts[2], f.accounts[1]);
+    let delegate = f.bank.create_wallet(1_000_000_000);
+    f.bank.update_token_account(lp, |account| {
+        account.amount = 2_000;
+        account.delegate = COption::Some(delegate);
+        account.delegated_amount = 2_000;
+    });
+    let delegate_output = f.bank.create_token_account(f.pool.mints[0], delegate, 0);
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::BeginDelegated {
+                continuation: f.continuation,
+                input: lp,
+                output,
+                delegate,
+            },
+            instruction::BeginDelegated {
+                amount_in: 2_000,
+                minimum_amount_out: 0,
+                num_steps: 1,
+                valid_until: None,
+            },
+        ))
+        .unwrap();
+
+    // the delegate signs the route, but the other output must still go to the owner
+    assert_eq!(
+        f.bank
+            .process_instruction(f.ss_withdraw(delegate, delegate_output)),
+        Err(router_error(ErrorCode::OutputOwnerMismatch))
+    );
+    f.bank
+        .process_instruction(f.ss_withdraw(delegate, f.accounts[0]))
+        .unwrap();
+    assert_eq!(f.balance(&output), 1_000);
+    assert_eq!(f.balance(&f.accounts[0]), RESERVES + 1_000);
+    assert_eq!(f.balance(&delegate_output), 0);
+
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::End {
+                continuation: f.continuation,
+                output,
+                owner: delegate,
+                payer: delegate,
+            },
+            instruction::End {},
+        ))
+        .unwrap();
+}
@@ -0,0 +1,288 @@
+//! Routes through Token-2022 accounts whose mint has a transfer fee:
+//! amounts are accounted by what each account received, net of the fee.
+
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_lang::InstructionData;
+use continuation_router::{accounts, instruction, ErrorCode};
+use harness::TestBank;
+use spl_token_2022::error::TokenError;
+
+/// Transfer fee of the underlying mint: 1%.
+const TRANSFER_FEE_BPS: u16 = 100;
+
+/// The router error as returned by an instruction.
+fn router_error(code: ErrorCode) -> ProgramError {
+    anchor_lang::error::Error::from(code).into()
+}
+
+/// An add-decimals wrapper of a Token-2022 mint with a transfer fee, and the
+/// token accounts of a user.
+struct Fixture {
+    bank: TestBank,
+    owner: Pubkey,
+    continuation: Pubkey,
+    wrapper: Pubkey,
+    underlying_mint: Pubkey,
+    wrapper_mint: Pubkey,
+    wrapper_underlying_tokens: Pubkey,
+    user_underlying_tokens: Pubkey,
+    user_wrapped_tokens: Pubkey,
+}
+
+impl Fixture {
+    /// Initializes a wrapper adding 3 decimals and gives the user `balance` underlying tokens.
+    fn new(balance: u64) -> Self {
+        let mut bank = TestBank::new();
+        bank.add_program(add_decimals::ID, add_decimals::entry);
+        bank.init_router(Pubkey::new_unique(), vec![add_decimals::ID]);
+        let owner = bank.create_wallet(1_000_000_000);
+        let continuation = bank.create_continuation();
+
+        let underlying_mint = bank.create_token_2022_mint(COption::None, 6, TRANSFER_FEE_BPS);
+        let (wrapper, nonce) = Pubkey::find_program_address(
+            &[b"anchor", underlying_mint.as_ref(), &[9]],
+            &add_decimals::ID,
+        );
+        let wrapper_mint = bank.create_token_2022_mint(COption::Some(wrapper), 9, 0);
+        let wrapper_underlying_tokens = bank.create_token_account(underlying_mint, wrapper, 0);
+        bank.process_instruction(harness::instruction(
+            add_decimals::ID,
+            add_decimals::accounts::InitializeWrapper {
+                wrapper,
+                wrapper_underlying_tokens,
+                underlying_mint,
+                wrapper_mint,
+                payer: owner,
+                rent: sysvar::rent::ID,
+                system_program: system_program::ID,
+            },
+            add_decimals::instruction::InitializeWrapper { _nonce: nonce },
+        ))
+        .unwrap();
+
+        let user_underlying_tokens = bank.create_token_account(underlying_mint, owner, balance);
+        let user_wrapped_tokens = bank.create_token_account(wrapper_mint, owner, 0);
+        Fixture {
+            bank,
+            owner,
+            continuation,
+            wrapper,
+            underlying_mint,
+            wrapper_mint,
+            wrapper_underlying_tokens,
+            user_underlying_tokens,
+            user_wrapped_tokens,
+        }
+    }
+
+    /// The accounts of a deposit or withdrawal of the user, followed by the
+    /// underlying accounts if `checked`.
+    fn user_stake(&self, checked: bool) -> Vec<AccountMeta> {
+        let mut metas = add_decimals::accounts::UserStake {
+            wrapper: self.wrapper,
+            wrapper_mint: self.wrapper_mint,
+            wrapper_underlying_tokens: self.wrapper_underlying_tokens,
+            owner: self.owner,
+            user_underlying_tokens: self.user_underlying_tokens,
+            user_wrapped_tokens: self.user_wrapped_tokens,
+            token_program: spl_token_2022::ID,
+        }
+        .to_account_metas(None);
+        if checked {
+            metas.push(AccountMeta::new_readonly(self.underlying_mint, false));
+            metas.push(AccountMeta::new_readonly(spl_token_2022::ID, false));
+        }
+        metas
+    }
+
+    fn deposit(&self, amount: u64, checked: bool) -> Instruction {
+        Instruction {
+            program_id: add_decimals::ID,
+            accounts: self.user_stake(checked),
+            data: add_decimals::instruction::Deposit {
+                deposit_amount: amount,
+            }
+            .data(),
+        }
+    }
+
+    fn withdraw(&self, amount: u64, checked: bool) -> Instruction {
+        Instruction {
+            program_id: add_decimals::ID,
+            accounts: self.user_stake(checked),
+            data: add_decimals::instruction::Withdraw {
+                max_burn_amount: amount,
+            }
+            .data(),
+        }
+    }
+
+    fn continuation_accounts(&self, swap_program: Pubkey) -> accounts::ContinuationAccounts {
+        accounts::ContinuationAccounts {
+            continuation: self.continuation,
+            token_program: spl_token_2022::ID,
+            swap_program,
+            config: Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID).0,
+            owner: self.owner,
+        }
+    }
+
+    fn balance(&self, account: &Pubkey) -> u64 {
+        self.bank.token_account(account).amount
+    }
+
+    /// Executes an instruction which is expected to fail without modifying any account.
+    fn assert_fails(&mut self, instruction: Instruction, err: ProgramError) {
+        let snapshot = self.bank.snapshot();
+        assert_eq!(self.bank.process_instruction(instruction), Err(err));
+        assert_eq!(self.bank.snapshot(), snapshot);
+    }
+}
+
+#[test]
+fn test_add_decimals_transfer_fee() {
+    let mut f = Fixture::new(1_000_000);
+
+    // transfers of a mint with a transfer fee must be checked against the mint
+    f.assert_fails(
+        f.deposit(1_000_000, false),
+        TokenError::MintRequiredForTransfer.into(),
+    );
+
+    // only the underlying tokens received by the wrapper are wrapped
+    f.bank
+        .process_instruction(f.deposit(1_000_000, true))
+        .unwrap();
+    assert_eq!(f.balance(&f.user_underlying_tokens), 0);
+    assert_eq!(f.balance(&f.wrapper_underlying_tokens), 990_000);
+    assert_eq!(f.balance(&f.user_wrapped_tokens), 990_000_000);
+
+    // the fee is withheld from the withdrawn tokens as well
+    f.bank
+        .process_instruction(f.withdraw(990_000_000, true))
+        .unwrap();
+    assert_eq!(f.balance(&f.user_wrapped_tokens), 0);
+    assert_eq!(f.balance(&f.wrapper_underlying_tokens), 0);
+    assert_eq!(f.balance(&f.user_underlying_tokens), 980_100);
+}
+
+#[test]
+fn test_route_transfer_fee() {
+    let mut f = Fixture::new(1_000_000);
+    let referrer = f.bank.create_wallet(0);
+    let referrer_tokens = f.bank.create_token_account(f.underlying_mint, referrer, 0);
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::BeginV2 {
+                continuation: f.continuation,
+                input: f.user_underlying_tokens,
+                output: f.user_wrapped_tokens,
+                owner: f.owner,
+            },
+            instruction::BeginV2 {
+                amount_in: 1_000_000,
+                minimum_amount_out: 980_100_000,
+                num_steps: 2,
+                valid_until: None,
+            },
+        ))
+        .unwrap();
+
+    // the fee is transferred checked against the mint, the first remaining account
+    let mut take_fee = harness::instruction(
+        continuation_router::ID,
+        accounts::TakeFeeAccounts {
+            continuation: f.continuation_accounts(spl_token_2022::ID),
+            action: accounts::TakeFee {
+                input: f.user_underlying_tokens,
+                referrer: referrer_tokens,
+            },
+        },
+        instruction::TakeFee {
+            fee_bps: 100,
+            max_fee: u64::MAX,
+        },
+    );
+    f.assert_fails(take_fee.clone(), TokenError::MintRequiredForTransfer.into());
+    take_fee
+        .accounts
+        .push(AccountMeta::new_readonly(f.underlying_mint, false));
+    f.bank.process_instruction(take_fee).unwrap();
+    assert_eq!(f.balance(&f.user_underlying_tokens), 990_000);
+    assert_eq!(f.balance(&referrer_tokens), 9_900);
+
+    // the next step is credited with what the wrapper minted for the tokens it received
+    let mut deposit = harness::instruction(
+        continuation_router::ID,
+        accounts::ADDepositAccounts {
+            continuation: f.continuation_accounts(add_decimals::ID),
+            action: accounts::ADDeposit {
+                input: f.user_underlying_tokens,
+                output: f.user_wrapped_tokens,
+            },
+        },
+        instruction::AdDeposit {},
+    );
+    deposit.accounts.extend(f.user_stake(true));
+    f.bank.process_instruction(deposit).unwrap();
+    assert_eq!(f.balance(&f.user_underlying_tokens), 0);
+    assert_eq!(f.balance(&f.user_wrapped_tokens), 980_100_000);
+
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::End {
+                continuation: f.continuation,
+                output: f.user_wrapped_tokens,
+                owner: f.owner,
+                payer: f.owner,
+            },
+            instruction::End {},
+        ))
+        .unwrap();
+    assert!(f.bank.account(&f.continuation).is_none());
+}
+
+#[test]
+fn test_invalid_token_program() {
+    let mut f = Fixture::new(1_000_000);
+    f.bank
+        .process_instruction(harness::instruction(
+            continuation_router::ID,
+            accounts::BeginV2 {
+                continuation: f.continuation,
+                input: f.user_underlying_tokens,
+                output: f.user_wrapped_tokens,
+                owner: f.owner,
+            },
+            instruction::BeginV2 {
+                amount_in: 1_000_000,
+                minimum_amount_out: 0,
+                num_steps: 1,
+                valid_until: None,
+            },
+        ))
+        .unwrap();
+
+    let mut continuation = f.continuation_accounts(add_decimals::ID);
+    continuation.token_program = add_decimals::ID;
+    let mut deposit = harness::instruction(
+        continuation_router::ID,
+        accounts::ADDepositAccounts {
+            continuation,
+            action: accounts::ADDeposit {
+                input: f.user_underlying_tokens,
+                output: f.user_wrapped_tokens,
+            },
+        },
+        instruction::AdDeposit {},
+    );
+    deposit.accounts.extend(f.user_stake(true));
+    f.assert_fails(deposit, router_error(ErrorCode::InvalidTokenProgram));
+}
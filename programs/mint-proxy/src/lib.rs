@@ -1,9 +1,11 @@
 //! Manages the minting of new Saber tokens.
+//!
+//! The token mint may be of either the SPL Token or the Token-2022 program.
 #![allow(deprecated)]
 
 use anchor_lang::solana_program;
 use anchor_lang::{prelude::*, solana_program::pubkey::PUBKEY_BYTES};
-use anchor_spl::token::{self, Mint, SetAuthority, Token, TokenAccount};
+use saber_token_interface::{self as token_interface, AuthorityType, Mint, SetAuthority};
 use vipers::prelude::*;
 
 mod proxy_seeds;
@@ -68,7 +70,9 @@ pub mod mint_proxy {
     impl MintProxy {
         pub fn new(ctx: Context<Initialize>, nonce: u8, hard_cap: u64) -> Result<Self> {
             require!(
-                ctx.accounts.token_mint.freeze_authority.is_none(),
+                Mint::load(&ctx.accounts.token_mint)?
+                    .freeze_authority
+                    .is_none(),
                 InvalidFreezeAuthority
             );
 
@@ -88,9 +92,9 @@ pub mod mint_proxy {
                 &ctx.accounts.token_mint.to_account_info(),
                 &ctx.accounts.token_program,
             );
-            token::set_authority(
+            token_interface::set_authority(
                 cpi_ctx,
-                spl_token::instruction::AuthorityType::MintTokens,
+                AuthorityType::MintTokens,
                 Some(proxy_mint_authority),
             )?;
 
@@ -155,7 +159,8 @@ pub mod mint_proxy {
             let minter_info = &mut ctx.accounts.minter_info;
             require!(minter_info.allowance >= amount, MinterAllowanceExceeded);
 
-            let new_supply = unwrap_int!(ctx.accounts.token_mint.supply.checked_add(amount),);
+            let supply = Mint::load(&ctx.accounts.token_mint)?.supply;
+            let new_supply = unwrap_int!(supply.checked_add(amount));
             require!(new_supply <= self.hard_cap, HardcapExceeded);
 
             minter_info.allowance = unwrap_int!(minter_info.allowance.checked_sub(amount));
@@ -163,14 +168,14 @@ pub mod mint_proxy {
             let proxy_signer = &[&seeds[..]];
             let cpi_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                token::MintTo {
+                token_interface::MintTo {
                     mint: ctx.accounts.token_mint.to_account_info(),
                     to: ctx.accounts.destination.to_account_info(),
                     authority: ctx.accounts.proxy_mint_authority.to_account_info(),
                 },
                 proxy_signer,
             );
-            token::mint_to(cpi_ctx, amount)?;
+            token_interface::mint_to(cpi_ctx, amount)?;
             Ok(())
         }
 
@@ -193,9 +198,9 @@ pub mod mint_proxy {
             )
             .with_signer(proxy_signer);
 
-            token::set_authority(
+            token_interface::set_authority(
                 cpi_ctx,
-                spl_token::instruction::AuthorityType::MintTokens,
+                AuthorityType::MintTokens,
                 Some(new_authority),
             )?;
 
@@ -224,11 +229,14 @@ pub struct Initialize<'info> {
     pub owner: UncheckedAccount<'info>,
 
     /// Token mint to mint.
+    /// CHECK: Loaded as a mint of either token program by [mint_proxy::MintProxy::new].
     #[account(mut)]
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: UncheckedAccount<'info>,
 
-    /// Token program.
-    pub token_program: Program<'info, Token>,
+    /// Token program of the mint, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::TokenProgramIDMismatch)]
+    pub token_program: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -237,10 +245,13 @@ pub struct SetMintAuthority<'info> {
     /// CHECK: This is actually checked
     #[account(address = PROXY_MINT_AUTHORITY)]
     pub proxy_mint_authority: UncheckedAccount<'info>,
+    /// CHECK: Set by the token program, which checks the mint authority.
     #[account(mut)]
-    pub token_mint: Account<'info, Mint>,
-    /// The [Token] program.
-    pub token_program: Program<'info, Token>,
+    pub token_mint: UncheckedAccount<'info>,
+    /// Token program of the mint, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::TokenProgramIDMismatch)]
+    pub token_program: UncheckedAccount<'info>,
 }
 
 /// Adds a minter.
@@ -318,19 +329,23 @@ pub struct PerformMint<'info> {
     pub minter: Signer<'info>,
 
     /// Token mint.
+    /// CHECK: Checked against the state, then loaded as a mint of either token program.
     #[account(mut)]
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: UncheckedAccount<'info>,
 
     /// Destination account for minted tokens.
+    /// CHECK: Checked by the token program, which only mints to accounts of the mint.
     #[account(mut)]
-    pub destination: Account<'info, TokenAccount>,
+    pub destination: UncheckedAccount<'info>,
 
     /// Minter information.
     #[account(mut, has_one = minter)]
     pub minter_info: Account<'info, MinterInfo>,
 
-    /// SPL Token program.
-    pub token_program: Program<'info, Token>,
+    /// Token program of the mint, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::TokenProgramIDMismatch)]
+    pub token_program: UncheckedAccount<'info>,
 }
 
 impl<'info> PerformMint<'info> {
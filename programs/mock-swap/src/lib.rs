@@ -0,0 +1,447 @@
+//! A mock StableSwap program for testing the router.
+//!
+//! Pools store a [SwapInfo] like StableSwap pools do, so that the router can read their
+//! reserves, but trade at a fixed rate instead of following the StableSwap curve.
+//! The swap authority of a pool is a [PoolConfig] account which holds the rate and
+//! the [Mode] of the pool, which may be set to misbehave.
+//!
+//! StableSwap instructions are not dispatched by Anchor sighashes, so they are handled
+//! by the fallback function. The program also implements [RouterActionProcessor] by
+//! swapping, allowing it to be routed through with pass-through actions.
+//!
+//! The router invokes StableSwap at its program address, so the `stable-swap-id` feature
+//! builds the mock with that address to allow deploying it in place of StableSwap.
+#![deny(clippy::unwrap_used)]
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::{prelude::*, solana_program::pubkey::PUBKEY_BYTES};
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use continuation_router::RouterActionProcessor;
+use stable_swap_client::fees::Fees;
+use stable_swap_client::instruction::SwapInstruction;
+use stable_swap_client::state::{SwapInfo, SwapTokenInfo};
+use vipers::prelude::*;
+use vipers::program_err;
+
+mod pool;
+
+#[cfg(not(feature = "stable-swap-id"))]
+declare_id!("7BE6r9HzXJJ4Wm7jEkYpqS378Kq3cgitj9NwNZnokjCt");
+
+#[cfg(feature = "stable-swap-id")]
+declare_id!("SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ");
+
+/// Denominator of all rates and fees, in basis points.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+#[allow(deprecated)]
+#[program]
+/// Mock swap program.
+pub mod mock_swap {
+    use super::*;
+
+    /// Initializes a pool.
+    ///
+    /// The swap account must be allocated with [SwapInfo::LEN] bytes and owned by this program.
+    /// The reserves and the mint authority of the pool mint must be the [PoolConfig],
+    /// which is a PDA with the seed `swap`, i.e. the StableSwap authority of the swap.
+    #[access_control(ctx.accounts.validate())]
+    pub fn initialize(ctx: Context<Initialize>, params: PoolParams) -> Result<()> {
+        params.validate()?;
+        let config = &mut ctx.accounts.config;
+        config.swap = ctx.accounts.swap.key();
+        config.admin = ctx.accounts.admin.key();
+        config.bump = unwrap_bump!(ctx, "config");
+        config.rate_bps = params.rate_bps;
+        config.mode = params.mode;
+
+        let token = |token: &InitializeToken, index| SwapTokenInfo {
+            reserves: token.reserve.key(),
+            mint: token.mint.key(),
+            admin_fees: token.admin_fees.key(),
+            index,
+        };
+        let info = SwapInfo {
+            is_initialized: true,
+            is_paused: false,
+            nonce: config.bump,
+            initial_amp_factor: 1,
+            target_amp_factor: 1,
+            start_ramp_ts: 0,
+            stop_ramp_ts: 0,
+            future_admin_deadline: 0,
+            future_admin_key: Pubkey::default(),
+            admin_key: config.admin,
+            token_a: token(&ctx.accounts.token_a, 0),
+            token_b: token(&ctx.accounts.token_b, 1),
+            pool_mint: ctx.accounts.pool_mint.key(),
+            fees: params.fees(),
+        };
+        info.pack_into_slice(&mut ctx.accounts.swap.try_borrow_mut_data()?);
+        Ok(())
+    }
+
+    /// Updates the rate, fees and mode of a pool.
+    pub fn set_params(ctx: Context<SetParams>, params: PoolParams) -> Result<()> {
+        params.validate()?;
+        let config = &mut ctx.accounts.config;
+        config.rate_bps = params.rate_bps;
+        config.mode = params.mode;
+
+        let swap = &ctx.accounts.swap;
+        let mut info = pool::load_swap_info(swap, config)?;
+        info.fees = params.fees();
+        info.pack_into_slice(&mut swap.try_borrow_mut_data()?);
+        Ok(())
+    }
+
+    /// Handles StableSwap instructions.
+    pub fn fallback<'info>(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'info>],
+        data: &[u8],
+    ) -> Result<()> {
+        let accounts = &mut &accounts[..];
+        let bumps = &mut Default::default();
+        match SwapInstruction::unpack(data)? {
+            SwapInstruction::Swap(args) => Swap::try_accounts(program_id, accounts, &[], bumps)?
+                .process(args.amount_in, args.minimum_amount_out),
+            SwapInstruction::Deposit(args) => {
+                Deposit::try_accounts(program_id, accounts, &[], bumps)?.process(
+                    args.token_a_amount,
+                    args.token_b_amount,
+                    args.min_mint_amount,
+                )
+            }
+            SwapInstruction::Withdraw(args) => {
+                Withdraw::try_accounts(program_id, accounts, &[], bumps)?.process(
+                    args.pool_token_amount,
+                    args.minimum_token_a_amount,
+                    args.minimum_token_b_amount,
+                )
+            }
+            SwapInstruction::WithdrawOne(args) => {
+                WithdrawOne::try_accounts(program_id, accounts, &[], bumps)?
+                    .process(args.pool_token_amount, args.minimum_token_amount)
+            }
+            _ => program_err!(UnsupportedInstruction),
+        }
+    }
+
+    #[state]
+    pub struct MockSwap;
+
+    impl<'info> RouterActionProcessor<'info, Swap<'info>> for MockSwap {
+        fn process_action(
+            ctx: Context<Swap>,
+            _action: u16,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> Result<()> {
+            ctx.accounts.process(amount_in, minimum_amount_out)
+        }
+    }
+}
+
+// --------------------------------
+// Instruction accounts
+// --------------------------------
+
+/// Accounts for [mock_swap::initialize].
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// The swap, which stores the [SwapInfo] of the pool.
+    /// CHECK: Checked in [Initialize::validate].
+    #[account(mut, owner = crate::ID)]
+    pub swap: UncheckedAccount<'info>,
+
+    /// The [PoolConfig], which is the authority of the swap.
+    #[account(
+        init,
+        seeds = [swap.key().as_ref()],
+        bump,
+        space = 8 + PoolConfig::LEN,
+        payer = payer
+    )]
+    pub config: Account<'info, PoolConfig>,
+
+    /// Token A of the pool.
+    pub token_a: InitializeToken<'info>,
+
+    /// Token B of the pool.
+    pub token_b: InitializeToken<'info>,
+
+    /// Mint of the LP tokens of the pool.
+    pub pool_mint: Account<'info, Mint>,
+
+    /// Account which may update the params of the pool.
+    pub admin: Signer<'info>,
+
+    /// Payer of the [PoolConfig].
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// System program. Required for initialization.
+    pub system_program: Program<'info, System>,
+}
+
+/// Token accounts of a pool.
+#[derive(Accounts)]
+pub struct InitializeToken<'info> {
+    /// Mint of the token.
+    pub mint: Account<'info, Mint>,
+    /// Reserves of the token.
+    pub reserve: Account<'info, TokenAccount>,
+    /// Account which receives the admin fees of the token.
+    pub admin_fees: Account<'info, TokenAccount>,
+}
+
+/// Accounts for [mock_swap::set_params].
+#[derive(Accounts)]
+pub struct SetParams<'info> {
+    /// The swap.
+    /// CHECK: Checked against the [PoolConfig].
+    #[account(mut)]
+    pub swap: UncheckedAccount<'info>,
+    /// The [PoolConfig] of the swap.
+    #[account(mut, has_one = admin, has_one = swap)]
+    pub config: Account<'info, PoolConfig>,
+    /// Admin of the pool.
+    pub admin: Signer<'info>,
+}
+
+/// Accounts of a StableSwap `swap` instruction.
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    /// The swap.
+    /// CHECK: Checked against the [PoolConfig].
+    pub swap: UncheckedAccount<'info>,
+    /// The [PoolConfig], which is the swap authority.
+    pub swap_authority: Account<'info, PoolConfig>,
+    /// Owner of the user token accounts.
+    pub user_authority: Signer<'info>,
+    /// Account of the user which tokens are swapped from.
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+    /// Reserve of the input token.
+    #[account(mut)]
+    pub swap_source: Account<'info, TokenAccount>,
+    /// Reserve of the output token.
+    #[account(mut)]
+    pub swap_destination: Account<'info, TokenAccount>,
+    /// Account of the user which receives the output tokens.
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+    /// Admin fee account of the output token.
+    #[account(mut)]
+    pub admin_fee_destination: Account<'info, TokenAccount>,
+    /// The SPL Token program.
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts of a StableSwap `deposit` instruction.
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    /// The swap.
+    /// CHECK: Checked against the [PoolConfig].
+    pub swap: UncheckedAccount<'info>,
+    /// The [PoolConfig], which is the swap authority.
+    pub swap_authority: Account<'info, PoolConfig>,
+    /// Owner of the user token accounts.
+    pub user_authority: Signer<'info>,
+    /// Account of the user which token A is deposited from.
+    #[account(mut)]
+    pub deposit_token_a: Account<'info, TokenAccount>,
+    /// Account of the user which token B is deposited from.
+    #[account(mut)]
+    pub deposit_token_b: Account<'info, TokenAccount>,
+    /// Reserve of token A.
+    #[account(mut)]
+    pub swap_token_a: Account<'info, TokenAccount>,
+    /// Reserve of token B.
+    #[account(mut)]
+    pub swap_token_b: Account<'info, TokenAccount>,
+    /// Mint of the LP tokens.
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+    /// Account of the user which receives the LP tokens.
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+    /// The SPL Token program.
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts of a StableSwap `withdraw` instruction.
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    /// The swap.
+    /// CHECK: Checked against the [PoolConfig].
+    pub swap: UncheckedAccount<'info>,
+    /// The [PoolConfig], which is the swap authority.
+    pub swap_authority: Account<'info, PoolConfig>,
+    /// Owner of the user token accounts.
+    pub user_authority: Signer<'info>,
+    /// Mint of the LP tokens.
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+    /// Account of the user which LP tokens are burned from.
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+    /// Reserve of token A.
+    #[account(mut)]
+    pub swap_token_a: Account<'info, TokenAccount>,
+    /// Reserve of token B.
+    #[account(mut)]
+    pub swap_token_b: Account<'info, TokenAccount>,
+    /// Account of the user which receives token A.
+    #[account(mut)]
+    pub destination_token_a: Account<'info, TokenAccount>,
+    /// Account of the user which receives token B.
+    #[account(mut)]
+    pub destination_token_b: Account<'info, TokenAccount>,
+    /// Admin fee account of token A.
+    #[account(mut)]
+    pub admin_fee_a: Account<'info, TokenAccount>,
+    /// Admin fee account of token B.
+    #[account(mut)]
+    pub admin_fee_b: Account<'info, TokenAccount>,
+    /// The SPL Token program.
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts of a StableSwap `withdraw_one` instruction.
+#[derive(Accounts)]
+pub struct WithdrawOne<'info> {
+    /// The swap.
+    /// CHECK: Checked against the [PoolConfig].
+    pub swap: UncheckedAccount<'info>,
+    /// The [PoolConfig], which is the swap authority.
+    pub swap_authority: Account<'info, PoolConfig>,
+    /// Owner of the user token accounts.
+    pub user_authority: Signer<'info>,
+    /// Mint of the LP tokens.
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+    /// Account of the user which LP tokens are burned from.
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+    /// Reserve of the token withdrawn.
+    #[account(mut)]
+    pub swap_base_token: Account<'info, TokenAccount>,
+    /// Reserve of the other token.
+    #[account(mut)]
+    pub swap_quote_token: Account<'info, TokenAccount>,
+    /// Account of the user which receives the withdrawn tokens.
+    #[account(mut)]
+    pub base_destination: Account<'info, TokenAccount>,
+    /// Admin fee account of the token withdrawn.
+    #[account(mut)]
+    pub admin_fee_destination: Account<'info, TokenAccount>,
+    /// The SPL Token program.
+    pub token_program: Program<'info, Token>,
+}
+
+// --------------------------------
+// Account structs
+// --------------------------------
+
+/// Configuration of a pool. This is the swap authority of the pool.
+#[account]
+#[derive(Default)]
+pub struct PoolConfig {
+    /// The swap.
+    pub swap: Pubkey,
+    /// Account which may update the params of the pool.
+    pub admin: Pubkey,
+    /// Bump seed.
+    pub bump: u8,
+    /// Amount of token B paid per token A, in basis points.
+    /// Token A is paid per token B at the inverse rate.
+    pub rate_bps: u64,
+    /// How the pool behaves.
+    pub mode: Mode,
+}
+
+impl PoolConfig {
+    pub const LEN: usize = PUBKEY_BYTES * 2 + 1 + 8 + Mode::LEN;
+}
+
+/// How a pool behaves.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Trades at the rate of the pool, enforcing minimum amounts out.
+    #[default]
+    Normal,
+    /// Trades at the rate of the pool, ignoring minimum amounts out.
+    IgnoreMinimum,
+    /// Takes the input, then takes `amount` tokens from the output account
+    /// instead of paying out.
+    LowerOutput {
+        /// Amount of tokens taken from the output account.
+        amount: u64,
+    },
+    /// Fails every instruction.
+    Fail,
+}
+
+impl Mode {
+    pub const LEN: usize = 1 + 8;
+}
+
+/// Parameters of a pool.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolParams {
+    /// Amount of token B paid per token A, in basis points.
+    pub rate_bps: u64,
+    /// Fee taken from the output of swaps and withdrawals, in basis points.
+    pub fee_bps: u64,
+    /// Share of the fee sent to the admin fee account, in basis points.
+    pub admin_fee_bps: u64,
+    /// How the pool behaves.
+    pub mode: Mode,
+}
+
+impl PoolParams {
+    /// Ensures the rate is non-zero and the fees are at most 100%.
+    pub fn validate(&self) -> Result<()> {
+        invariant!(self.rate_bps != 0, ZeroRate);
+        invariant!(self.fee_bps <= BPS_DENOMINATOR, InvalidFee);
+        invariant!(self.admin_fee_bps <= BPS_DENOMINATOR, InvalidFee);
+        Ok(())
+    }
+
+    /// The StableSwap fees of the pool.
+    pub fn fees(&self) -> Fees {
+        Fees {
+            admin_trade_fee_numerator: self.admin_fee_bps,
+            admin_trade_fee_denominator: BPS_DENOMINATOR,
+            admin_withdraw_fee_numerator: self.admin_fee_bps,
+            admin_withdraw_fee_denominator: BPS_DENOMINATOR,
+            trade_fee_numerator: self.fee_bps,
+            trade_fee_denominator: BPS_DENOMINATOR,
+            withdraw_fee_numerator: self.fee_bps,
+            withdraw_fee_denominator: BPS_DENOMINATOR,
+        }
+    }
+}
+
+#[error_code]
+#[derive(Eq, PartialEq)]
+pub enum ErrorCode {
+    #[msg("The pool is configured to fail.")]
+    PoolFailure,
+    #[msg("Output is below the minimum amount out.")]
+    ExceededSlippage,
+    #[msg("Accounts do not match the swap.")]
+    AccountMismatch,
+    #[msg("Swap is already initialized.")]
+    AlreadyInitialized,
+    #[msg("Rate must be non-zero.")]
+    ZeroRate,
+    #[msg("Fees may not exceed 100%.")]
+    InvalidFee,
+    #[msg("Instruction is not supported by the mock.")]
+    UnsupportedInstruction,
+}
@@ -0,0 +1,388 @@
+//! Trading logic of pools.
+
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token;
+use stable_swap_math::math::FeeCalculator;
+
+use crate::*;
+
+/// Loads the [SwapInfo] of the swap of a pool.
+pub fn load_swap_info(swap: &AccountInfo, config: &PoolConfig) -> Result<SwapInfo> {
+    assert_keys_eq!(*swap.owner, crate::ID, AccountMismatch);
+    assert_keys_eq!(swap.key(), config.swap, AccountMismatch);
+    Ok(SwapInfo::unpack(&swap.try_borrow_data()?)?)
+}
+
+impl PoolConfig {
+    /// Converts an amount of token A to token B, or of token B to token A if `a_to_b` is false.
+    pub fn convert(&self, amount: u64, a_to_b: bool) -> Option<u64> {
+        let (numerator, denominator) = if a_to_b {
+            (self.rate_bps, BPS_DENOMINATOR)
+        } else {
+            (BPS_DENOMINATOR, self.rate_bps)
+        };
+        let result = (amount as u128)
+            .checked_mul(numerator.into())?
+            .checked_div(denominator.into())?;
+        u64::try_from(result).ok()
+    }
+
+    /// Fails if the pool is in [Mode::Fail].
+    fn assert_active(&self) -> Result<()> {
+        invariant!(self.mode != Mode::Fail, PoolFailure);
+        Ok(())
+    }
+
+    /// Ensures an output meets its minimum, unless the pool ignores minimums.
+    fn check_minimum(&self, amount: u64, minimum: u64) -> Result<()> {
+        if self.mode != Mode::IgnoreMinimum {
+            invariant!(amount >= minimum, ExceededSlippage);
+        }
+        Ok(())
+    }
+
+    /// Amount of tokens to take from an output account, if the pool lowers outputs.
+    fn lowered_output(&self, destination: &TokenAccount) -> Option<u64> {
+        match self.mode {
+            Mode::LowerOutput { amount } => Some(amount.min(destination.amount)),
+            _ => None,
+        }
+    }
+}
+
+/// Token instructions of a pool instruction.
+struct Transfers<'a, 'info> {
+    token_program: &'a Program<'info, Token>,
+    config: &'a Account<'info, PoolConfig>,
+    user_authority: &'a Signer<'info>,
+}
+
+impl<'a, 'info> Transfers<'a, 'info> {
+    /// Transfers tokens of the user.
+    fn user_transfer(
+        &self,
+        from: &Account<'info, TokenAccount>,
+        to: &Account<'info, TokenAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let cpi_accounts = token::Transfer {
+            from: from.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.user_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)
+    }
+
+    /// Transfers tokens of the reserves of the pool.
+    fn pool_transfer(
+        &self,
+        from: &Account<'info, TokenAccount>,
+        to: &Account<'info, TokenAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let seeds: &[&[u8]] = &[self.config.swap.as_ref(), &[self.config.bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = token::Transfer {
+            from: from.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer(cpi_ctx, amount)
+    }
+
+    /// Pays `amount` tokens from a reserve to the user and `admin_fee` tokens to the admin.
+    ///
+    /// If the pool lowers outputs, tokens are taken from the user instead.
+    fn pay_out(
+        &self,
+        reserve: &Account<'info, TokenAccount>,
+        destination: &Account<'info, TokenAccount>,
+        admin_fees: &Account<'info, TokenAccount>,
+        amount: u64,
+        admin_fee: u64,
+    ) -> Result<()> {
+        if let Some(lowered) = self.config.lowered_output(destination) {
+            return self.user_transfer(destination, reserve, lowered);
+        }
+        self.pool_transfer(reserve, destination, amount)?;
+        self.pool_transfer(reserve, admin_fees, admin_fee)
+    }
+
+    /// Burns LP tokens of the user.
+    fn burn(
+        &self,
+        mint: &Account<'info, Mint>,
+        from: &Account<'info, TokenAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_accounts = token::Burn {
+            mint: mint.to_account_info(),
+            from: from.to_account_info(),
+            authority: self.user_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::burn(cpi_ctx, amount)
+    }
+
+    /// Mints LP tokens to the user.
+    ///
+    /// If the pool lowers outputs, LP tokens of the user are burned instead.
+    fn mint(
+        &self,
+        mint: &Account<'info, Mint>,
+        to: &Account<'info, TokenAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        if let Some(lowered) = self.config.lowered_output(to) {
+            return self.burn(mint, to, lowered);
+        }
+        let seeds: &[&[u8]] = &[self.config.swap.as_ref(), &[self.config.bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = token::MintTo {
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::mint_to(cpi_ctx, amount)
+    }
+}
+
+impl<'info> Initialize<'info> {
+    /// Validates the accounts of a new pool.
+    pub fn validate(&self) -> Result<()> {
+        let data = self.swap.try_borrow_data()?;
+        invariant!(data.len() == SwapInfo::LEN, AccountMismatch);
+        invariant!(data[0] == 0, AlreadyInitialized);
+
+        for token in [&self.token_a, &self.token_b] {
+            assert_keys_eq!(token.reserve.owner, self.config, AccountMismatch);
+            assert_keys_eq!(token.reserve.mint, token.mint, AccountMismatch);
+            assert_keys_eq!(token.admin_fees.mint, token.mint, AccountMismatch);
+        }
+        assert_keys_eq!(
+            unwrap_opt!(Option::<Pubkey>::from(self.pool_mint.mint_authority)),
+            self.config,
+            AccountMismatch
+        );
+        Ok(())
+    }
+}
+
+impl<'info> Swap<'info> {
+    /// Swaps `amount_in` tokens of the source for the other token of the pool.
+    pub fn process(&self, amount_in: u64, minimum_amount_out: u64) -> Result<()> {
+        let config = &self.swap_authority;
+        config.assert_active()?;
+        let info = load_swap_info(&self.swap, config)?;
+        let reserves = (self.swap_source.key(), self.swap_destination.key());
+        let a_to_b = if reserves == (info.token_a.reserves, info.token_b.reserves) {
+            true
+        } else if reserves == (info.token_b.reserves, info.token_a.reserves) {
+            false
+        } else {
+            return program_err!(AccountMismatch);
+        };
+        let output_token = if a_to_b { info.token_b } else { info.token_a };
+        assert_keys_eq!(
+            self.admin_fee_destination,
+            output_token.admin_fees,
+            AccountMismatch
+        );
+
+        let amount = unwrap_int!(config.convert(amount_in, a_to_b));
+        let fee = unwrap_int!(info.fees.trade_fee(amount));
+        let admin_fee = unwrap_int!(info.fees.admin_trade_fee(fee));
+        let amount_out = unwrap_int!(amount.checked_sub(fee));
+        config.check_minimum(amount_out, minimum_amount_out)?;
+
+        let transfers = Transfers {
+            token_program: &self.token_program,
+            config,
+            user_authority: &self.user_authority,
+        };
+        transfers.user_transfer(&self.source, &self.swap_source, amount_in)?;
+        transfers.pay_out(
+            &self.swap_destination,
+            &self.destination,
+            &self.admin_fee_destination,
+            amount_out,
+            admin_fee,
+        )
+    }
+}
+
+impl<'info> Deposit<'info> {
+    /// Deposits tokens for LP tokens, which are worth one token A each.
+    pub fn process(
+        &self,
+        token_a_amount: u64,
+        token_b_amount: u64,
+        min_mint_amount: u64,
+    ) -> Result<()> {
+        let config = &self.swap_authority;
+        config.assert_active()?;
+        let info = load_swap_info(&self.swap, config)?;
+        assert_keys_eq!(self.swap_token_a, info.token_a.reserves, AccountMismatch);
+        assert_keys_eq!(self.swap_token_b, info.token_b.reserves, AccountMismatch);
+        assert_keys_eq!(self.pool_mint, info.pool_mint, AccountMismatch);
+
+        let mint_amount = unwrap_int!(config
+            .convert(token_b_amount, false)
+            .and_then(|amount| amount.checked_add(token_a_amount)));
+        config.check_minimum(mint_amount, min_mint_amount)?;
+
+        let transfers = Transfers {
+            token_program: &self.token_program,
+            config,
+            user_authority: &self.user_authority,
+        };
+        transfers.user_transfer(&self.deposit_token_a, &self.swap_token_a, token_a_amount)?;
+        transfers.user_transfer(&self.deposit_token_b, &self.swap_token_b, token_b_amount)?;
+        transfers.mint(&self.pool_mint, &self.destination, mint_amount)
+    }
+}
+
+impl<'info> Withdraw<'info> {
+    /// Burns LP tokens for a share of both reserves.
+    pub fn process(
+        &self,
+        pool_token_amount: u64,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+    ) -> Result<()> {
+        let config = &self.swap_authority;
+        config.assert_active()?;
+        let info = load_swap_info(&self.swap, config)?;
+        assert_keys_eq!(self.swap_token_a, info.token_a.reserves, AccountMismatch);
+        assert_keys_eq!(self.swap_token_b, info.token_b.reserves, AccountMismatch);
+        assert_keys_eq!(self.admin_fee_a, info.token_a.admin_fees, AccountMismatch);
+        assert_keys_eq!(self.admin_fee_b, info.token_b.admin_fees, AccountMismatch);
+        assert_keys_eq!(self.pool_mint, info.pool_mint, AccountMismatch);
+
+        let supply = self.pool_mint.supply;
+        invariant!(supply != 0, AccountMismatch);
+        let share = |reserve: u64| -> Option<(u64, u64)> {
+            let amount = (reserve as u128)
+                .checked_mul(pool_token_amount.into())?
+                .checked_div(supply.into())?;
+            let amount = u64::try_from(amount).ok()?;
+            let fee = info.fees.withdraw_fee(amount)?;
+            Some((amount.checked_sub(fee)?, info.fees.admin_withdraw_fee(fee)?))
+        };
+        let (amount_a, admin_fee_a) = unwrap_int!(share(self.swap_token_a.amount));
+        let (amount_b, admin_fee_b) = unwrap_int!(share(self.swap_token_b.amount));
+        config.check_minimum(amount_a, minimum_token_a_amount)?;
+        config.check_minimum(amount_b, minimum_token_b_amount)?;
+
+        let transfers = Transfers {
+            token_program: &self.token_program,
+            config,
+            user_authority: &self.user_authority,
+        };
+        transfers.burn(&self.pool_mint, &self.source, pool_token_amount)?;
+        transfers.pay_out(
+            &self.swap_token_a,
+            &self.destination_token_a,
+            &self.admin_fee_a,
+            amount_a,
+            admin_fee_a,
+        )?;
+        transfers.pay_out(
+            &self.swap_token_b,
+            &self.destination_token_b,
+            &self.admin_fee_b,
+            amount_b,
+            admin_fee_b,
+        )
+    }
+}
+
+impl<'info> WithdrawOne<'info> {
+    /// Burns LP tokens, which are worth one token A each, for one of the tokens of the pool.
+    pub fn process(&self, pool_token_amount: u64, minimum_token_amount: u64) -> Result<()> {
+        let config = &self.swap_authority;
+        config.assert_active()?;
+        let info = load_swap_info(&self.swap, config)?;
+        let reserves = (self.swap_base_token.key(), self.swap_quote_token.key());
+        let base_token = if reserves == (info.token_a.reserves, info.token_b.reserves) {
+            info.token_a
+        } else if reserves == (info.token_b.reserves, info.token_a.reserves) {
+            info.token_b
+        } else {
+            return program_err!(AccountMismatch);
+        };
+        assert_keys_eq!(
+            self.admin_fee_destination,
+            base_token.admin_fees,
+            AccountMismatch
+        );
+        assert_keys_eq!(self.pool_mint, info.pool_mint, AccountMismatch);
+
+        let amount = if base_token.index == 0 {
+            pool_token_amount
+        } else {
+            unwrap_int!(config.convert(pool_token_amount, true))
+        };
+        let fee = unwrap_int!(info.fees.withdraw_fee(amount));
+        let admin_fee = unwrap_int!(info.fees.admin_withdraw_fee(fee));
+        let amount_out = unwrap_int!(amount.checked_sub(fee));
+        config.check_minimum(amount_out, minimum_token_amount)?;
+
+        let transfers = Transfers {
+            token_program: &self.token_program,
+            config,
+            user_authority: &self.user_authority,
+        };
+        transfers.burn(&self.pool_mint, &self.source, pool_token_amount)?;
+        transfers.pay_out(
+            &self.swap_base_token,
+            &self.base_destination,
+            &self.admin_fee_destination,
+            amount_out,
+            admin_fee,
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_config_len() {
+        let config = PoolConfig {
+            mode: Mode::LowerOutput { amount: 0 },
+            ..PoolConfig::default()
+        };
+        assert_eq!(config.try_to_vec().unwrap().len(), PoolConfig::LEN);
+    }
+
+    #[test]
+    fn test_convert() {
+        let config = PoolConfig {
+            rate_bps: 20_000,
+            ..PoolConfig::default()
+        };
+        assert_eq!(config.convert(100, true), Some(200));
+        assert_eq!(config.convert(101, false), Some(50));
+        assert_eq!(config.convert(u64::MAX, true), None);
+    }
+}
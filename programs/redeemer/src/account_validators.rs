@@ -7,13 +7,11 @@ impl<'info> Validate<'info> for CreateRedeemer<'info> {
     fn validate(&self) -> Result<()> {
         self.tokens.validate()?;
 
-        assert_keys_eq!(self.tokens.redemption_vault.owner, self.redeemer);
-        assert_keys_eq!(
-            self.tokens.redemption_vault.mint,
-            self.tokens.redemption_mint
-        );
-        invariant!(self.tokens.redemption_vault.delegate.is_none());
-        invariant!(self.tokens.redemption_vault.close_authority.is_none());
+        let redemption_vault = TokenAccount::load(&self.tokens.redemption_vault)?;
+        assert_keys_eq!(redemption_vault.owner, self.redeemer);
+        assert_keys_eq!(redemption_vault.mint, self.tokens.redemption_mint);
+        invariant!(redemption_vault.delegate.is_none());
+        invariant!(redemption_vault.close_authority.is_none());
 
         Ok(())
     }
@@ -25,7 +23,7 @@ impl<'info> Validate<'info> for RedeemTokens<'info> {
         self.tokens.validate_token_accounts(&self.redeemer)?;
 
         assert_keys_eq!(
-            self.iou_source.mint,
+            TokenAccount::load(&self.iou_source)?.mint,
             self.redeemer.iou_mint,
             "iou_source.mint"
         );
@@ -33,7 +31,7 @@ impl<'info> Validate<'info> for RedeemTokens<'info> {
         require!(self.source_authority.is_signer, Unauthorized);
         assert_keys_eq!(
             self.source_authority,
-            self.redemption_destination.owner,
+            TokenAccount::load(&self.redemption_destination)?.owner,
             "redemption_destination.owner"
         );
 
@@ -68,11 +66,20 @@ impl<'info> Validate<'info> for RedeemTokensFromMintProxy<'info> {
 
 impl<'info> Validate<'info> for ReadonlyTokenPair<'info> {
     fn validate(&self) -> Result<()> {
+        let iou_mint = Mint::load(&self.iou_mint)?;
+        let redemption_mint = Mint::load(&self.redemption_mint)?;
         require!(
-            self.iou_mint.decimals == self.redemption_mint.decimals,
+            iou_mint.decimals == redemption_mint.decimals,
             DecimalsMismatch
         );
-        assert_keys_eq!(self.redemption_vault.mint, self.redemption_mint);
+        require!(
+            iou_mint.token_program == redemption_mint.token_program,
+            InvalidTokenProgram
+        );
+        assert_keys_eq!(
+            TokenAccount::load(&self.redemption_vault)?.mint,
+            self.redemption_mint
+        );
 
         Ok(())
     }
@@ -80,7 +87,18 @@ impl<'info> Validate<'info> for ReadonlyTokenPair<'info> {
 
 impl<'info> Validate<'info> for MutTokenPair<'info> {
     fn validate(&self) -> Result<()> {
-        assert_keys_eq!(self.redemption_vault.mint, self.redemption_mint);
+        require!(
+            Mint::load(&self.iou_mint)?.token_program == self.token_program.key(),
+            InvalidTokenProgram
+        );
+        require!(
+            Mint::load(&self.redemption_mint)?.token_program == self.token_program.key(),
+            InvalidTokenProgram
+        );
+        assert_keys_eq!(
+            TokenAccount::load(&self.redemption_vault)?.mint,
+            self.redemption_mint
+        );
 
         Ok(())
     }
@@ -1,13 +1,16 @@
 //! Redeems Quarry IOU tokens for Saber tokens via the Saber mint proxy.
+//!
+//! The IOU and redemption mints may be of either the SPL Token or the Token-2022 program,
+//! but must both be of the same token program.
 #![deny(rustdoc::all)]
 #![allow(rustdoc::missing_doc_code_examples)]
 #![allow(deprecated)]
 
 use anchor_lang::prelude::*;
 use anchor_lang::{accounts::cpi_state::CpiState, solana_program::pubkey::PUBKEY_BYTES};
-use anchor_spl::token::{self, Mint, Token, TokenAccount};
 use mint_proxy::mint_proxy::MintProxy;
 use mint_proxy::MinterInfo;
+use saber_token_interface::{self as token_interface, Mint, TokenAccount};
 use vipers::prelude::*;
 
 mod account_validators;
@@ -43,11 +46,13 @@ pub mod redeemer {
             amount,
         )?;
 
-        let cpi_accounts = token::Transfer {
+        let cpi_accounts = token_interface::TransferChecked {
             from: ctx.accounts.tokens.redemption_vault.to_account_info(),
+            mint: ctx.accounts.tokens.redemption_mint.to_account_info(),
             to: ctx.accounts.redemption_destination.to_account_info(),
             authority: ctx.accounts.redeemer.to_account_info(),
         };
+        let decimals = Mint::load(&ctx.accounts.tokens.redemption_mint)?.decimals;
 
         let seeds = gen_redeemer_signer_seeds!(ctx.accounts.redeemer);
         let signer_seeds = &[&seeds[..]];
@@ -57,7 +62,7 @@ pub mod redeemer {
             cpi_accounts,
             signer_seeds,
         );
-        token::transfer(cpi_ctx, amount)?;
+        token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
         let redeemer = &ctx.accounts.redeemer;
         emit!(RedeemTokensEvent {
@@ -129,7 +134,7 @@ pub mod redeemer {
     pub fn redeem_all_tokens_from_mint_proxy(
         ctx: Context<RedeemTokensFromMintProxy>,
     ) -> Result<()> {
-        let amount = ctx.accounts.redeem_ctx.iou_source.amount;
+        let amount = TokenAccount::load(&ctx.accounts.redeem_ctx.iou_source)?.amount;
         redeem_tokens_from_mint_proxy(ctx, amount)
     }
 }
@@ -164,27 +169,35 @@ impl Redeemer {
 #[derive(Accounts)]
 pub struct MutTokenPair<'info> {
     /// Mint of the IOU token.
+    /// CHECK: Loaded as a mint of `token_program` in validation.
     #[account(mut)]
-    pub iou_mint: Account<'info, Mint>,
+    pub iou_mint: UncheckedAccount<'info>,
     /// Mint of the redemption token.
+    /// CHECK: Loaded as a mint of `token_program` in validation.
     #[account(mut)]
-    pub redemption_mint: Account<'info, Mint>,
+    pub redemption_mint: UncheckedAccount<'info>,
     /// Vault of the redemption token.
+    /// CHECK: Loaded as a token account of either token program in validation.
     #[account(mut)]
-    pub redemption_vault: Account<'info, TokenAccount>,
-    /// The spl_token program.
-    pub token_program: Program<'info, Token>,
+    pub redemption_vault: UncheckedAccount<'info>,
+    /// The token program of both mints, either SPL Token or Token-2022.
+    /// CHECK: Checked to be a token program.
+    #[account(constraint = token_interface::is_token_program(token_program.key) @ ErrorCode::InvalidTokenProgram)]
+    pub token_program: UncheckedAccount<'info>,
 }
 
 /// Token-related accounts without mutation.
 #[derive(Accounts)]
 pub struct ReadonlyTokenPair<'info> {
     /// Mint of the IOU token.
-    pub iou_mint: Account<'info, Mint>,
+    /// CHECK: Loaded as a mint of either token program in validation.
+    pub iou_mint: UncheckedAccount<'info>,
     /// Mint of the redemption token.
-    pub redemption_mint: Account<'info, Mint>,
+    /// CHECK: Loaded as a mint of either token program in validation.
+    pub redemption_mint: UncheckedAccount<'info>,
     /// Vault of the redemption token.
-    pub redemption_vault: Account<'info, TokenAccount>,
+    /// CHECK: Loaded as a token account of either token program in validation.
+    pub redemption_vault: UncheckedAccount<'info>,
 }
 
 /// Accounts for [redeemer::create_redeemer]
@@ -222,11 +235,13 @@ pub struct RedeemTokens<'info> {
     /// Authority of the source of the redeemed tokens.
     pub source_authority: Signer<'info>,
     /// Source of the IOU tokens.
+    /// CHECK: Loaded as a token account of either token program in validation.
     #[account(mut)]
-    pub iou_source: Box<Account<'info, TokenAccount>>,
+    pub iou_source: UncheckedAccount<'info>,
     /// Destination of the IOU tokens.
+    /// CHECK: Loaded as a token account of either token program in validation.
     #[account(mut)]
-    pub redemption_destination: Box<Account<'info, TokenAccount>>,
+    pub redemption_destination: UncheckedAccount<'info>,
 }
 
 /// Accounts for [redeemer::redeem_tokens_from_mint_proxy].
@@ -273,4 +288,6 @@ pub enum ErrorCode {
     Unauthorized,
     #[msg("Redemption token and IOU token decimals must match")]
     DecimalsMismatch,
+    #[msg("Token program must be the SPL Token or the Token-2022 program, and own both mints")]
+    InvalidTokenProgram,
 }
@@ -1,7 +1,7 @@
 use crate::MutTokenPair;
 use crate::Redeemer;
 use anchor_lang::prelude::*;
-use anchor_spl::token;
+use saber_token_interface as token_interface;
 use vipers::assert_keys_eq;
 
 impl<'info> MutTokenPair<'info> {
@@ -12,13 +12,13 @@ impl<'info> MutTokenPair<'info> {
         source_authority: AccountInfo<'info>,
         amount: u64,
     ) -> Result<()> {
-        let cpi_accounts = token::Burn {
+        let cpi_accounts = token_interface::Burn {
             mint: self.iou_mint.to_account_info(),
             from: source_account,
             authority: source_authority,
         };
         let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
-        token::burn(cpi_ctx, amount)?;
+        token_interface::burn(cpi_ctx, amount)?;
 
         Ok(())
     }
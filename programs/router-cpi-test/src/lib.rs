@@ -0,0 +1,180 @@
+//! Routes a swap through the continuation router on behalf of a PDA.
+//!
+//! The vault is a PDA with the seeds `"Vault"` and the key of its admin. It owns the token
+//! accounts of the route and signs every router instruction via `invoke_signed`.
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+use continuation_router::cpi::accounts as router_accounts;
+use continuation_router::program::ContinuationRouter;
+use vipers::prelude::*;
+
+declare_id!("CW5t5HZ9HYrHqFnr8QHYHRTGvzdS3GKWishvaCb8KDmS");
+
+/// Router CPI test program.
+#[program]
+pub mod router_cpi_test {
+    use super::*;
+
+    /// Swaps the vault's tokens through a StableSwap pool via the router.
+    ///
+    /// The continuation must be a signer, as it is created by this instruction.
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, VaultSwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<()> {
+        let admin = ctx.accounts.admin.key();
+        let seeds: &[&[u8]] = &[b"Vault", admin.as_ref(), &[unwrap_bump!(ctx, "vault")]];
+        let signer_seeds = &[seeds];
+        let router_program = ctx.accounts.router_program.to_account_info();
+
+        continuation_router::cpi_helpers::create_continuation(
+            ctx.accounts.admin.to_account_info(),
+            ctx.accounts.continuation.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            &[],
+        )?;
+
+        continuation_router::cpi::begin_v2(
+            CpiContext::new_with_signer(
+                router_program.clone(),
+                router_accounts::BeginV2 {
+                    continuation: ctx.accounts.continuation.to_account_info(),
+                    input: ctx.accounts.input.to_account_info(),
+                    output: ctx.accounts.output.to_account_info(),
+                    owner: ctx.accounts.vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_in,
+            minimum_amount_out,
+            1,
+            None,
+        )?;
+
+        continuation_router::cpi::ss_swap(CpiContext::new_with_signer(
+            router_program.clone(),
+            router_accounts::SSSwapAccounts {
+                continuation: router_accounts::ContinuationAccounts {
+                    continuation: ctx.accounts.continuation.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                    swap_program: ctx.accounts.swap_program.to_account_info(),
+                    config: ctx.accounts.config.to_account_info(),
+                    owner: ctx.accounts.vault.to_account_info(),
+                },
+                action: router_accounts::SSSwap {
+                    swap: router_accounts::StableSwap {
+                        swap: ctx.accounts.swap.to_account_info(),
+                        swap_authority: ctx.accounts.swap_authority.to_account_info(),
+                        clock: ctx.accounts.clock.to_account_info(),
+                    },
+                    input: router_accounts::SwapToken {
+                        user: ctx.accounts.input.to_account_info(),
+                        reserve: ctx.accounts.input_reserve.to_account_info(),
+                    },
+                    output: router_accounts::SwapOutput {
+                        user_token: router_accounts::SwapToken {
+                            user: ctx.accounts.output.to_account_info(),
+                            reserve: ctx.accounts.output_reserve.to_account_info(),
+                        },
+                        fees: ctx.accounts.output_fees.to_account_info(),
+                    },
+                },
+            },
+            signer_seeds,
+        ))?;
+
+        let result = continuation_router::cpi_helpers::end(CpiContext::new_with_signer(
+            router_program,
+            router_accounts::End {
+                continuation: ctx.accounts.continuation.to_account_info(),
+                output: ctx.accounts.output.to_account_info(),
+                owner: ctx.accounts.vault.to_account_info(),
+                payer: ctx.accounts.vault.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        emit!(VaultSwapEvent {
+            vault: ctx.accounts.vault.key(),
+            amount_in: result.amount_in.amount,
+            amount_out: result.amount_out.amount,
+        });
+        Ok(())
+    }
+}
+
+/// Accounts for [router_cpi_test::swap].
+#[derive(Accounts)]
+pub struct VaultSwap<'info> {
+    /// Admin of the vault. Pays for the continuation.
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The vault, which owns the input and output token accounts.
+    /// CHECK: PDA, only used as a signer.
+    #[account(mut, seeds = [b"Vault".as_ref(), admin.key().as_ref()], bump)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// The continuation account to create.
+    /// CHECK: Created by this instruction.
+    #[account(mut)]
+    pub continuation: Signer<'info>,
+
+    /// Input token account of the vault.
+    #[account(mut, constraint = input.owner == vault.key())]
+    pub input: Box<Account<'info, TokenAccount>>,
+
+    /// Output token account of the vault.
+    #[account(mut, constraint = output.owner == vault.key())]
+    pub output: Box<Account<'info, TokenAccount>>,
+
+    /// The StableSwap swap account.
+    /// CHECK: Checked by the swap program.
+    pub swap: UncheckedAccount<'info>,
+    /// The StableSwap swap authority.
+    /// CHECK: Checked by the swap program.
+    pub swap_authority: UncheckedAccount<'info>,
+    /// The clock.
+    pub clock: Sysvar<'info, Clock>,
+    /// The pool's reserves of the input token.
+    /// CHECK: Checked by the swap program.
+    #[account(mut)]
+    pub input_reserve: UncheckedAccount<'info>,
+    /// The pool's reserves of the output token.
+    /// CHECK: Checked by the swap program.
+    #[account(mut)]
+    pub output_reserve: UncheckedAccount<'info>,
+    /// The pool's fee account of the output token.
+    /// CHECK: Checked by the swap program.
+    #[account(mut)]
+    pub output_fees: UncheckedAccount<'info>,
+
+    /// The router's config.
+    /// CHECK: Checked by the router.
+    pub config: UncheckedAccount<'info>,
+
+    /// The continuation router program.
+    pub router_program: Program<'info, ContinuationRouter>,
+    /// The StableSwap program.
+    /// CHECK: Checked by the router.
+    pub swap_program: UncheckedAccount<'info>,
+    /// The token program.
+    pub token_program: Program<'info, Token>,
+    /// The system program.
+    pub system_program: Program<'info, System>,
+}
+
+/// Emitted when the vault swaps.
+#[event]
+pub struct VaultSwapEvent {
+    /// The vault.
+    pub vault: Pubkey,
+    /// Amount of tokens the vault swapped.
+    pub amount_in: u64,
+    /// Amount of tokens the vault received.
+    pub amount_out: u64,
+}
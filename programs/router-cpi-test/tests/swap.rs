@@ -0,0 +1,109 @@
+//! Routes a swap of a vault through the router via CPI.
+
+#[path = "../../continuation-router/tests/harness/mod.rs"]
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use harness::{Pool, TestBank};
+use mock_swap::{Mode, PoolParams};
+
+const AMOUNT_IN: u64 = 1_000_000;
+
+/// A vault holding tokens of both sides of a pool.
+struct Fixture {
+    bank: TestBank,
+    admin: Pubkey,
+    vault: Pubkey,
+    pool: Pool,
+    input: Pubkey,
+    output: Pubkey,
+}
+
+impl Fixture {
+    fn new(rate_bps: u64) -> Self {
+        let mut bank = TestBank::new();
+        bank.add_program(router_cpi_test::ID, router_cpi_test::entry);
+        bank.init_router(Pubkey::new_unique(), vec![mock_swap::ID]);
+
+        let admin = bank.create_wallet(1_000_000_000);
+        let (vault, _) =
+            Pubkey::find_program_address(&[b"Vault", admin.as_ref()], &router_cpi_test::ID);
+        let mints = [bank.create_mint(), bank.create_mint()];
+        let pool = bank.create_pool(
+            mints,
+            [AMOUNT_IN * 1_000, AMOUNT_IN * 1_000],
+            PoolParams {
+                rate_bps,
+                fee_bps: 0,
+                admin_fee_bps: 0,
+                mode: Mode::Normal,
+            },
+        );
+        let input = bank.create_token_account(mints[0], vault, AMOUNT_IN);
+        let output = bank.create_token_account(mints[1], vault, 0);
+        Fixture {
+            bank,
+            admin,
+            vault,
+            pool,
+            input,
+            output,
+        }
+    }
+
+    fn swap(&self, continuation: Pubkey, minimum_amount_out: u64) -> Instruction {
+        harness::instruction(
+            router_cpi_test::ID,
+            router_cpi_test::accounts::VaultSwap {
+                admin: self.admin,
+                vault: self.vault,
+                continuation,
+                input: self.input,
+                output: self.output,
+                swap: self.pool.swap,
+                swap_authority: self.pool.config,
+                clock: sysvar::clock::ID,
+                input_reserve: self.pool.reserves[0],
+                output_reserve: self.pool.reserves[1],
+                output_fees: self.pool.admin_fees[1],
+                config: Pubkey::find_program_address(&[b"RouterConfig"], &continuation_router::ID)
+                    .0,
+                router_program: continuation_router::ID,
+                swap_program: mock_swap::ID,
+                token_program: anchor_spl::token::ID,
+                system_program: anchor_lang::system_program::ID,
+            },
+            router_cpi_test::instruction::Swap {
+                amount_in: AMOUNT_IN,
+                minimum_amount_out,
+            },
+        )
+    }
+}
+
+#[test]
+fn test_swap() {
+    let mut f = Fixture::new(20_000);
+    let continuation = Pubkey::new_unique();
+    f.bank
+        .process_instruction(f.swap(continuation, 2 * AMOUNT_IN))
+        .unwrap();
+
+    assert_eq!(f.bank.token_account(&f.input).amount, 0);
+    assert_eq!(f.bank.token_account(&f.output).amount, 2 * AMOUNT_IN);
+    // the continuation is closed at the end of the route
+    assert!(f.bank.account(&continuation).is_none());
+}
+
+#[test]
+fn test_swap_minimum_amount_out() {
+    let mut f = Fixture::new(20_000);
+    assert_eq!(
+        f.bank
+            .process_instruction(f.swap(Pubkey::new_unique(), 2 * AMOUNT_IN + 1)),
+        Err(anchor_lang::error::Error::from(mock_swap::ErrorCode::ExceededSlippage).into())
+    );
+    assert_eq!(f.bank.token_account(&f.input).amount, AMOUNT_IN);
+}
@@ -66,6 +66,11 @@ export const SABER_CODERS = buildCoderMap<{
   Redeemer: RedeemerTypes;
 }>(SABER_IDLS, SABER_ADDRESSES);
 
+/**
+ * Size of a `ContinuationV2` account created for `beginV2`, including the discriminator.
+ */
+export const CONTINUATION_ACCOUNT_SIZE = 8 + 600;
+
 /**
  * Mint of the Saber IOU token.
  */
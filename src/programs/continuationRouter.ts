@@ -8,6 +8,7 @@ export type ContinuationRouterTypes = AnchorTypes<
   ContinuationRouterIDL,
   {
     continuation: ContinuationState;
+    continuationV2: ContinuationV2State;
   }
 >;
 
@@ -18,7 +19,10 @@ export type ContinuationRouterEvents = ContinuationRouterTypes["Events"];
 export type ContinuationRouterAccounts = ContinuationRouterTypes["Accounts"];
 
 export type ContinuationState = ContinuationRouterAccounts["Continuation"];
+export type ContinuationV2State = ContinuationRouterAccounts["ContinuationV2"];
 
 export type SwapCompleteEvent = ContinuationRouterEvents["SwapCompleteEvent"];
+export type SwapExactOutCompleteEvent =
+  ContinuationRouterEvents["SwapExactOutCompleteEvent"];
 
 export { ContinuationRouterJSON } from "../idls/continuation_router";
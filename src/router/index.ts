@@ -1,4 +1,5 @@
 export * from "./entities";
+export * from "./pda";
 export * from "./router";
 export * from "./utils/sortedInsert";
 export * from "./wrappers";
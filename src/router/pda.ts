@@ -0,0 +1,24 @@
+import { utils } from "@project-serum/anchor";
+import { PublicKey } from "@solana/web3.js";
+
+import { SABER_ADDRESSES } from "../constants";
+
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey(
+  "BPFLoaderUpgradeab1e11111111111111111111111"
+);
+
+export const findRouterConfigKey = async (): Promise<[PublicKey, number]> => {
+  return PublicKey.findProgramAddress(
+    [utils.bytes.utf8.encode("RouterConfig")],
+    SABER_ADDRESSES.ContinuationRouter
+  );
+};
+
+export const findRouterProgramDataKey = async (): Promise<
+  [PublicKey, number]
+> => {
+  return PublicKey.findProgramAddress(
+    [SABER_ADDRESSES.ContinuationRouter.toBuffer()],
+    BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+};
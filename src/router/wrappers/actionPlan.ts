@@ -19,10 +19,12 @@ import {
 } from "@solana/web3.js";
 import invariant from "tiny-invariant";
 
+import { CONTINUATION_ACCOUNT_SIZE } from "../../constants";
 import type {
   AddDecimalsProgram,
   ContinuationRouterProgram,
 } from "../../programs";
+import { findRouterConfigKey } from "../pda";
 import type { Router } from "../router";
 import { WrappedTokenActions } from "./wrappedTokenActions";
 
@@ -79,12 +81,14 @@ export class ActionPlan {
 
     const continuationAddr = continuationKP.publicKey;
     const createIX = await this.program.account.continuation.createInstruction(
-      continuationKP
+      continuationKP,
+      CONTINUATION_ACCOUNT_SIZE
     );
     const begin = this.program.instruction.beginV2(
       this.inputAmount.toU64(),
       this.minimumAmountOut.toU64(),
       this.actions.length,
+      null,
       {
         accounts: {
           continuation: continuationKP.publicKey,
@@ -173,6 +177,7 @@ export class ActionPlan {
       this.inputAmount.toU64(),
       this.minimumAmountOut.toU64(),
       this.actions.length,
+      null,
       {
         accounts: {
           continuation: continuationAddr,
@@ -314,6 +319,7 @@ const makeSSInstruction = async (
     continuation,
     tokenProgram: TOKEN_PROGRAM_ID,
     swapProgram: SWAP_PROGRAM_ID,
+    config: (await findRouterConfigKey())[0],
     owner: user,
   };
 
@@ -436,6 +442,7 @@ const makeSSInstructionWithEmpheralInput = async (
     continuation,
     tokenProgram: TOKEN_PROGRAM_ID,
     swapProgram: SWAP_PROGRAM_ID,
+    config: (await findRouterConfigKey())[0],
     owner: user,
   };
 
@@ -577,6 +584,7 @@ const makeADInstruction = async (
     continuation,
     tokenProgram: TOKEN_PROGRAM_ID,
     swapProgram: addDecimals.programId,
+    config: (await findRouterConfigKey())[0],
     owner: user,
   };
 
@@ -2,7 +2,7 @@
 
 import * as anchor from "@project-serum/anchor";
 import { expectTX } from "@saberhq/chai-solana";
-import { SignerWallet } from "@saberhq/solana-contrib";
+import { SignerWallet, TransactionEnvelope } from "@saberhq/solana-contrib";
 import type { StableSwap } from "@saberhq/stableswap-sdk";
 import { deployNewSwap, SWAP_PROGRAM_ID } from "@saberhq/stableswap-sdk";
 import * as serumCmn from "@saberhq/token-utils";
@@ -22,7 +22,12 @@ import { expect } from "chai";
 import invariant from "tiny-invariant";
 
 import type { SwapCompleteEvent } from "../src";
-import { SABER_CODERS, WrappedToken } from "../src";
+import {
+  findRouterConfigKey,
+  findRouterProgramDataKey,
+  SABER_CODERS,
+  WrappedToken,
+} from "../src";
 import { WrappedTokenActions } from "../src/router/wrappers/wrappedTokenActions";
 import { initATA } from "./utils";
 import {
@@ -213,6 +218,31 @@ describe("Router", () => {
       }),
     });
     bcSwap = bcLPSwap;
+
+    console.log("Approving swap programs");
+    const router = sdk.programs.ContinuationRouter;
+    const [config] = await findRouterConfigKey();
+    const [programData] = await findRouterProgramDataKey();
+    await expectTX(
+      new TransactionEnvelope(provider, [
+        router.instruction.initializeConfig({
+          accounts: {
+            config,
+            admin: provider.wallet.publicKey,
+            programData,
+            payer: provider.wallet.publicKey,
+            systemProgram: web3.SystemProgram.programId,
+          },
+        }),
+        ...[SWAP_PROGRAM_ID, sdk.programs.AddDecimals.programId].map(
+          (program) =>
+            router.instruction.addApprovedProgram(program, {
+              accounts: { config, admin: provider.wallet.publicKey },
+            })
+        ),
+      ]),
+      "approve swap programs"
+    ).to.be.fulfilled;
   });
 
   let user: anchor.web3.Keypair;